@@ -1,42 +1,1680 @@
-use std::io::{Stdin, Stdout, Write};
+use std::io::{self, Read, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
 
 use termion::{
-    event::{Event, Key},
+    event::Event,
     input::{MouseTerminal, TermRead},
     raw::RawTerminal,
 };
 
-use crate::buffer::{buffer::ViewBuffer, editor::EditorBuffer};
+use crate::buffer::{
+    agenda::{AgendaAction, AgendaBuffer},
+    backlinks::{BacklinksAction, BacklinksBuffer},
+    buffer::ViewBuffer,
+    drawer::{DrawerAction, DrawerBuffer},
+    editor::EditorBuffer,
+    outline::{OutlineAction, OutlineBuffer},
+    picker::{PickerAction, PickerBuffer, PickerItem},
+    preview::PreviewBuffer,
+    prompt::{PromptAction, PromptBuffer},
+    status::{StatusBuffer, StatusInfo},
+    tab::{Tab, TabBuffer},
+    tasks::{TasksAction, TasksBuffer},
+};
+use crate::note::backlink::{backlinks, mention_names, unlinked_mentions};
+use crate::note::daily::parse_day;
+use crate::note::link::{find_heading, Link};
+use crate::note::outline::headings;
+use crate::note::rename::RenamePlan;
+use crate::note::task::{agenda, complete_task, open_tasks, postpone_task, Task};
+use crate::note::template::{self, list_templates};
+use crate::note::time::DateTime;
+use crate::note::vault::Vault;
+use crate::note::walk::{walk_notes_async, NoteFile};
+use crate::search::index::{Index, SearchHit};
+use unicode_width::UnicodeWidthChar;
 
+use super::command::{
+    complete_command, default_bindings, event_name, parse_command, Action, Binding, Command,
+};
 use super::config::Config;
+use super::layout::{Direction, Layout, Node, SplitSize};
+
+const HISTORY_LIMIT: usize = 100; // locations to go back
+const MENTION_CANDIDATES: usize = 200; // notes searched for unlinked mentions
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaneId {
+    Drawer,
+    Tabs,
+    Editor,
+    Picker,
+    Preview,
+    Backlinks,
+    Tasks,
+    Agenda,
+    Outline,
+    Status,
+    Prompt,
+}
+
+// what the app shows first
+#[derive(Debug, Clone, PartialEq)]
+pub enum Startup {
+    Vault,         // the drawer of the vault and the latest note
+    File(PathBuf), // only the file
+    Text(String),  // the vault and a new note with the text
+}
+
+// what the opened picker is choosing
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PickerKind {
+    Buffer,
+    Palette,
+    File,
+    SearchResult,
+    Template,
+}
+
+// a command listed in the palette
+#[derive(Debug, Clone)]
+enum PaletteEntry {
+    App(Action),
+    Pane(PaneId, Event),
+}
+
+// what the opened prompt is asking
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PromptKind {
+    Command,
+    Search,
+    SearchNotes,
+    TemplateValue, // of a variable of the template
+    Confirm(Confirm),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Confirm {
+    CloseBuffer,
+    Quit,
+    CreateNote, // of the followed link
+    Rename,     // and rewrite the links shown in the preview
+}
+
+// a note being made from a template, waiting for the values of its variables
+#[derive(Debug, Clone)]
+struct TemplateFill {
+    template: String,
+    values: Vec<(String, String)>,
+    missing: Vec<String>, // asked in order
+}
+
+// a place in a note, kept in the history of followed links
+#[derive(Debug, Clone, PartialEq)]
+struct Location {
+    path: PathBuf,
+    x: usize,
+    y: usize,
+}
 
 pub struct App {
     pub config: Config,
-    editor_buffer: EditorBuffer,
-    // drawer_buffer: todo!() // wip wip
+    vault: Vault,
+    editors: Vec<EditorBuffer>,
+    current: usize,
+    drawer_buffer: DrawerBuffer,
+    tab_buffer: TabBuffer,
+    status_buffer: StatusBuffer,
+    picker_buffer: PickerBuffer,
+    picker: Option<PickerKind>,
+    picker_return: PaneId, // focus before the picker is opened
+    palette: Vec<PaletteEntry>,
+    preview_buffer: PreviewBuffer,
+    backlinks_buffer: BacklinksBuffer,
+    tasks_buffer: TasksBuffer,
+    agenda_buffer: AgendaBuffer,
+    outline_buffer: OutlineBuffer,
+    finder_files: Vec<NoteFile>,
+    finder_rx: Option<Receiver<Vec<NoteFile>>>, // files from the indexing thread
+    index: Option<Index>,                       // loaded at the first search
+    search_hits: Vec<SearchHit>,
+    back: Vec<Location>, // history of followed links
+    forward: Vec<Location>,
+    pending_link: Option<Link>, // to a missing note, waiting for the confirm
+    pending_rename: Option<RenamePlan>,
+    templates: Vec<PathBuf>, // in the template picker
+    new_title: String,       // typed with `new`, while choosing the template
+    pending_template: Option<TemplateFill>,
+    bindings: Vec<Binding>,
+    prompt_buffer: PromptBuffer,
+    prompt: Option<PromptKind>,
+    prompt_return: PaneId, // focus before the prompt is opened
+    last_search: String,
+    quit: bool,
+    layout: Layout<PaneId>,
+    width: usize,
+    height: usize,
 }
 
 impl App {
-    pub fn setup() -> Self {
-        App {
-            config: Config {},
-            editor_buffer: EditorBuffer::new(""),
+    pub fn setup(config: Config, startup: Startup) -> io::Result<Self> {
+        let vault = Vault::open(&config.notes_dir, &config.extensions, config.name_style)?;
+        let drawer_buffer = DrawerBuffer::new(&config.notes_dir);
+        let mut layout = Layout::new(
+            Node::join(
+                Direction::Vertical,
+                SplitSize::Second(1),
+                Node::join(
+                    Direction::Vertical,
+                    SplitSize::Second(1),
+                    Node::split(
+                        Direction::Horizontal,
+                        SplitSize::First(config.drawer_width),
+                        Node::Pane(PaneId::Drawer),
+                        Node::join(
+                            Direction::Vertical,
+                            SplitSize::First(1),
+                            Node::Pane(PaneId::Tabs),
+                            Node::Pane(PaneId::Editor),
+                        ),
+                    ),
+                    Node::Pane(PaneId::Status),
+                ),
+                Node::Pane(PaneId::Prompt),
+            ),
+            PaneId::Drawer,
+        );
+        layout.set_focusable(PaneId::Tabs, false);
+        layout.set_focusable(PaneId::Status, false);
+        layout.set_focusable(PaneId::Preview, false);
+        layout.set_visible(PaneId::Prompt, false);
+        let mut app = App {
+            config,
+            vault,
+            editors: vec![EditorBuffer::new("")],
+            current: 0,
+            drawer_buffer,
+            tab_buffer: TabBuffer::new(),
+            status_buffer: StatusBuffer::new(),
+            picker_buffer: PickerBuffer::new("", vec![]),
+            picker: None,
+            picker_return: PaneId::Editor,
+            palette: vec![],
+            preview_buffer: PreviewBuffer::new(),
+            backlinks_buffer: BacklinksBuffer::new(),
+            tasks_buffer: TasksBuffer::new(),
+            agenda_buffer: AgendaBuffer::new(),
+            outline_buffer: OutlineBuffer::new(),
+            finder_files: vec![],
+            finder_rx: None,
+            index: None,
+            search_hits: vec![],
+            back: vec![],
+            forward: vec![],
+            pending_link: None,
+            pending_rename: None,
+            templates: vec![],
+            new_title: String::new(),
+            pending_template: None,
+            bindings: default_bindings(),
+            prompt_buffer: PromptBuffer::new(),
+            prompt: None,
+            prompt_return: PaneId::Editor,
+            last_search: String::new(),
+            quit: false,
+            layout,
+            width: 100,
+            height: 40,
+        };
+        app.editors[0] = app.scratch_editor();
+        app.notes_changed();
+        match startup {
+            Startup::Vault => {
+                if let Some(latest) = app.vault.latest() {
+                    let path = app.vault.root.join(&latest.path);
+                    app.open_path(&path);
+                    app.drawer_buffer.select_path(&path);
+                }
+                let count = app.vault.notes().len();
+                app.status_buffer.set_message(&format!(
+                    "{} notes in {}",
+                    count,
+                    app.vault.root.to_string_lossy()
+                ));
+            }
+            Startup::Text(text) => {
+                app.add_editor(EditorBuffer::new(&text));
+                app.layout.focus(PaneId::Editor);
+            }
+            Startup::File(path) => {
+                app.open_path(&path);
+                app.layout.set_visible(PaneId::Drawer, false);
+                app.layout.focus(PaneId::Editor);
+            }
         }
+        Ok(app)
     }
 
-    pub fn run(&mut self, stdin: Stdin, mut stdout: MouseTerminal<RawTerminal<Stdout>>) {
+    // input is stdin, or the tty when stdin is piped
+    pub fn run(
+        &mut self,
+        input: impl Read + Send + 'static,
+        mut stdout: MouseTerminal<RawTerminal<Stdout>>,
+    ) {
         write!(stdout, "{}", termion::clear::All).unwrap();
-        for event in stdin.events() {
-            let event = event.unwrap();
-            match event {
-                Event::Key(Key::Ctrl('q')) => break,
-                Event::Key(Key::Ctrl('n')) => {
-                    // wip wip
-                    // move view focus
+        self.refresh();
+        self.render(&mut stdout);
+        // read keys in another thread, so that background work can be shown while waiting
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for event in input.events() {
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        loop {
+            match rx.recv_timeout(Duration::from_millis(50)) {
+                Ok(event) => {
+                    let event = event.unwrap();
+                    if let Event::Key(_) = event {
+                        self.status_buffer.clear_message();
+                    }
+                    self.handle_event(event);
+                    if self.quit {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if !self.poll_finder() {
+                        continue;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            self.render(&mut stdout);
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        // the prompt and the picker take every key while they are open
+        if matches!(self.layout.focus, PaneId::Prompt | PaneId::Picker) {
+            self.update(event);
+            self.take_actions();
+            self.update_preview();
+            return;
+        }
+        let action = self
+            .bindings
+            .iter()
+            .find(|b| b.event == event)
+            .map(|b| b.action);
+        match action {
+            Some(action) => self.run_action(action),
+            None => {
+                self.update(event);
+                self.take_actions();
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Palette => self.open_palette(),
+            Action::CommandPrompt => self.open_prompt(PromptKind::Command, ": ", ""),
+            Action::NewNote => self.open_prompt(PromptKind::Command, ": ", "new "),
+            Action::Open => self.open_prompt(PromptKind::Command, ": ", "open "),
+            Action::FindFile => self.open_file_finder(),
+            Action::SearchNotes => self.open_prompt(PromptKind::SearchNotes, "search notes: ", ""),
+            Action::FollowLink => self.follow_link(),
+            Action::GoBack => self.go_back(),
+            Action::GoForward => self.go_forward(),
+            Action::Today => self.open_daily(&DateTime::now()),
+            Action::PrevDay => self.step_daily(-1),
+            Action::NextDay => self.step_daily(1),
+            Action::Goto => self.open_prompt(PromptKind::Command, ": ", "goto "),
+            Action::Search => {
+                let query = self.last_search.clone();
+                self.open_prompt(PromptKind::Search, "/", &query);
+            }
+            Action::Save => self.save_editor(),
+            Action::Close => self.close_editor(),
+            Action::Quit => self.request_quit(),
+            Action::NextBuffer => self.switch_editor((self.current + 1) % self.editors.len()),
+            Action::PrevBuffer => {
+                let len = self.editors.len();
+                self.switch_editor((self.current + len - 1) % len);
+            }
+            Action::BufferList => self.open_buffer_picker(),
+            Action::ToggleDrawer => {
+                self.layout.toggle(PaneId::Drawer);
+                self.layout.focus(PaneId::Editor);
+            }
+            Action::ToggleBacklinks => {
+                if self.layout.is_visible(PaneId::Backlinks) {
+                    self.layout.remove(PaneId::Backlinks);
+                    self.layout.focus(PaneId::Editor);
+                } else {
+                    self.layout.insert(
+                        PaneId::Editor,
+                        PaneId::Backlinks,
+                        Direction::Horizontal,
+                        SplitSize::Second(40),
+                    );
+                    self.update_backlinks(true);
+                    self.layout.focus(PaneId::Backlinks);
+                }
+            }
+            Action::ToggleTasks => {
+                if self.layout.is_visible(PaneId::Tasks) {
+                    self.layout.remove(PaneId::Tasks);
+                    self.layout.focus(PaneId::Editor);
+                } else {
+                    self.show_tasks(None);
+                }
+            }
+            Action::ToggleOutline => {
+                if self.layout.is_visible(PaneId::Outline) {
+                    self.layout.remove(PaneId::Outline);
+                    self.layout.focus(PaneId::Editor);
+                } else {
+                    self.layout.insert(
+                        PaneId::Editor,
+                        PaneId::Outline,
+                        Direction::Horizontal,
+                        SplitSize::Second(30),
+                    );
+                    self.update_outline(true);
+                    self.layout.focus(PaneId::Outline);
+                }
+            }
+            Action::ToggleAgenda => {
+                if self.layout.is_visible(PaneId::Agenda) {
+                    self.layout.remove(PaneId::Agenda);
+                    self.layout.focus(PaneId::Editor);
+                } else {
+                    self.layout.insert(
+                        PaneId::Editor,
+                        PaneId::Agenda,
+                        Direction::Horizontal,
+                        SplitSize::Second(40),
+                    );
+                    self.update_agenda();
+                    self.layout.focus(PaneId::Agenda);
+                }
+            }
+            Action::FocusNext => self.layout.focus_next(),
+            Action::GrowWidth => self
+                .layout
+                .resize(self.layout.focus, Direction::Horizontal, 1),
+            Action::ShrinkWidth => self
+                .layout
+                .resize(self.layout.focus, Direction::Horizontal, -1),
+            Action::GrowHeight => self
+                .layout
+                .resize(self.layout.focus, Direction::Vertical, 1),
+            Action::ShrinkHeight => self
+                .layout
+                .resize(self.layout.focus, Direction::Vertical, -1),
+        }
+        self.refresh();
+    }
+
+    fn take_actions(&mut self) {
+        if let Some(action) = self.drawer_buffer.take_action() {
+            self.handle_drawer_action(action);
+        }
+        if let Some(action) = self.picker_buffer.take_action() {
+            self.handle_picker_action(action);
+        }
+        if let Some(action) = self.prompt_buffer.take_action() {
+            self.handle_prompt_action(action);
+        }
+        if let Some(BacklinksAction::Open(path, line)) = self.backlinks_buffer.take_action() {
+            self.push_location();
+            self.open_path(&self.vault.root.join(path));
+            self.editors[self.current].text.set_cursor(0, line);
+            self.layout.focus(PaneId::Editor);
+            self.refresh();
+        }
+        if let Some(TasksAction::Open(path, line)) = self.tasks_buffer.take_action() {
+            self.push_location();
+            self.open_path(&self.vault.root.join(path));
+            self.editors[self.current].text.set_cursor(0, line);
+            self.layout.focus(PaneId::Editor);
+            self.refresh();
+        }
+        if let Some(action) = self.agenda_buffer.take_action() {
+            self.handle_agenda_action(action);
+        }
+        if let Some(action) = self.outline_buffer.take_action() {
+            self.handle_outline_action(action);
+        }
+    }
+
+    fn handle_outline_action(&mut self, action: OutlineAction) {
+        let editor = &mut self.editors[self.current];
+        match action {
+            OutlineAction::Move(..) | OutlineAction::Shift(..)
+                if editor.text.cursor_count() > 1 =>
+            {
+                self.status_buffer
+                    .set_error("sections can't be moved with several cursors");
+            }
+            OutlineAction::Jump(line) => {
+                self.push_location();
+                self.editors[self.current].text.set_cursor(0, line);
+                self.layout.focus(PaneId::Editor);
+            }
+            OutlineAction::Move(line, up) => {
+                if !editor.move_section(line, up) {
+                    let message = format!(
+                        "no section {} under the same heading",
+                        if up { "above" } else { "below" }
+                    );
+                    self.status_buffer.set_error(&message);
+                }
+            }
+            OutlineAction::Shift(line, delta) => {
+                if !editor.shift_section(line, delta) {
+                    self.status_buffer.set_error("headings are levels 1 to 6");
+                }
+            }
+        }
+        self.update_outline(true);
+        self.refresh();
+    }
+
+    fn handle_agenda_action(&mut self, action: AgendaAction) {
+        match action {
+            AgendaAction::Open(path, line) => {
+                self.push_location();
+                self.open_path(&self.vault.root.join(path));
+                self.editors[self.current].text.set_cursor(0, line);
+                self.layout.focus(PaneId::Editor);
+            }
+            AgendaAction::Complete(task) => {
+                self.edit_task(&task, |root| {
+                    complete_task(root, &task).map(|_| format!("Completed: {}", task.text))
+                });
+            }
+            AgendaAction::Postpone(task, days) => {
+                self.edit_task(&task, |root| {
+                    postpone_task(root, &task, days, &DateTime::today())
+                        .map(|date| format!("Postponed to {}", date.format("%Y-%m-%d")))
+                });
+            }
+        }
+        self.refresh();
+    }
+
+    // change the line of the task in its note. an open buffer of the note is
+    // read again, and one with unsaved changes has to be saved first.
+    fn edit_task(&mut self, task: &Task, edit: impl FnOnce(&Path) -> io::Result<String>) {
+        let path = self.vault.root.join(&task.path);
+        let open = self
+            .editors
+            .iter()
+            .position(|e| e.path.as_ref() == Some(&path));
+        if let Some(editor) = open.map(|i| &self.editors[i]).filter(|e| e.is_modified()) {
+            let message = format!("save {} before changing its tasks", editor.name());
+            self.status_buffer.set_error(&message);
+            return;
+        }
+        let message = match edit(&self.vault.root).and_then(|m| self.vault.update(&path).map(|_| m))
+        {
+            Ok(message) => message,
+            Err(e) => {
+                self.status_buffer.set_error(&e.to_string());
+                return;
+            }
+        };
+        if let Some(i) = open {
+            self.reload_editor(i);
+        }
+        self.notes_changed();
+        self.status_buffer.set_message(&message);
+    }
+
+    fn handle_drawer_action(&mut self, action: DrawerAction) {
+        let changed = matches!(action, DrawerAction::Deleted(_));
+        match action {
+            DrawerAction::Open(path) => {
+                self.open_path(&path);
+                self.layout.focus(PaneId::Editor);
+            }
+            DrawerAction::Rename(from, to) => self.request_rename(&from, &to),
+            DrawerAction::Deleted(path) => {
+                // keep modified buffers, so that they can be saved again
+                let current = self.editors[self.current].path.clone();
+                self.editors.retain(|e| {
+                    e.is_modified() || !e.path.as_ref().is_some_and(|p| p.starts_with(&path))
+                });
+                if self.editors.is_empty() {
+                    self.editors.push(self.scratch_editor());
+                }
+                self.current = self
+                    .editors
+                    .iter()
+                    .position(|e| e.path == current)
+                    .unwrap_or(0);
+            }
+        }
+        // files were deleted
+        if changed {
+            let result = self.vault.refresh().and_then(|_| match &mut self.index {
+                Some(index) => index.sync(&self.vault),
+                None => Ok(()),
+            });
+            if let Err(e) = result {
+                self.status_buffer.set_error(&e.to_string());
+            }
+            self.notes_changed();
+        }
+        self.refresh();
+    }
+
+    fn handle_picker_action(&mut self, action: PickerAction) {
+        let kind = self.picker.take();
+        self.layout.remove(PaneId::Picker);
+        self.layout.remove(PaneId::Preview);
+        self.layout.focus(self.picker_return);
+        self.preview_buffer.clear();
+        self.finder_rx = None; // stops the indexing thread
+        match (kind, action) {
+            (Some(PickerKind::Buffer), PickerAction::Select(i)) => {
+                self.current = i;
+                self.layout.focus(PaneId::Editor);
+            }
+            (Some(PickerKind::Palette), PickerAction::Select(i)) => {
+                match self.palette[i].clone() {
+                    PaletteEntry::App(action) => self.run_action(action),
+                    PaletteEntry::Pane(id, event) => {
+                        // run the command by passing its key to the pane
+                        self.layout.focus(id);
+                        self.update(event);
+                        self.take_actions();
+                    }
+                }
+            }
+            (Some(PickerKind::SearchResult), PickerAction::Select(i)) => {
+                let hit = self.search_hits[i].clone();
+                let path = self.vault.root.join(&hit.path);
+                self.open_path(&path);
+                self.editors[self.current]
+                    .text
+                    .set_cursor(hit.column, hit.line);
+                self.layout.focus(PaneId::Editor);
+            }
+            (Some(PickerKind::Template), PickerAction::Select(0)) => {
+                let title = std::mem::take(&mut self.new_title);
+                self.create_plain_note(&title);
+            }
+            (Some(PickerKind::Template), PickerAction::Select(i)) => {
+                let title = std::mem::take(&mut self.new_title);
+                match std::fs::read_to_string(&self.templates[i - 1]) {
+                    Ok(text) => {
+                        let mut missing = template::variables(&text);
+                        let mut values = vec![];
+                        if !title.is_empty() {
+                            missing.retain(|name| name != "title");
+                            values.push((String::from("title"), title));
+                        }
+                        self.pending_template = Some(TemplateFill {
+                            template: text,
+                            values,
+                            missing,
+                        });
+                        self.fill_template();
+                    }
+                    Err(e) => self.status_buffer.set_error(&e.to_string()),
+                }
+            }
+            (Some(PickerKind::File), PickerAction::Select(i)) => {
+                let path = self.config.notes_dir.join(&self.finder_files[i].path);
+                self.open_path(&path);
+                self.drawer_buffer.select_path(&path);
+                self.layout.focus(PaneId::Editor);
+            }
+            _ => {}
+        }
+        self.refresh();
+    }
+
+    fn handle_prompt_action(&mut self, action: PromptAction) {
+        let kind = self.prompt;
+        if let PromptAction::Complete(text) = action {
+            if kind == Some(PromptKind::Command) {
+                let completions = complete_command(&text, &self.config.notes_dir);
+                self.prompt_buffer.set_completions(completions);
+            }
+            self.refresh();
+            return;
+        }
+        self.close_prompt();
+        if kind == Some(PromptKind::Confirm(Confirm::Rename)) {
+            self.layout.remove(PaneId::Preview);
+            self.preview_buffer.clear();
+        }
+        let PromptAction::Submit(text) = action else {
+            self.pending_rename = None;
+            self.pending_template = None;
+            return;
+        };
+        match kind {
+            Some(PromptKind::Command) => {
+                let result = parse_command(&text).and_then(|c| self.execute(c));
+                if let Err(e) = result {
+                    self.status_buffer.set_error(&e);
+                }
+            }
+            Some(PromptKind::Search) => self.search(&text),
+            Some(PromptKind::SearchNotes) => self.search_notes(&text),
+            Some(PromptKind::TemplateValue) => {
+                if let Some(fill) = &mut self.pending_template {
+                    let name = fill.missing.remove(0);
+                    fill.values.push((name, text));
+                }
+                self.fill_template();
+            }
+            Some(PromptKind::Confirm(confirm)) if text == "y" || text == "Y" => match confirm {
+                Confirm::CloseBuffer => self.remove_editor(),
+                Confirm::Quit => self.quit = true,
+                Confirm::CreateNote => self.create_link_target(),
+                Confirm::Rename => self.finish_rename(),
+            },
+            _ => self.pending_rename = None,
+        }
+        self.refresh();
+    }
+
+    fn execute(&mut self, command: Command) -> Result<(), String> {
+        match command {
+            Command::New(title) => self.new_note(&title),
+            Command::Open(path) => {
+                let path = self.config.notes_dir.join(path);
+                self.open_path(&path);
+                self.layout.focus(PaneId::Editor);
+            }
+            Command::SaveAs(path) => {
+                let path = self.config.notes_dir.join(path);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                self.editors[self.current].path = Some(path.clone());
+                self.save_editor();
+                self.drawer_buffer.select_path(&path);
+            }
+            Command::Save => self.save_editor(),
+            Command::Goto(line) => {
+                self.editors[self.current]
+                    .text
+                    .set_cursor(0, line.saturating_sub(1));
+                self.layout.focus(PaneId::Editor);
+            }
+            Command::Search(query) => self.search(&query),
+            Command::Daily(day) => match parse_day(&day, &DateTime::now()) {
+                Some(date) => self.open_daily(&date),
+                None => return Err(format!("daily: invalid day: {}", day)),
+            },
+            Command::Tasks(tag) => {
+                let tag = tag.trim_start_matches('#');
+                self.show_tasks((!tag.is_empty()).then(|| tag.to_string()));
+            }
+            Command::Close => self.close_editor(),
+            Command::Quit => self.request_quit(),
+        }
+        Ok(())
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind, label: &str, text: &str) {
+        let history_key = match kind {
+            PromptKind::Command => "command",
+            PromptKind::Search => "search",
+            PromptKind::SearchNotes => "notes",
+            PromptKind::TemplateValue => "template",
+            PromptKind::Confirm(_) => "",
+        };
+        if let PromptKind::Confirm(_) = kind {
+            self.prompt_buffer.confirm(label);
+        } else {
+            self.prompt_buffer.open(label, text, history_key);
+        }
+        if self.layout.focus != PaneId::Prompt {
+            self.prompt_return = self.layout.focus;
+        }
+        self.prompt = Some(kind);
+        self.layout.set_visible(PaneId::Prompt, true);
+        self.layout.focus(PaneId::Prompt);
+        self.refresh();
+    }
+
+    fn close_prompt(&mut self) {
+        self.prompt = None;
+        self.layout.set_visible(PaneId::Prompt, false);
+        self.layout.focus(self.prompt_return);
+    }
+
+    fn search(&mut self, query: &str) {
+        self.last_search = query.to_string();
+        let editor = &mut self.editors[self.current];
+        let from = editor.text.get_cursor_pos();
+        match editor.text.find(query, from) {
+            Some((start, end)) => {
+                editor.text.select_range(start, end);
+                self.layout.focus(PaneId::Editor);
+            }
+            None => self
+                .status_buffer
+                .set_error(&format!("not found: {}", query)),
+        }
+    }
+
+    fn open_picker(&mut self, kind: PickerKind, picker: PickerBuffer) {
+        if self.picker.is_none() {
+            self.picker_return = self.layout.focus;
+        }
+        self.picker_buffer = picker;
+        self.picker = Some(kind);
+        self.layout.insert(
+            PaneId::Editor,
+            PaneId::Picker,
+            Direction::Vertical,
+            SplitSize::Second(12),
+        );
+        self.layout.focus(PaneId::Picker);
+        self.refresh();
+    }
+
+    fn open_buffer_picker(&mut self) {
+        let items = self
+            .editors
+            .iter()
+            .map(|e| PickerItem {
+                label: format!("{}{}", e.name(), if e.is_modified() { "*" } else { "" }),
+                detail: e
+                    .path
+                    .as_ref()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            })
+            .collect();
+        let mut picker = PickerBuffer::new("buffers", items);
+        picker.select(self.current);
+        self.open_picker(PickerKind::Buffer, picker);
+    }
+
+    // commands of the app and of the focused pane
+    fn open_palette(&mut self) {
+        let focus = self.layout.focus;
+        let pane_name = format!("{:?}", focus);
+        let mut entries = vec![];
+        let mut items = vec![];
+        for binding in self.bindings.iter() {
+            entries.push(PaletteEntry::App(binding.action));
+            items.push(PickerItem {
+                label: binding.name.to_string(),
+                detail: event_name(&binding.event),
+            });
+        }
+        for (name, event) in self.pane(focus).get_commands() {
+            items.push(PickerItem {
+                label: format!("{}: {}", pane_name, name),
+                detail: event_name(&event),
+            });
+            entries.push(PaletteEntry::Pane(focus, event));
+        }
+        self.palette = entries;
+        self.open_picker(PickerKind::Palette, PickerBuffer::new("commands", items));
+    }
+
+    // find a note by its path or title. files are listed by a background thread
+    // and added to the picker as they are found.
+    fn open_file_finder(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        walk_notes_async(&self.config.notes_dir, &self.config.extensions, tx);
+        self.finder_rx = Some(rx);
+        self.finder_files.clear();
+        let mut picker = PickerBuffer::new("files...", vec![]);
+        picker.match_detail = true;
+        self.open_picker(PickerKind::File, picker);
+        self.layout.insert(
+            PaneId::Picker,
+            PaneId::Preview,
+            Direction::Horizontal,
+            SplitSize::Ratio(0.5),
+        );
+        self.refresh();
+    }
+
+    // full-text search over the vault. results are listed in the picker
+    // and the match is shown in the preview.
+    fn search_notes(&mut self, query: &str) {
+        let result = self
+            .load_index()
+            .and_then(|index| index.search(query, 1000));
+        let hits = match result {
+            Ok(hits) if hits.is_empty() => {
+                self.status_buffer
+                    .set_error(&format!("no match: {}", query));
+                return;
+            }
+            Ok(hits) => hits,
+            Err(e) => {
+                self.status_buffer.set_error(&e);
+                return;
+            }
+        };
+        let items = hits
+            .iter()
+            .map(|hit| PickerItem {
+                label: format!("{}:{}", hit.path.to_string_lossy(), hit.line + 1),
+                detail: hit.text.trim().to_string(),
+            })
+            .collect();
+        self.search_hits = hits;
+        let mut picker = PickerBuffer::new(&format!("search: {}", query), items);
+        picker.match_detail = true;
+        self.open_picker(PickerKind::SearchResult, picker);
+        self.layout.insert(
+            PaneId::Picker,
+            PaneId::Preview,
+            Direction::Horizontal,
+            SplitSize::Ratio(0.5),
+        );
+        self.update_preview();
+    }
+
+    // the index is built when it is used first, and kept up to date on save
+    fn load_index(&mut self) -> Result<&mut Index, String> {
+        if self.index.is_none() {
+            let mut index = Index::open(&self.vault.root).map_err(|e| e.to_string())?;
+            index.sync(&self.vault).map_err(|e| e.to_string())?;
+            self.index = Some(index);
+        }
+        Ok(self.index.as_mut().unwrap())
+    }
+
+    // take files found by the indexing thread. returns true if the view changed.
+    fn poll_finder(&mut self) -> bool {
+        let Some(rx) = &self.finder_rx else {
+            return false;
+        };
+        let mut files = vec![];
+        let mut done = false;
+        loop {
+            match rx.try_recv() {
+                Ok(mut batch) => files.append(&mut batch),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    done = true;
+                    break;
+                }
+            }
+        }
+        if files.is_empty() && !done {
+            return false;
+        }
+        let items = files
+            .iter()
+            .map(|f| PickerItem {
+                label: f.path.to_string_lossy().to_string(),
+                detail: f.title.clone(),
+            })
+            .collect();
+        self.finder_files.append(&mut files);
+        self.picker_buffer.add_items(items);
+        if done {
+            self.finder_rx = None;
+            self.picker_buffer.title = String::from("files");
+        }
+        self.update_preview();
+        self.refresh();
+        true
+    }
+
+    // show the selected file of the finder or the search results
+    fn update_preview(&mut self) {
+        let selected = self.picker_buffer.selected_index();
+        let target = match (self.picker, selected) {
+            (Some(PickerKind::File), Some(i)) => Some((self.finder_files[i].path.clone(), None)),
+            (Some(PickerKind::SearchResult), Some(i)) => {
+                let hit = &self.search_hits[i];
+                Some((hit.path.clone(), Some(hit.line)))
+            }
+            (Some(PickerKind::File | PickerKind::SearchResult), None) => None,
+            _ => return,
+        };
+        match target {
+            Some((path, mark)) => {
+                let path = self.vault.root.join(path);
+                self.preview_buffer.show(&path, mark);
+            }
+            None => self.preview_buffer.clear(),
+        }
+        self.refresh();
+    }
+
+    // tags and titles in the drawer and the editors follow the vault
+    fn notes_changed(&mut self) {
+        self.drawer_buffer.set_tags(&self.vault);
+        let tags = self.tag_names();
+        let titles = self.note_titles();
+        for editor in self.editors.iter_mut() {
+            editor.tags = tags.clone();
+            editor.titles = titles.clone();
+        }
+        self.update_backlinks(true);
+        self.update_tasks();
+        self.update_agenda();
+    }
+
+    // open the task pane with the tasks which have the tag
+    fn show_tasks(&mut self, tag: Option<String>) {
+        if !self.layout.is_visible(PaneId::Tasks) {
+            self.layout.insert(
+                PaneId::Editor,
+                PaneId::Tasks,
+                Direction::Horizontal,
+                SplitSize::Second(40),
+            );
+        }
+        self.tasks_buffer.set_tasks(tag, vec![]);
+        self.update_tasks();
+        self.layout.focus(PaneId::Tasks);
+    }
+
+    // open tasks of the vault, when the pane is shown
+    fn update_tasks(&mut self) {
+        if !self.layout.is_visible(PaneId::Tasks) {
+            return;
+        }
+        let tag = self.tasks_buffer.tag.clone();
+        let tasks = open_tasks(&self.vault, tag.as_deref());
+        self.tasks_buffer.set_tasks(tag, tasks);
+    }
+
+    // open tasks with a due date, when the pane is shown
+    fn update_agenda(&mut self) {
+        if !self.layout.is_visible(PaneId::Agenda) {
+            return;
+        }
+        let today = DateTime::today();
+        let tasks = open_tasks(&self.vault, None);
+        self.agenda_buffer.set_agenda(agenda(tasks, &today));
+    }
+
+    // headings of the current note, when the pane is shown.
+    // with `follow`, the selection moves to the heading of the cursor.
+    fn update_outline(&mut self, follow: bool) {
+        if !self.layout.is_visible(PaneId::Outline) {
+            return;
+        }
+        let editor = &self.editors[self.current];
+        let line = editor.text.get_cursor_pos().y;
+        self.outline_buffer
+            .set_outline(headings(&editor.text.text), line, follow);
+    }
+
+    // references to the current note, when the pane is shown. they are found
+    // again when the note changes, or when `force` is set after the vault changed.
+    fn update_backlinks(&mut self, force: bool) {
+        if !self.layout.is_visible(PaneId::Backlinks) {
+            return;
+        }
+        let path = self.editors[self.current]
+            .path
+            .as_ref()
+            .filter(|p| p.starts_with(&self.vault.root))
+            .cloned();
+        if !force && path == self.backlinks_buffer.path {
+            return;
+        }
+        let Some(note) = path.as_ref().and_then(|p| self.vault.get(p)) else {
+            self.backlinks_buffer.set_references(path, vec![], vec![]);
+            return;
+        };
+        let names = mention_names(&note.title, &note.aliases);
+        let path = path.unwrap();
+        let links = backlinks(&self.vault, &path);
+        // notes which have the words of a name are found by the index
+        let mut candidates = vec![];
+        match self.load_index() {
+            Ok(index) => {
+                for name in names {
+                    let query = format!("\"{}\"", name.replace('"', " "));
+                    if let Ok(hits) = index.search(&query, MENTION_CANDIDATES) {
+                        candidates.extend(hits.into_iter().map(|h| h.path));
+                    }
                 }
-                _ => {}
             }
-            self.editor_buffer.update_view(event);
+            Err(e) => self.status_buffer.set_error(&e),
+        }
+        candidates.sort();
+        candidates.dedup();
+        let mentions = unlinked_mentions(&self.vault, &path, &candidates);
+        self.backlinks_buffer
+            .set_references(Some(path), links, mentions);
+    }
+
+    // recently modified notes first
+    fn note_titles(&self) -> Vec<String> {
+        let mut notes = self.vault.notes().iter().collect::<Vec<_>>();
+        notes.sort_by_key(|n| std::cmp::Reverse(n.modified));
+        notes.into_iter().map(|n| n.title.clone()).collect()
+    }
+
+    // open the note of the link under the cursor. a missing note can be created.
+    fn follow_link(&mut self) {
+        let Some(link) = self.editors[self.current].link_at_cursor() else {
+            self.status_buffer.set_error("no link at the cursor");
+            return;
+        };
+        if link.target.is_empty() {
+            self.push_location();
+            self.jump_to_heading(&link);
+            return;
+        }
+        let found = self
+            .vault
+            .find(&link.target)
+            .first()
+            .map(|n| self.vault.root.join(&n.path));
+        match found {
+            Some(path) => {
+                self.push_location();
+                self.open_path(&path);
+                self.jump_to_heading(&link);
+                self.layout.focus(PaneId::Editor);
+            }
+            None => {
+                let label = format!("{} does not exist. create it? (y/n) ", link.target);
+                self.pending_link = Some(link);
+                self.open_prompt(PromptKind::Confirm(Confirm::CreateNote), &label, "");
+            }
+        }
+    }
+
+    fn create_link_target(&mut self) {
+        let Some(link) = self.pending_link.take() else {
+            return;
+        };
+        match self.vault.create_note(&link.target) {
+            Ok(path) => {
+                self.notes_changed();
+                self.drawer_buffer.refresh();
+                self.drawer_buffer.select_path(&path);
+                self.push_location();
+                self.open_path(&path);
+                self.editors[self.current].text.set_cursor(0, 1);
+                self.layout.focus(PaneId::Editor);
+            }
+            Err(e) => self.status_buffer.set_error(&e.to_string()),
+        }
+    }
+
+    // a new note, from a template when there are any
+    fn new_note(&mut self, title: &str) {
+        self.templates = list_templates(&self.vault.root.join(&self.config.templates_dir));
+        if self.templates.is_empty() {
+            self.create_plain_note(title);
+            return;
+        }
+        self.new_title = title.to_string();
+        let mut items = vec![PickerItem {
+            label: String::from("empty note"),
+            detail: String::new(),
+        }];
+        items.extend(self.templates.iter().map(|p| {
+            PickerItem {
+                label: p
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                detail: p
+                    .file_name()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            }
+        }));
+        self.open_picker(PickerKind::Template, PickerBuffer::new("templates", items));
+    }
+
+    // only the heading, or nothing without a title
+    fn create_plain_note(&mut self, title: &str) {
+        let content = if title.is_empty() {
+            String::new()
+        } else {
+            format!("# {}\n", title)
+        };
+        self.create_note(title, &content, Some((0, 1)));
+    }
+
+    // ask the value of the next variable, or make the note when all are known
+    fn fill_template(&mut self) {
+        let Some(fill) = &self.pending_template else {
+            return;
+        };
+        if let Some(name) = fill.missing.first() {
+            let label = format!("{}: ", name);
+            self.open_prompt(PromptKind::TemplateValue, &label, "");
+            return;
+        }
+        let fill = self.pending_template.take().unwrap();
+        let expanded = template::expand(&fill.template, &DateTime::now(), &fill.values);
+        let title = fill
+            .values
+            .iter()
+            .find(|(name, _)| name == "title")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+        self.create_note(&title, &expanded.text, expanded.cursor);
+    }
+
+    // a note with a title is written now. without a title, it is named from
+    // the first heading when it is saved. cursor is (x, y).
+    fn create_note(&mut self, title: &str, content: &str, cursor: Option<(usize, usize)>) {
+        if title.is_empty() {
+            self.add_editor(EditorBuffer::new(content));
+        } else {
+            let path = match self.vault.create_note_with(title, content) {
+                Ok(path) => path,
+                Err(e) => {
+                    self.status_buffer.set_error(&e.to_string());
+                    return;
+                }
+            };
+            self.notes_changed();
+            self.drawer_buffer.refresh();
+            self.drawer_buffer.select_path(&path);
+            self.open_path(&path);
+        }
+        if let Some((x, y)) = cursor {
+            self.editors[self.current].text.set_cursor(x, y);
+        }
+        self.layout.focus(PaneId::Editor);
+    }
+
+    // open the daily note of the date, which is created from the template if missing
+    fn open_daily(&mut self, date: &DateTime) {
+        let exists = self.vault.daily_path(&self.config.daily, date).exists();
+        match self.vault.create_daily(&self.config.daily, date) {
+            Ok(path) => {
+                if !exists {
+                    self.notes_changed();
+                    self.drawer_buffer.refresh();
+                }
+                self.open_path(&path);
+                self.drawer_buffer.select_path(&path);
+                self.layout.focus(PaneId::Editor);
+            }
+            Err(e) => self.status_buffer.set_error(&e.to_string()),
+        }
+    }
+
+    // the nearest existing daily note before (-1) or after (1) the current one,
+    // or today when the current note is not a daily note
+    fn step_daily(&mut self, step: i64) {
+        let daily = &self.config.daily;
+        let current = self.editors[self.current]
+            .path
+            .as_ref()
+            .and_then(|p| daily.date_of(p.strip_prefix(&self.vault.root).ok()?))
+            .unwrap_or_else(DateTime::now);
+        let current = (current.year, current.month, current.day);
+        let days = self.vault.notes().iter().filter_map(|n| {
+            daily
+                .date_of(&n.path)
+                .map(|d| ((d.year, d.month, d.day), n))
+        });
+        let found = if step < 0 {
+            days.filter(|(d, _)| *d < current).max_by_key(|(d, _)| *d)
+        } else {
+            days.filter(|(d, _)| *d > current).min_by_key(|(d, _)| *d)
+        }
+        .map(|(_, n)| self.vault.root.join(&n.path));
+        match found {
+            Some(path) => {
+                self.open_path(&path);
+                self.drawer_buffer.select_path(&path);
+                self.layout.focus(PaneId::Editor);
+            }
+            None if step < 0 => self.status_buffer.set_message("no earlier daily note"),
+            None => self.status_buffer.set_message("no later daily note"),
+        }
+    }
+
+    // move a file or a folder of the drawer. links to it are shown first,
+    // and rewritten when the rename is confirmed.
+    fn request_rename(&mut self, from: &Path, to: &Path) {
+        let plan = match self.vault.plan_rename(from, to) {
+            Ok(plan) => plan,
+            Err(e) => {
+                self.status_buffer.set_error(&e.to_string());
+                return;
+            }
+        };
+        // the buffers would overwrite the rewritten links when they are saved
+        let root = &self.vault.root;
+        let unsaved = self.editors.iter().find(|e| {
+            e.is_modified()
+                && e.path.as_ref().is_some_and(|p| {
+                    p.starts_with(root.join(&plan.from))
+                        || plan.files.iter().any(|f| *p == root.join(&f.path))
+                })
+        });
+        if let Some(editor) = unsaved {
+            let message = format!("save {} before renaming", editor.name());
+            self.status_buffer.set_error(&message);
+            return;
+        }
+        if plan.files.is_empty() {
+            self.pending_rename = Some(plan);
+            self.finish_rename();
+            return;
+        }
+        let label = format!(
+            "update {} links in {} notes? (y/n) ",
+            plan.change_count(),
+            plan.files.len()
+        );
+        self.preview_buffer.show_lines("rename", plan.preview());
+        self.layout.insert(
+            PaneId::Editor,
+            PaneId::Preview,
+            Direction::Vertical,
+            SplitSize::Ratio(0.5),
+        );
+        self.pending_rename = Some(plan);
+        self.open_prompt(PromptKind::Confirm(Confirm::Rename), &label, "");
+    }
+
+    fn finish_rename(&mut self) {
+        let Some(plan) = self.pending_rename.take() else {
+            return;
+        };
+        let root = self.vault.root.clone();
+        if let Err(e) = self.vault.apply_rename(&plan) {
+            self.status_buffer.set_error(&e.to_string());
+            return;
+        }
+        if let Some(index) = &mut self.index {
+            if let Err(e) = index.sync(&self.vault) {
+                self.status_buffer.set_error(&e.to_string());
+            }
+        }
+        let (from, to) = (root.join(&plan.from), root.join(&plan.to));
+        let moved = |path: &Path| match path.strip_prefix(&from) {
+            Ok(rest) if rest.as_os_str().is_empty() => to.clone(),
+            Ok(rest) => to.join(rest),
+            Err(_) => path.to_path_buf(),
+        };
+        for location in self.back.iter_mut().chain(self.forward.iter_mut()) {
+            location.path = moved(&location.path);
+        }
+        let rewritten = plan
+            .files
+            .iter()
+            .map(|f| moved(&root.join(&f.path)))
+            .collect::<Vec<PathBuf>>();
+        for i in 0..self.editors.len() {
+            let Some(path) = self.editors[i].path.as_ref().map(|p| moved(p)) else {
+                continue;
+            };
+            self.editors[i].path = Some(path.clone());
+            if rewritten.contains(&path) {
+                self.reload_editor(i);
+            }
+        }
+        self.drawer_buffer.renamed(&from, &to);
+        self.notes_changed();
+        let mut message = format!("Renamed to {}", plan.to.to_string_lossy());
+        if !plan.files.is_empty() {
+            message.push_str(&format!(
+                ", updated {} links in {} notes",
+                plan.change_count(),
+                plan.files.len()
+            ));
+        }
+        self.status_buffer.set_message(&message);
+    }
+
+    // read the file of an unmodified buffer again, keeping the cursor
+    fn reload_editor(&mut self, i: usize) {
+        let Some(path) = self.editors[i].path.clone() else {
+            return;
+        };
+        match EditorBuffer::open(&path) {
+            Ok(mut editor) => {
+                let cursor = self.editors[i].text.get_cursor_pos();
+                editor.set_fold_front_matter(self.config.fold_front_matter);
+                editor.snippets = self.config.snippets_for(Some(&path));
+                editor.text.set_cursor(cursor.x, cursor.y);
+                self.editors[i] = editor;
+            }
+            Err(e) => self.status_buffer.set_error(&e.to_string()),
+        }
+    }
+
+    fn jump_to_heading(&mut self, link: &Link) {
+        let Some(heading) = &link.heading else {
+            return;
+        };
+        let editor = &mut self.editors[self.current];
+        let lines = editor
+            .text
+            .text
+            .iter()
+            .map(|l| l.iter().collect::<String>())
+            .collect::<Vec<String>>();
+        match find_heading(&lines, heading) {
+            Some(y) => editor.text.set_cursor(0, y),
+            None => self
+                .status_buffer
+                .set_error(&format!("no heading: {}", heading)),
+        }
+    }
+
+    fn location(&self) -> Option<Location> {
+        let editor = &self.editors[self.current];
+        let cursor = editor.text.get_cursor_pos();
+        editor.path.as_ref().map(|path| Location {
+            path: path.clone(),
+            x: cursor.x,
+            y: cursor.y,
+        })
+    }
+
+    // remember where a link is followed from
+    fn push_location(&mut self) {
+        if let Some(location) = self.location() {
+            self.back.push(location);
+            if self.back.len() > HISTORY_LIMIT {
+                self.back.remove(0);
+            }
+            self.forward.clear();
+        }
+    }
+
+    fn go_back(&mut self) {
+        let current = self.location();
+        if let Some(location) = pop_existing(&mut self.back) {
+            self.forward.extend(current);
+            self.open_location(&location);
+        } else {
+            self.status_buffer.set_message("no more history");
+        }
+    }
+
+    fn go_forward(&mut self) {
+        let current = self.location();
+        if let Some(location) = pop_existing(&mut self.forward) {
+            self.back.extend(current);
+            self.open_location(&location);
+        } else {
+            self.status_buffer.set_message("no more history");
+        }
+    }
+
+    fn open_location(&mut self, location: &Location) {
+        self.open_path(&location.path);
+        self.editors[self.current]
+            .text
+            .set_cursor(location.x, location.y);
+        self.layout.focus(PaneId::Editor);
+    }
+
+    // most used tags first
+    fn tag_names(&self) -> Vec<String> {
+        let mut tags = self.vault.tags();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        tags.into_iter().map(|(tag, _)| tag).collect()
+    }
+
+    fn scratch_editor(&self) -> EditorBuffer {
+        let mut editor = EditorBuffer::new("");
+        editor.snippets = self.config.snippets_for(None);
+        editor
+    }
+
+    // open the editor as a new tab (a scratch tab is replaced)
+    fn add_editor(&mut self, mut editor: EditorBuffer) {
+        editor.set_fold_front_matter(self.config.fold_front_matter);
+        editor.tags = self.tag_names();
+        editor.titles = self.note_titles();
+        editor.snippets = self.config.snippets_for(editor.path.as_deref());
+        if self.editors[self.current].is_scratch() {
+            self.editors[self.current] = editor;
+        } else {
+            self.editors.push(editor);
+            self.current = self.editors.len() - 1;
+        }
+    }
+
+    fn open_path(&mut self, path: &Path) {
+        if let Some(i) = self
+            .editors
+            .iter()
+            .position(|e| e.path.as_deref() == Some(path))
+        {
+            self.current = i;
+            return;
+        }
+        match EditorBuffer::open(path) {
+            Ok(editor) => self.add_editor(editor),
+            Err(e) => self.status_buffer.set_error(&e.to_string()),
+        }
+    }
+
+    // a note without a file is named by the vault
+    fn save_editor(&mut self) {
+        let editor = &mut self.editors[self.current];
+        let is_new = editor.path.is_none();
+        if is_new {
+            // named from the first heading
+            let content = editor.content();
+            let title = content
+                .lines()
+                .find_map(|l| l.strip_prefix("# "))
+                .unwrap_or_default();
+            editor.path = Some(self.vault.new_note_path(title));
+        }
+        if let Err(e) = editor.save() {
+            if is_new {
+                editor.path = None;
+            }
+            self.status_buffer.set_error(&e.to_string());
+            return;
+        }
+        let name = editor.name();
+        if let Some(path) = editor.path.clone() {
+            if path.starts_with(&self.vault.root) {
+                let result = self.vault.update(&path).and_then(|_| {
+                    match (&mut self.index, self.vault.get(&path)) {
+                        (Some(index), Some(note)) => index.update(note),
+                        _ => Ok(()),
+                    }
+                });
+                if let Err(e) = result {
+                    self.status_buffer.set_error(&e.to_string());
+                    return;
+                }
+                self.notes_changed();
+            }
+            if is_new {
+                self.drawer_buffer.refresh();
+                self.drawer_buffer.select_path(&path);
+            }
+        }
+        self.status_buffer.set_message(&format!("Saved {}", name));
+    }
+
+    fn switch_editor(&mut self, index: usize) {
+        if index < self.editors.len() {
+            self.current = index;
+        }
+        self.refresh();
+    }
+
+    fn close_editor(&mut self) {
+        if self.editors[self.current].is_modified() {
+            self.open_prompt(
+                PromptKind::Confirm(Confirm::CloseBuffer),
+                "unsaved changes. close anyway? (y/n) ",
+                "",
+            );
+            return;
+        }
+        self.remove_editor();
+        self.refresh();
+    }
+
+    fn remove_editor(&mut self) {
+        self.editors.remove(self.current);
+        if self.editors.is_empty() {
+            self.editors.push(self.scratch_editor());
+        }
+        if self.current >= self.editors.len() {
+            self.current = self.editors.len() - 1;
+        }
+    }
+
+    fn request_quit(&mut self) {
+        if self.editors.iter().any(|e| e.is_modified()) {
+            self.open_prompt(
+                PromptKind::Confirm(Confirm::Quit),
+                "unsaved changes. quit anyway? (y/n) ",
+                "",
+            );
+        } else {
+            self.quit = true;
+        }
+    }
+
+    fn pane(&mut self, id: PaneId) -> &mut dyn ViewBuffer {
+        match id {
+            PaneId::Drawer => &mut self.drawer_buffer,
+            PaneId::Tabs => &mut self.tab_buffer,
+            PaneId::Editor => &mut self.editors[self.current],
+            PaneId::Picker => &mut self.picker_buffer,
+            PaneId::Preview => &mut self.preview_buffer,
+            PaneId::Backlinks => &mut self.backlinks_buffer,
+            PaneId::Tasks => &mut self.tasks_buffer,
+            PaneId::Agenda => &mut self.agenda_buffer,
+            PaneId::Outline => &mut self.outline_buffer,
+            PaneId::Status => &mut self.status_buffer,
+            PaneId::Prompt => &mut self.prompt_buffer,
+        }
+    }
+
+    // rebuild views without input (after focus or size changes)
+    fn refresh(&mut self) {
+        self.update(Event::Unsupported(vec![]));
+    }
+
+    // set size and focus of each pane, then pass the event to the focused one.
+    // other panes only rebuild their view after the focused one is updated.
+    fn update(&mut self, event: Event) {
+        let (width, height) = termion::terminal_size().unwrap_or((100, 40));
+        self.width = width as usize;
+        self.height = height as usize;
+        let focus = self.layout.focus;
+        let rects = self.layout.calc(self.width, self.height);
+        for (id, rect) in rects.iter() {
+            self.pane(*id)
+                .set_view_info(rect.width, rect.height, *id == focus);
+        }
+        if rects.iter().any(|(id, _)| *id == focus) {
+            self.pane(focus).update_view(event);
+        }
+        self.update_backlinks(false);
+        self.update_outline(false);
+
+        let tabs = self
+            .editors
+            .iter()
+            .map(|e| Tab {
+                name: e.name(),
+                modified: e.is_modified(),
+            })
+            .collect();
+        self.tab_buffer.set_tabs(tabs, self.current);
+        let status = self.status_info();
+        self.status_buffer.set_status(status);
+        for (id, _) in rects.iter().filter(|(id, _)| *id != focus) {
+            self.pane(*id).update_view(Event::Unsupported(vec![]));
+        }
+    }
+
+    fn status_info(&self) -> StatusInfo {
+        let editor = &self.editors[self.current];
+        let cursor = editor.text.get_cursor_pos();
+        let column = editor.text.text[cursor.y][..cursor.x]
+            .iter()
+            .map(|c| c.width().unwrap_or(0))
+            .sum::<usize>();
+        let mode = match self.layout.focus {
+            PaneId::Drawer => "DRAWER",
+            PaneId::Picker => "PICK",
+            PaneId::Backlinks => "LINKS",
+            PaneId::Tasks => "TASKS",
+            PaneId::Agenda => "AGENDA",
+            PaneId::Outline => "OUTLINE",
+            PaneId::Prompt => "PROMPT",
+            _ => "EDIT",
+        };
+        StatusInfo {
+            mode: mode.to_string(),
+            name: editor.name(),
+            modified: editor.is_modified(),
+            line: cursor.y + 1,
+            column: column + 1,
+            char_index: cursor.x + 1,
+            selection: editor.text.selection_len(),
+            words: editor.text.word_count(),
+            encoding: editor.encoding().to_string(),
+            line_ending: editor.line_ending().to_string(),
+        }
+    }
+
+    fn render(&mut self, stdout: &mut impl Write) {
+        let views = self
+            .layout
+            .panes()
+            .into_iter()
+            .map(|id| (id, self.pane(id).get_view()))
+            .collect::<Vec<_>>();
+        let screen = self.layout.compose(self.width, self.height, &views);
+        for (row, line) in screen.iter().enumerate() {
+            write!(
+                stdout,
+                "{}{}",
+                termion::cursor::Goto(1, row as u16 + 1),
+                line
+            )
+            .unwrap();
+        }
+        let cursor = self.pane(self.layout.focus).get_cursor_pos();
+        let (x, y) = self.layout.screen_cursor(self.width, self.height, cursor);
+        write!(
+            stdout,
+            "{}",
+            termion::cursor::Goto(x as u16 + 1, y as u16 + 1)
+        )
+        .unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+// the last location whose file still exists
+fn pop_existing(history: &mut Vec<Location>) -> Option<Location> {
+    while let Some(location) = history.pop() {
+        if location.path.exists() {
+            return Some(location);
         }
     }
+    None
 }
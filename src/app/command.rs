@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::Path;
+
+use termion::event::{Event, Key};
+
+// プロンプトに入力するコマンド。
+// "open memo.md" のように、コマンド名と引数を空白で区切って入力する。数字だけなら行移動。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    New(String), // title, or empty for a scratch note
+    Open(String),
+    SaveAs(String),
+    Save,
+    Goto(usize),
+    Search(String),
+    Daily(String), // day of the daily note, empty for today
+    Tasks(String), // tag of the tasks, empty for all
+    Close,
+    Quit,
+}
+
+pub const COMMAND_NAMES: &[&str] = &[
+    "new", "open", "saveas", "write", "goto", "search", "daily", "tasks", "close", "quit",
+];
+
+// commands which take a file path
+const PATH_COMMANDS: &[&str] = &["open", "saveas"];
+
+pub fn parse_command(input: &str) -> Result<Command, String> {
+    let input = input.trim();
+    let (name, arg) = match input.split_once(' ') {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (input, ""),
+    };
+    if let Ok(line) = name.parse::<usize>() {
+        return Ok(Command::Goto(line));
+    }
+    let need_arg = |command: Command| {
+        if arg.is_empty() {
+            Err(format!("{}: argument required", name))
+        } else {
+            Ok(command)
+        }
+    };
+    match name {
+        "new" | "n" => Ok(Command::New(arg.to_string())),
+        "open" | "o" | "e" => need_arg(Command::Open(arg.to_string())),
+        "saveas" => need_arg(Command::SaveAs(arg.to_string())),
+        "write" | "w" => Ok(Command::Save),
+        "goto" | "g" => match arg.parse::<usize>() {
+            Ok(line) => Ok(Command::Goto(line)),
+            Err(_) => Err(format!("goto: invalid line number: {}", arg)),
+        },
+        "search" | "/" => need_arg(Command::Search(arg.to_string())),
+        "daily" | "d" => Ok(Command::Daily(arg.to_string())),
+        "tasks" => Ok(Command::Tasks(arg.to_string())),
+        "close" => Ok(Command::Close),
+        "quit" | "q" => Ok(Command::Quit),
+        "" => Err(String::from("no command")),
+        _ => Err(format!("unknown command: {}", name)),
+    }
+}
+
+// candidates for the whole input. paths are relative to root.
+pub fn complete_command(input: &str, root: &Path) -> Vec<String> {
+    match input.split_once(' ') {
+        None => COMMAND_NAMES
+            .iter()
+            .filter(|c| c.starts_with(input))
+            .map(|c| c.to_string())
+            .collect(),
+        Some((name, arg)) if PATH_COMMANDS.contains(&name) => complete_path(arg, root)
+            .into_iter()
+            .map(|p| format!("{} {}", name, p))
+            .collect(),
+        Some(_) => vec![],
+    }
+}
+
+pub fn complete_path(input: &str, root: &Path) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let Ok(read_dir) = fs::read_dir(root.join(dir)) else {
+        return vec![];
+    };
+    let mut candidates = read_dir
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let suffix = if e.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, suffix))
+        })
+        .collect::<Vec<String>>();
+    candidates.sort();
+    candidates
+}
+
+// commands of the app core. they are bound to keys and listed in the command palette.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Palette,
+    CommandPrompt,
+    NewNote,
+    Open,
+    FindFile,
+    SearchNotes,
+    FollowLink,
+    Today,
+    PrevDay,
+    NextDay,
+    GoBack,
+    GoForward,
+    Goto,
+    Search,
+    Save,
+    Close,
+    Quit,
+    NextBuffer,
+    PrevBuffer,
+    BufferList,
+    ToggleDrawer,
+    ToggleBacklinks,
+    ToggleTasks,
+    ToggleAgenda,
+    ToggleOutline,
+    FocusNext,
+    GrowWidth,
+    ShrinkWidth,
+    GrowHeight,
+    ShrinkHeight,
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub action: Action,
+    pub name: &'static str,
+    pub event: Event,
+}
+
+pub fn default_bindings() -> Vec<Binding> {
+    let bind = |action, name, key| Binding {
+        action,
+        name,
+        event: Event::Key(key),
+    };
+    vec![
+        bind(Action::Palette, "Command Palette", Key::Ctrl('p')),
+        bind(Action::CommandPrompt, "Command Prompt", Key::Ctrl('x')),
+        bind(Action::NewNote, "New Note", Key::Alt('n')),
+        bind(Action::Open, "Open File", Key::Ctrl('o')),
+        bind(Action::FindFile, "Find File", Key::Ctrl('t')),
+        bind(Action::SearchNotes, "Search Notes", Key::Alt('f')),
+        bind(Action::FollowLink, "Follow Link", Key::Alt('o')),
+        bind(Action::Today, "Today's Note", Key::Alt('t')),
+        bind(Action::PrevDay, "Previous Daily Note", Key::Alt('{')),
+        bind(Action::NextDay, "Next Daily Note", Key::Alt('}')),
+        bind(Action::GoBack, "Go Back", Key::Alt('b')),
+        bind(Action::GoForward, "Go Forward", Key::Alt('B')),
+        bind(Action::Goto, "Go to Line", Key::Ctrl('g')),
+        bind(Action::Search, "Search", Key::Ctrl('f')),
+        bind(Action::Save, "Save", Key::Ctrl('s')),
+        bind(Action::Close, "Close Buffer", Key::Ctrl('w')),
+        bind(Action::Quit, "Quit", Key::Ctrl('q')),
+        bind(Action::NextBuffer, "Next Buffer", Key::Alt('.')),
+        bind(Action::PrevBuffer, "Previous Buffer", Key::Alt(',')),
+        bind(Action::BufferList, "Buffer List", Key::Ctrl('e')),
+        bind(Action::ToggleDrawer, "Toggle Drawer", Key::Ctrl('b')),
+        bind(Action::ToggleBacklinks, "Toggle Backlinks", Key::Alt('r')),
+        bind(Action::ToggleTasks, "Toggle Tasks", Key::Alt('d')),
+        bind(Action::ToggleAgenda, "Toggle Agenda", Key::Alt('a')),
+        bind(Action::ToggleOutline, "Toggle Outline", Key::Alt('O')),
+        bind(Action::FocusNext, "Focus Next Pane", Key::Ctrl('n')),
+        bind(Action::GrowWidth, "Grow Pane Width", Key::Alt('l')),
+        bind(Action::ShrinkWidth, "Shrink Pane Width", Key::Alt('h')),
+        bind(Action::GrowHeight, "Grow Pane Height", Key::Alt('j')),
+        bind(Action::ShrinkHeight, "Shrink Pane Height", Key::Alt('k')),
+    ]
+}
+
+// key name to show in the palette
+pub fn event_name(event: &Event) -> String {
+    match event {
+        Event::Key(key) => match key {
+            Key::Ctrl(c) => format!("Ctrl-{}", c.to_ascii_uppercase()),
+            Key::Alt(c) => format!("Alt-{}", c),
+            Key::Char('\n') => String::from("Enter"),
+            Key::Char('\t') => String::from("Tab"),
+            Key::Char(c) => c.to_string(),
+            Key::Esc => String::from("Esc"),
+            Key::Backspace => String::from("Backspace"),
+            Key::Delete => String::from("Delete"),
+            Key::Left => String::from("Left"),
+            Key::Right => String::from("Right"),
+            Key::Up => String::from("Up"),
+            Key::Down => String::from("Down"),
+            Key::BackTab => String::from("Shift-Tab"),
+            Key::F(n) => format!("F{}", n),
+            _ => format!("{:?}", key),
+        },
+        _ => String::new(),
+    }
+}
@@ -1,3 +1,193 @@
-pub struct Config {}
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-impl Config {}
+use crate::buffer::snippet::{default_snippets, Snippet};
+use crate::note::daily::{parse_weekday, Daily};
+use crate::note::template;
+use crate::note::vault::NameStyle;
+
+// 設定は ~/.config/note-cli/config に "key = value" の形で書く。
+// 環境変数 NOTE_DIR があれば notes_dir より優先する。どちらもなければ ~/notes を使う。
+// スニペットは "snippet.md.todo = - [ ] $0" のように拡張子(すべてのファイルなら *)とトリガーで書く。
+
+const DEFAULT_NOTES_DIR: &str = "~/notes";
+
+pub struct Config {
+    pub notes_dir: PathBuf,
+    pub drawer_width: usize,
+    pub extensions: Vec<String>,          // of note files
+    pub name_style: NameStyle,            // file name of new notes
+    pub inbox: String,                    // note which `add` appends to
+    pub fold_front_matter: bool,          // show the front matter as one row
+    pub daily: Daily,                     // file names and template of daily notes
+    pub templates_dir: PathBuf,           // relative to the vault, or absolute
+    pub snippets: Vec<(String, Snippet)>, // extension and snippet
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            notes_dir: expand_home(DEFAULT_NOTES_DIR),
+            drawer_width: 30,
+            extensions: vec![
+                String::from("md"),
+                String::from("markdown"),
+                String::from("txt"),
+            ],
+            name_style: NameStyle::Slug,
+            inbox: String::from("inbox"),
+            fold_front_matter: true,
+            daily: Daily::default(),
+            templates_dir: PathBuf::from(template::DEFAULT_DIR),
+            snippets: default_snippets(),
+        }
+    }
+}
+
+impl Config {
+    // defaults, then the config file, then the environment
+    pub fn load() -> Result<Self, String> {
+        let mut config = Config::default();
+        if let Some(path) = config_path() {
+            if let Ok(content) = fs::read_to_string(&path) {
+                config
+                    .parse(&content)
+                    .map_err(|e| format!("{}: {}", path.to_string_lossy(), e))?;
+            }
+        }
+        if let Ok(dir) = env::var("NOTE_DIR") {
+            if !dir.is_empty() {
+                config.notes_dir = expand_home(&dir);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn parse(&mut self, content: &str) -> Result<(), String> {
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected key = value", i + 1));
+            };
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "notes_dir" => self.notes_dir = expand_home(value),
+                "drawer_width" => {
+                    self.drawer_width = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid number: {}", i + 1, value))?
+                }
+                "extensions" => {
+                    self.extensions = value
+                        .split(',')
+                        .map(|e| e.trim().trim_start_matches('.').to_string())
+                        .filter(|e| !e.is_empty())
+                        .collect()
+                }
+                "new_note_name" => {
+                    self.name_style = match value {
+                        "timestamp" => NameStyle::Timestamp,
+                        "slug" => NameStyle::Slug,
+                        _ => return Err(format!("line {}: unknown name style: {}", i + 1, value)),
+                    }
+                }
+                "inbox" => self.inbox = value.to_string(),
+                "fold_front_matter" => {
+                    self.fold_front_matter = match value {
+                        "true" => true,
+                        "false" => false,
+                        _ => {
+                            return Err(format!(
+                                "line {}: expected true or false: {}",
+                                i + 1,
+                                value
+                            ))
+                        }
+                    }
+                }
+                "daily_pattern" => self.daily.pattern = value.to_string(),
+                "daily_template" => self.daily.template = Some(expand_home(value)),
+                "templates_dir" => self.templates_dir = expand_home(value),
+                "week_start" => {
+                    self.daily.week_start = parse_weekday(value)
+                        .ok_or_else(|| format!("line {}: unknown weekday: {}", i + 1, value))?
+                }
+                key if key.starts_with("snippet.") => {
+                    let Some((ext, trigger)) = key["snippet.".len()..].split_once('.') else {
+                        return Err(format!("line {}: expected snippet.EXT.TRIGGER", i + 1));
+                    };
+                    if ext.is_empty() || trigger.is_empty() || trigger.contains(char::is_whitespace)
+                    {
+                        return Err(format!("line {}: invalid snippet: {}", i + 1, key));
+                    }
+                    self.snippets.push((
+                        ext.to_string(),
+                        Snippet {
+                            trigger: trigger.to_string(),
+                            body: unescape(value),
+                        },
+                    ));
+                }
+                key => return Err(format!("line {}: unknown key: {}", i + 1, key)),
+            }
+        }
+        Ok(())
+    }
+
+    // snippets for the file (a scratch buffer is markdown). later ones win.
+    pub fn snippets_for(&self, path: Option<&Path>) -> Vec<Snippet> {
+        let ext = path
+            .map(|p| {
+                p.extension()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .unwrap_or_else(|| String::from("md"));
+        self.snippets
+            .iter()
+            .filter(|(e, _)| e == "*" || *e == ext)
+            .map(|(_, snippet)| snippet.clone())
+            .collect()
+    }
+}
+
+// "\n" and "\t" in a value. other backslashes are left as they are.
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('n')) => {
+                result.push('\n');
+                chars.next();
+            }
+            ('\\', Some('t')) => {
+                result.push('\t');
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn config_path() -> Option<PathBuf> {
+    let dir = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(dir.join("note-cli").join("config"))
+}
+
+// "~/notes" -> "/home/user/notes"
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
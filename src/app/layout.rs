@@ -0,0 +1,464 @@
+use unicode_width::UnicodeWidthChar;
+
+// Layoutは画面をペインの木として分割する。
+// 各ペインはキー(K)で識別し、実際のViewBufferはAppが持つ。
+// calcで各ペインの位置と大きさを求めて、composeで各ペインの表示を一つの画面にまとめる。
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Horizontal, // first | second
+    Vertical,   // first above second
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitSize {
+    Ratio(f32),    // share of the first child
+    First(usize),  // fixed size of the first child
+    Second(usize), // fixed size of the second child
+}
+
+#[derive(Debug, Clone)]
+pub enum Node<K> {
+    Pane(K),
+    Split {
+        dir: Direction,
+        size: SplitSize,
+        border: bool,
+        first: Box<Node<K>>,
+        second: Box<Node<K>>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Layout<K> {
+    pub root: Node<K>,
+    pub focus: K,
+    hidden: Vec<K>,
+    unfocusable: Vec<K>,
+}
+
+impl<K: Copy + PartialEq> Node<K> {
+    pub fn split(dir: Direction, size: SplitSize, first: Node<K>, second: Node<K>) -> Self {
+        Node::Split {
+            dir,
+            size,
+            border: true,
+            first: Box::new(first),
+            second: Box::new(second),
+        }
+    }
+
+    // split without border line (for bars)
+    pub fn join(dir: Direction, size: SplitSize, first: Node<K>, second: Node<K>) -> Self {
+        Node::Split {
+            dir,
+            size,
+            border: false,
+            first: Box::new(first),
+            second: Box::new(second),
+        }
+    }
+
+    fn contains(&self, key: K) -> bool {
+        match self {
+            Node::Pane(k) => *k == key,
+            Node::Split { first, second, .. } => first.contains(key) || second.contains(key),
+        }
+    }
+
+    fn panes(&self, panes: &mut Vec<K>) {
+        match self {
+            Node::Pane(k) => panes.push(*k),
+            Node::Split { first, second, .. } => {
+                first.panes(panes);
+                second.panes(panes);
+            }
+        }
+    }
+}
+
+impl<K: Copy + PartialEq> Layout<K> {
+    pub fn new(root: Node<K>, focus: K) -> Self {
+        Layout {
+            root,
+            focus,
+            hidden: vec![],
+            unfocusable: vec![],
+        }
+    }
+
+    pub fn set_focusable(&mut self, key: K, focusable: bool) {
+        self.unfocusable.retain(|k| *k != key);
+        if !focusable {
+            self.unfocusable.push(key);
+        }
+    }
+
+    pub fn is_visible(&self, key: K) -> bool {
+        self.root.contains(key) && !self.hidden.contains(&key)
+    }
+
+    pub fn set_visible(&mut self, key: K, visible: bool) {
+        self.hidden.retain(|k| *k != key);
+        if !visible {
+            self.hidden.push(key);
+            if self.focus == key {
+                self.focus_next();
+            }
+        }
+    }
+
+    pub fn toggle(&mut self, key: K) {
+        let visible = self.is_visible(key);
+        self.set_visible(key, !visible);
+    }
+
+    // visible panes in layout order
+    pub fn panes(&self) -> Vec<K> {
+        let mut panes = vec![];
+        self.root.panes(&mut panes);
+        panes.retain(|k| !self.hidden.contains(k));
+        panes
+    }
+
+    pub fn focus(&mut self, key: K) {
+        if self.is_visible(key) && !self.unfocusable.contains(&key) {
+            self.focus = key;
+        }
+    }
+
+    pub fn focus_next(&mut self) {
+        let panes = self.focusable_panes();
+        if panes.is_empty() {
+            return;
+        }
+        let i = panes.iter().position(|k| *k == self.focus).unwrap_or(0);
+        self.focus = panes[(i + 1) % panes.len()];
+    }
+
+    fn focusable_panes(&self) -> Vec<K> {
+        let mut panes = self.panes();
+        panes.retain(|k| !self.unfocusable.contains(k));
+        panes
+    }
+
+    // replace the pane `target` with a split of `target` and `key`.
+    pub fn insert(&mut self, target: K, key: K, dir: Direction, size: SplitSize) {
+        fn insert<K: Copy + PartialEq>(
+            node: &mut Node<K>,
+            target: K,
+            key: K,
+            dir: Direction,
+            size: SplitSize,
+        ) {
+            match node {
+                Node::Pane(k) if *k == target => {
+                    *node = Node::split(dir, size, Node::Pane(target), Node::Pane(key));
+                }
+                Node::Pane(_) => {}
+                Node::Split { first, second, .. } => {
+                    insert(first, target, key, dir, size);
+                    insert(second, target, key, dir, size);
+                }
+            }
+        }
+        if !self.root.contains(key) {
+            insert(&mut self.root, target, key, dir, size);
+        }
+    }
+
+    // remove the pane and collapse its parent split.
+    pub fn remove(&mut self, key: K) {
+        fn remove<K: Copy + PartialEq>(node: &mut Node<K>, key: K) {
+            if let Node::Split { first, second, .. } = node {
+                if matches!(**first, Node::Pane(k) if k == key) {
+                    *node = (**second).clone();
+                } else if matches!(**second, Node::Pane(k) if k == key) {
+                    *node = (**first).clone();
+                } else {
+                    remove(first, key);
+                    remove(second, key);
+                }
+            }
+        }
+        remove(&mut self.root, key);
+        self.hidden.retain(|k| *k != key);
+        if self.focus == key {
+            self.focus_next();
+        }
+    }
+
+    // grow (delta > 0) or shrink the pane along the direction
+    // by moving the border of the nearest split around it.
+    pub fn resize(&mut self, key: K, dir: Direction, delta: isize) {
+        fn resize<K: Copy + PartialEq>(
+            node: &mut Node<K>,
+            key: K,
+            dir: Direction,
+            delta: isize,
+        ) -> bool {
+            let Node::Split {
+                dir: d,
+                size,
+                first,
+                second,
+                ..
+            } = node
+            else {
+                return false;
+            };
+            let in_first = first.contains(key);
+            if !in_first && !second.contains(key) {
+                return false;
+            }
+            let child = if in_first { first } else { second };
+            if resize(child, key, dir, delta) || *d != dir {
+                return true;
+            }
+            let delta = if in_first { delta } else { -delta };
+            *size = match *size {
+                SplitSize::Ratio(r) => SplitSize::Ratio((r + delta as f32 * 0.05).clamp(0.1, 0.9)),
+                SplitSize::First(n) => SplitSize::First(n.saturating_add_signed(delta).max(1)),
+                SplitSize::Second(n) => SplitSize::Second(n.saturating_add_signed(-delta).max(1)),
+            };
+            true
+        }
+        resize(&mut self.root, key, dir, delta);
+    }
+
+    pub fn calc(&self, width: usize, height: usize) -> Vec<(K, Rect)> {
+        let mut rects = vec![];
+        self.calc_node(
+            &self.root,
+            Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+            &mut rects,
+            &mut vec![],
+        );
+        rects
+    }
+
+    pub fn rect(&self, key: K, width: usize, height: usize) -> Option<Rect> {
+        self.calc(width, height)
+            .into_iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, r)| r)
+    }
+
+    fn calc_node(
+        &self,
+        node: &Node<K>,
+        rect: Rect,
+        rects: &mut Vec<(K, Rect)>,
+        borders: &mut Vec<(Direction, Rect)>,
+    ) {
+        match node {
+            Node::Pane(k) => {
+                if !self.hidden.contains(k) {
+                    rects.push((*k, rect));
+                }
+            }
+            Node::Split {
+                dir,
+                size,
+                border,
+                first,
+                second,
+            } => {
+                let first_visible = self.has_visible(first);
+                let second_visible = self.has_visible(second);
+                if !first_visible || !second_visible {
+                    if first_visible {
+                        self.calc_node(first, rect, rects, borders);
+                    } else if second_visible {
+                        self.calc_node(second, rect, rects, borders);
+                    }
+                    return;
+                }
+                let total = match dir {
+                    Direction::Horizontal => rect.width,
+                    Direction::Vertical => rect.height,
+                };
+                let line = if *border { 1 } else { 0 };
+                let space = total.saturating_sub(line);
+                let first_size = match *size {
+                    SplitSize::Ratio(r) => (space as f32 * r).round() as usize,
+                    SplitSize::First(n) => n,
+                    SplitSize::Second(n) => space.saturating_sub(n),
+                }
+                .min(space);
+                let second_size = space - first_size;
+                let (a, b, l) = match dir {
+                    Direction::Horizontal => (
+                        Rect {
+                            width: first_size,
+                            ..rect
+                        },
+                        Rect {
+                            x: rect.x + first_size + line,
+                            width: second_size,
+                            ..rect
+                        },
+                        Rect {
+                            x: rect.x + first_size,
+                            width: line,
+                            ..rect
+                        },
+                    ),
+                    Direction::Vertical => (
+                        Rect {
+                            height: first_size,
+                            ..rect
+                        },
+                        Rect {
+                            y: rect.y + first_size + line,
+                            height: second_size,
+                            ..rect
+                        },
+                        Rect {
+                            y: rect.y + first_size,
+                            height: line,
+                            ..rect
+                        },
+                    ),
+                };
+                if *border {
+                    borders.push((*dir, l));
+                }
+                self.calc_node(first, a, rects, borders);
+                self.calc_node(second, b, rects, borders);
+            }
+        }
+    }
+
+    fn has_visible(&self, node: &Node<K>) -> bool {
+        let mut panes = vec![];
+        node.panes(&mut panes);
+        panes.iter().any(|k| !self.hidden.contains(k))
+    }
+
+    // build screen lines from the views of each pane.
+    pub fn compose(&self, width: usize, height: usize, views: &[(K, Vec<String>)]) -> Vec<String> {
+        let mut grid = vec![vec![String::from(" "); width]; height];
+        let mut rects = vec![];
+        let mut borders = vec![];
+        self.calc_node(
+            &self.root,
+            Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+            &mut rects,
+            &mut borders,
+        );
+
+        for (dir, rect) in borders {
+            let line = match dir {
+                Direction::Horizontal => "│",
+                Direction::Vertical => "─",
+            };
+            for row in grid.iter_mut().skip(rect.y).take(rect.height) {
+                for cell in row.iter_mut().skip(rect.x).take(rect.width) {
+                    *cell = line.to_string();
+                }
+            }
+        }
+
+        for (key, rect) in rects {
+            let Some((_, view)) = views.iter().find(|(k, _)| *k == key) else {
+                continue;
+            };
+            for (row, line) in view.iter().take(rect.height).enumerate() {
+                let y = rect.y + row;
+                if y >= height {
+                    break;
+                }
+                let cells = split_cells(line, rect.width);
+                for (i, cell) in cells.into_iter().enumerate() {
+                    if rect.x + i < width {
+                        grid[y][rect.x + i] = cell;
+                    }
+                }
+            }
+        }
+
+        grid.into_iter().map(|row| row.concat()).collect()
+    }
+
+    // cursor position on the screen for the focused pane
+    pub fn screen_cursor(
+        &self,
+        width: usize,
+        height: usize,
+        cursor: (usize, usize),
+    ) -> (usize, usize) {
+        match self.rect(self.focus, width, height) {
+            Some(rect) => (
+                rect.x + cursor.0.min(rect.width.saturating_sub(1)),
+                rect.y + cursor.1.min(rect.height.saturating_sub(1)),
+            ),
+            None => (0, 0),
+        }
+    }
+}
+
+// split a view line into screen cells of the given width.
+// a wide char takes two cells (the second one is empty),
+// escape sequences are attached to the next char.
+fn split_cells(line: &str, width: usize) -> Vec<String> {
+    let mut cells = vec![];
+    let mut count = 0;
+    let mut escape = String::new();
+    let mut styled = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            escape.push(c);
+            for e in chars.by_ref() {
+                escape.push(e);
+                if e.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            styled = true;
+            continue;
+        }
+        let w = c.width().unwrap_or(0);
+        if w == 0 {
+            continue;
+        }
+        if count + w > width {
+            break;
+        }
+        cells.push(format!("{}{}", escape, c));
+        escape.clear();
+        if w == 2 {
+            cells.push(String::new());
+        }
+        count += w;
+    }
+    while cells.len() < width {
+        cells.push(format!("{}{}", escape, " "));
+        escape.clear();
+    }
+    if styled {
+        if let Some(last) = cells.last_mut() {
+            last.push_str(termion::style::Reset.as_ref());
+        }
+    }
+    cells
+}
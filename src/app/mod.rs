@@ -1,2 +1,4 @@
 pub mod app;
-pub mod config;
\ No newline at end of file
+pub mod command;
+pub mod config;
+pub mod layout;
@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+
+use termion::event::{Event, Key};
+use unicode_width::UnicodeWidthChar;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+use crate::note::task::{Agenda, Task};
+
+// AgendaBufferは期日のあるタスクを期限切れ、今日、これからの三つに分けて表示するペイン。
+// 各行は期日、タスク、ノートのタイトル。
+// Enterでタスクの行を開き、xで完了、pで一日、Pで一週間延ばす。
+// ファイルの書き換えはtake_actionで受け取ったAppが行う。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgendaAction {
+    Open(PathBuf, usize), // relative path and line
+    Complete(Task),
+    Postpone(Task, i64), // days
+}
+
+#[derive(Debug, Clone)]
+pub struct AgendaBuffer {
+    pub agenda: Agenda,
+    pub selected: usize, // index in overdue, today, then upcoming
+    top: usize,          // first row
+    pub info: ViewBufferInfo,
+    action: Option<AgendaAction>,
+}
+
+impl ViewBuffer for AgendaBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, event: Event) {
+        let count = self.tasks().count();
+        if self.info.focus {
+            match event {
+                Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                Event::Key(Key::Down) | Event::Key(Key::Char('j')) if self.selected + 1 < count => {
+                    self.selected += 1;
+                }
+                Event::Key(Key::Char('\n')) => {
+                    if let Some(task) = self.selected_task() {
+                        self.action = Some(AgendaAction::Open(task.path.clone(), task.line));
+                    }
+                }
+                Event::Key(Key::Char('x')) => {
+                    self.action = self.selected_task().cloned().map(AgendaAction::Complete);
+                }
+                Event::Key(Key::Char('p')) => {
+                    self.action = self
+                        .selected_task()
+                        .map(|task| AgendaAction::Postpone(task.clone(), 1));
+                }
+                Event::Key(Key::Char('P')) => {
+                    self.action = self
+                        .selected_task()
+                        .map(|task| AgendaAction::Postpone(task.clone(), 7));
+                }
+                _ => {}
+            }
+        }
+
+        let mut rows = vec![];
+        let mut selected_row = 0;
+        let mut i = 0;
+        for (name, tasks) in [
+            ("Overdue", &self.agenda.overdue),
+            ("Today", &self.agenda.today),
+            ("Upcoming", &self.agenda.upcoming),
+        ] {
+            if !rows.is_empty() {
+                rows.push(String::new());
+            }
+            rows.push(format!("{} ({})", name, tasks.len()));
+            for task in tasks.iter() {
+                if i == self.selected {
+                    selected_row = rows.len();
+                }
+                let due = task.due.map(|d| d.format("%Y-%m-%d")).unwrap_or_default();
+                rows.push(self.entry_row(i, &format!(" {} {}  {}", due, task.text, task.title)));
+                i += 1;
+            }
+        }
+
+        let height = self.info.height.max(1);
+        if selected_row < self.top {
+            self.top = selected_row;
+        } else if selected_row + 1 > self.top + height {
+            self.top = selected_row + 1 - height;
+        }
+        if count == 0 {
+            self.top = 0;
+        }
+        self.info.buffer = rows.into_iter().skip(self.top).take(height).collect();
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+
+    fn get_commands(&self) -> Vec<(String, Event)> {
+        [
+            ("Open Task", Key::Char('\n')),
+            ("Complete Task", Key::Char('x')),
+            ("Postpone Task a Day", Key::Char('p')),
+            ("Postpone Task a Week", Key::Char('P')),
+        ]
+        .into_iter()
+        .map(|(name, key)| (name.to_string(), Event::Key(key)))
+        .collect()
+    }
+}
+
+impl AgendaBuffer {
+    pub fn new() -> Self {
+        AgendaBuffer {
+            agenda: Agenda {
+                overdue: vec![],
+                today: vec![],
+                upcoming: vec![],
+            },
+            selected: 0,
+            top: 0,
+            info: ViewBufferInfo {
+                width: 40,
+                height: 10,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            action: None,
+        }
+    }
+
+    pub fn take_action(&mut self) -> Option<AgendaAction> {
+        self.action.take()
+    }
+
+    // the selection stays on the same task, or on the same row when it is gone
+    pub fn set_agenda(&mut self, agenda: Agenda) {
+        let selected = self.selected_task().map(|t| (t.path.clone(), t.line));
+        self.agenda = agenda;
+        let count = self.tasks().count();
+        self.selected = selected
+            .and_then(|(path, line)| self.tasks().position(|t| t.path == path && t.line == line))
+            .unwrap_or(self.selected.min(count.saturating_sub(1)));
+    }
+
+    pub fn selected_task(&self) -> Option<&Task> {
+        self.tasks().nth(self.selected)
+    }
+
+    fn tasks(&self) -> impl Iterator<Item = &Task> {
+        self.agenda
+            .overdue
+            .iter()
+            .chain(self.agenda.today.iter())
+            .chain(self.agenda.upcoming.iter())
+    }
+
+    // cut to the width, inverted if selected
+    fn entry_row(&self, i: usize, text: &str) -> String {
+        let text = text.replace('\t', "    ");
+        let mut width = 0;
+        let cut = text
+            .chars()
+            .take_while(|c| {
+                width += c.width().unwrap_or(0);
+                width <= self.info.width
+            })
+            .collect::<String>();
+        if i == self.selected && self.info.focus {
+            format!("{}{}{}", termion::style::Invert, cut, termion::style::Reset)
+        } else {
+            cut
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use termion::event::{Event, Key};
+use unicode_width::UnicodeWidthChar;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+use crate::note::backlink::Reference;
+
+// BacklinksBufferは開いているノートへのバックリンクを表示するペイン。
+// 上にリンクしているノート、下にリンクせずにタイトルに触れているノートを並べ、
+// 各項目はノートのタイトルと、その行の内容の二行で表示する。
+// Enterで選んだ行を開く。開く操作はtake_actionでAppが受け取る。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BacklinksAction {
+    Open(PathBuf, usize), // relative path and line
+}
+
+#[derive(Debug, Clone)]
+pub struct BacklinksBuffer {
+    pub path: Option<PathBuf>, // note whose backlinks are shown
+    pub links: Vec<Reference>,
+    pub mentions: Vec<Reference>,
+    pub selected: usize, // index in links, then mentions
+    top: usize,          // first row
+    pub info: ViewBufferInfo,
+    action: Option<BacklinksAction>,
+}
+
+impl ViewBuffer for BacklinksBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, event: Event) {
+        let count = self.links.len() + self.mentions.len();
+        if self.info.focus {
+            match event {
+                Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                Event::Key(Key::Down) | Event::Key(Key::Char('j')) if self.selected + 1 < count => {
+                    self.selected += 1;
+                }
+                Event::Key(Key::Char('\n')) => {
+                    if let Some(r) = self.selected_reference() {
+                        self.action = Some(BacklinksAction::Open(r.path.clone(), r.line));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut rows = vec![format!("Backlinks ({})", self.links.len())];
+        let mut selected_row = 0;
+        for (i, r) in self.links.iter().chain(self.mentions.iter()).enumerate() {
+            if i == self.links.len() {
+                rows.push(String::new());
+                rows.push(format!("Unlinked mentions ({})", self.mentions.len()));
+            }
+            if i == self.selected {
+                selected_row = rows.len();
+            }
+            rows.push(self.entry_row(i, &format!(" {}:{}", r.title, r.line + 1)));
+            rows.push(self.entry_row(i, &format!("   {}", r.text)));
+        }
+        if self.mentions.is_empty() {
+            rows.push(String::new());
+            rows.push(String::from("Unlinked mentions (0)"));
+        }
+
+        let height = self.info.height.max(2);
+        if selected_row < self.top {
+            self.top = selected_row;
+        } else if selected_row + 2 > self.top + height {
+            self.top = selected_row + 2 - height;
+        }
+        if count == 0 {
+            self.top = 0;
+        }
+        self.info.buffer = rows.into_iter().skip(self.top).take(height).collect();
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+
+    fn get_commands(&self) -> Vec<(String, Event)> {
+        vec![(String::from("Open Reference"), Event::Key(Key::Char('\n')))]
+    }
+}
+
+impl BacklinksBuffer {
+    pub fn new() -> Self {
+        BacklinksBuffer {
+            path: None,
+            links: vec![],
+            mentions: vec![],
+            selected: 0,
+            top: 0,
+            info: ViewBufferInfo {
+                width: 40,
+                height: 10,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            action: None,
+        }
+    }
+
+    pub fn take_action(&mut self) -> Option<BacklinksAction> {
+        self.action.take()
+    }
+
+    // the selection stays on the same note and line if it is still listed
+    pub fn set_references(
+        &mut self,
+        path: Option<PathBuf>,
+        links: Vec<Reference>,
+        mentions: Vec<Reference>,
+    ) {
+        let same = self.path == path;
+        let selected = self.selected_reference().map(|r| (r.path.clone(), r.line));
+        self.path = path;
+        self.links = links;
+        self.mentions = mentions;
+        self.selected = selected
+            .filter(|_| same)
+            .and_then(|(path, line)| {
+                self.links
+                    .iter()
+                    .chain(self.mentions.iter())
+                    .position(|r| r.path == path && r.line == line)
+            })
+            .unwrap_or(0);
+        if !same {
+            self.top = 0;
+        }
+    }
+
+    pub fn selected_reference(&self) -> Option<&Reference> {
+        self.links
+            .iter()
+            .chain(self.mentions.iter())
+            .nth(self.selected)
+    }
+
+    // cut to the width, inverted if selected
+    fn entry_row(&self, i: usize, text: &str) -> String {
+        let text = text.replace('\t', "    ");
+        let mut width = 0;
+        let cut = text
+            .chars()
+            .take_while(|c| {
+                width += c.width().unwrap_or(0);
+                width <= self.info.width
+            })
+            .collect::<String>();
+        if i == self.selected && self.info.focus {
+            format!("{}{}{}", termion::style::Invert, cut, termion::style::Reset)
+        } else {
+            cut
+        }
+    }
+}
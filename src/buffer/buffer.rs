@@ -5,6 +5,12 @@ pub trait ViewBuffer {
     fn update_view(&mut self, event: Event);
     fn get_view(&self) -> Vec<String>;
     fn get_cursor_pos(&self) -> (usize, usize);
+
+    // commands of this buffer, listed in the command palette.
+    // (name, event) : the command runs when the event is passed to update_view.
+    fn get_commands(&self) -> Vec<(String, Event)> {
+        vec![]
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,573 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use termion::event::{Event, Key};
+use unicode_width::UnicodeWidthStr;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+use crate::note::vault::Vault;
+
+// DrawerBufferはノートディレクトリをツリー表示する。
+// 展開されているディレクトリだけを辿って、表示する行(entries)を平坦なリストとして持つ。
+// タグモードではディレクトリの代わりにタグを階層で並べ、タグの下にノートを表示する。
+
+#[derive(Debug, Clone)]
+pub struct DrawerEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,        // a folder, or a tag
+    pub tag: Option<String>, // full name of the tag in the tag mode
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawerMode {
+    Files,
+    Tags,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawerInput {
+    NewFile,
+    NewFolder,
+    Rename,
+    Delete, // waiting for y/n
+}
+
+// requests to the app core. taken by App after update_view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawerAction {
+    Open(PathBuf),
+    Rename(PathBuf, PathBuf), // the app moves it and rewrites the links
+    Deleted(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct DrawerBuffer {
+    pub root: PathBuf,
+    pub entries: Vec<DrawerEntry>,
+    pub selected: usize,
+    pub top: usize,
+    pub info: ViewBufferInfo,
+    pub mode: DrawerMode,
+    expanded: HashSet<PathBuf>,
+    expanded_tags: HashSet<String>,
+    tags: Vec<(String, usize)>, // with the number of notes
+    tag_notes: BTreeMap<String, Vec<(PathBuf, String)>>, // path and title by tag
+    input: Option<(DrawerInput, String)>,
+    message: Option<String>,
+    action: Option<DrawerAction>,
+}
+
+impl ViewBuffer for DrawerBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, event: Event) {
+        if self.info.focus {
+            if self.input.is_some() {
+                self.update_input(event);
+            } else {
+                self.message = None;
+                self.update_tree(event);
+            }
+        }
+
+        // keep selected row inside the view
+        let rows = self.tree_height();
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if rows > 0 && self.selected >= self.top + rows {
+            self.top = self.selected + 1 - rows;
+        }
+
+        let header = match self.mode {
+            DrawerMode::Files => format!("{}/", self.root_name()),
+            DrawerMode::Tags => String::from("#tags"),
+        };
+        let mut view = vec![header];
+        for (i, entry) in self.entries.iter().enumerate().skip(self.top).take(rows) {
+            let mark = if !entry.is_dir {
+                "  "
+            } else if self.is_expanded(entry) {
+                "▾ "
+            } else {
+                "▸ "
+            };
+            let suffix = if entry.is_dir && entry.tag.is_none() {
+                "/"
+            } else {
+                ""
+            };
+            let line = format!(
+                "{}{}{}{}",
+                "  ".repeat(entry.depth),
+                mark,
+                entry.name,
+                suffix
+            );
+            if i == self.selected && self.info.focus {
+                view.push(format!(
+                    "{}{}{}",
+                    termion::style::Invert,
+                    line,
+                    termion::style::Reset
+                ));
+            } else {
+                view.push(line);
+            }
+        }
+        self.info.cursor = Cursor {
+            x: 0,
+            y: self.selected.saturating_sub(self.top) + 1,
+        };
+        if let Some(line) = self.bottom_line() {
+            while view.len() < self.info.height.saturating_sub(1) {
+                view.push(String::new());
+            }
+            if self.input.is_some() {
+                self.info.cursor = Cursor {
+                    x: line.width(),
+                    y: view.len(),
+                };
+            }
+            view.push(line);
+        }
+        self.info.buffer = view;
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+
+    fn get_commands(&self) -> Vec<(String, Event)> {
+        [
+            ("Open / Toggle Folder", Key::Char('\n')),
+            ("Expand Folder", Key::Right),
+            ("Collapse Folder", Key::Left),
+            ("New File", Key::Char('a')),
+            ("New Folder", Key::Char('A')),
+            ("Rename", Key::Char('r')),
+            ("Delete", Key::Char('d')),
+            ("Refresh", Key::Char('R')),
+            ("Toggle Tags", Key::Char('t')),
+        ]
+        .into_iter()
+        .map(|(name, key)| (name.to_string(), Event::Key(key)))
+        .collect()
+    }
+}
+
+impl DrawerBuffer {
+    pub fn new(root: &Path) -> Self {
+        let mut drawer = DrawerBuffer {
+            root: root.to_path_buf(),
+            entries: vec![],
+            selected: 0,
+            top: 0,
+            info: ViewBufferInfo {
+                width: 30,
+                height: 40,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            mode: DrawerMode::Files,
+            expanded: HashSet::new(),
+            expanded_tags: HashSet::new(),
+            tags: vec![],
+            tag_notes: BTreeMap::new(),
+            input: None,
+            message: None,
+            action: None,
+        };
+        drawer.refresh();
+        drawer
+    }
+
+    pub fn take_action(&mut self) -> Option<DrawerAction> {
+        self.action.take()
+    }
+
+    pub fn selected_entry(&self) -> Option<&DrawerEntry> {
+        self.entries.get(self.selected)
+    }
+
+    // rebuild visible rows from the file system, or from the tags.
+    pub fn refresh(&mut self) {
+        let mut entries = vec![];
+        match self.mode {
+            DrawerMode::Files => {
+                self.expanded.retain(|p| p.is_dir());
+                read_tree(&self.root, 0, &self.expanded, &mut entries);
+            }
+            DrawerMode::Tags => self.read_tags(None, 0, &mut entries),
+        }
+        self.entries = entries;
+        if self.selected >= self.entries.len() {
+            self.selected = self.entries.len().saturating_sub(1);
+        }
+    }
+
+    // tags of the notes in the vault, for the tag mode
+    pub fn set_tags(&mut self, vault: &Vault) {
+        self.tags = vault.tags();
+        self.tag_notes.clear();
+        for note in vault.notes() {
+            for tag in note.tags.iter() {
+                self.tag_notes
+                    .entry(tag.clone())
+                    .or_default()
+                    .push((vault.root.join(&note.path), note.title.clone()));
+            }
+        }
+        for notes in self.tag_notes.values_mut() {
+            notes.sort_by_key(|n| n.1.to_lowercase());
+        }
+        self.expanded_tags
+            .retain(|t| self.tags.iter().any(|(tag, _)| tag == t));
+        if self.mode == DrawerMode::Tags {
+            self.refresh();
+        }
+    }
+
+    pub fn select_path(&mut self, path: &Path) {
+        if self.mode == DrawerMode::Tags {
+            return; // the note may be under many tags
+        }
+        // open parent dirs so the path becomes visible
+        let mut parent = path.parent();
+        while let Some(p) = parent {
+            if p == self.root || !p.starts_with(&self.root) {
+                break;
+            }
+            self.expanded.insert(p.to_path_buf());
+            parent = p.parent();
+        }
+        self.refresh();
+        if let Some(i) = self.entries.iter().position(|e| e.path == path) {
+            self.selected = i;
+        }
+    }
+
+    fn update_tree(&mut self, event: Event) {
+        match event {
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Char('j'))
+                if self.selected + 1 < self.entries.len() =>
+            {
+                self.selected += 1;
+            }
+            Event::Key(Key::Right) | Event::Key(Key::Char('l')) => {
+                if let Some(entry) = self.selected_entry().cloned() {
+                    if entry.is_dir {
+                        self.set_expanded(&entry, true);
+                    }
+                }
+            }
+            Event::Key(Key::Left) | Event::Key(Key::Char('h')) => {
+                if let Some(entry) = self.selected_entry().cloned() {
+                    if entry.is_dir && self.is_expanded(&entry) {
+                        self.set_expanded(&entry, false);
+                    } else if let Some(i) = self.entries[..self.selected]
+                        .iter()
+                        .rposition(|e| e.depth + 1 == entry.depth)
+                    {
+                        // move to parent directory
+                        self.selected = i;
+                    }
+                }
+            }
+            Event::Key(Key::Char('\n')) => {
+                if let Some(entry) = self.selected_entry().cloned() {
+                    if !entry.is_dir {
+                        self.action = Some(DrawerAction::Open(entry.path));
+                    } else {
+                        let expand = !self.is_expanded(&entry);
+                        self.set_expanded(&entry, expand);
+                    }
+                }
+            }
+            Event::Key(Key::Char('t')) => {
+                self.mode = match self.mode {
+                    DrawerMode::Files => DrawerMode::Tags,
+                    DrawerMode::Tags => DrawerMode::Files,
+                };
+                self.selected = 0;
+                self.top = 0;
+                self.refresh();
+            }
+            Event::Key(Key::Char('R')) => {
+                self.refresh();
+            }
+            // files are not edited in the tag mode
+            _ if self.mode == DrawerMode::Tags => {}
+            Event::Key(Key::Char('a')) => {
+                self.input = Some((DrawerInput::NewFile, String::new()));
+            }
+            Event::Key(Key::Char('A')) => {
+                self.input = Some((DrawerInput::NewFolder, String::new()));
+            }
+            Event::Key(Key::Char('r')) => {
+                if let Some(entry) = self.selected_entry() {
+                    let name = entry
+                        .path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    self.input = Some((DrawerInput::Rename, name));
+                }
+            }
+            Event::Key(Key::Char('d')) if self.selected_entry().is_some() => {
+                self.input = Some((DrawerInput::Delete, String::new()));
+            }
+            _ => {}
+        }
+    }
+
+    fn update_input(&mut self, event: Event) {
+        let Some((kind, mut text)) = self.input.take() else {
+            return;
+        };
+        match event {
+            Event::Key(Key::Esc) | Event::Key(Key::Ctrl('c')) => {}
+            Event::Key(Key::Char('\n')) => {
+                if let Err(e) = self.commit_input(&kind, &text) {
+                    self.message = Some(e.to_string());
+                }
+            }
+            Event::Key(Key::Char(c)) if kind == DrawerInput::Delete => match c {
+                'y' | 'Y' => {
+                    if let Err(e) = self.commit_input(&kind, &text) {
+                        self.message = Some(e.to_string());
+                    }
+                }
+                _ => {}
+            },
+            Event::Key(Key::Char(c)) => {
+                text.push(c);
+                self.input = Some((kind, text));
+            }
+            Event::Key(Key::Backspace) => {
+                text.pop();
+                self.input = Some((kind, text));
+            }
+            _ => {
+                self.input = Some((kind, text));
+            }
+        }
+    }
+
+    fn commit_input(&mut self, kind: &DrawerInput, text: &str) -> io::Result<()> {
+        let name = text.trim();
+        if name.is_empty() && *kind != DrawerInput::Delete {
+            return Ok(());
+        }
+        // names are relative to the folder and stay in the notes root
+        let outside = Path::new(name).components().any(|c| {
+            matches!(
+                c,
+                Component::ParentDir | Component::RootDir | Component::Prefix(_)
+            )
+        });
+        if outside && *kind != DrawerInput::Delete {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is outside the notes folder", name),
+            ));
+        }
+        match kind {
+            DrawerInput::NewFile => {
+                let path = self.target_dir().join(name);
+                if path.exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", name),
+                    ));
+                }
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::File::create(&path)?;
+                self.select_path(&path);
+                self.action = Some(DrawerAction::Open(path));
+            }
+            DrawerInput::NewFolder => {
+                let path = self.target_dir().join(name);
+                fs::create_dir_all(&path)?;
+                self.select_path(&path);
+            }
+            DrawerInput::Rename => {
+                let Some(entry) = self.selected_entry().cloned() else {
+                    return Ok(());
+                };
+                let parent = entry.path.parent().unwrap_or(&self.root).to_path_buf();
+                let path = parent.join(name);
+                if path == entry.path {
+                    return Ok(());
+                }
+                if path.exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", name),
+                    ));
+                }
+                self.action = Some(DrawerAction::Rename(entry.path, path));
+            }
+            DrawerInput::Delete => {
+                let Some(entry) = self.selected_entry().cloned() else {
+                    return Ok(());
+                };
+                if entry.is_dir {
+                    fs::remove_dir_all(&entry.path)?;
+                } else {
+                    fs::remove_file(&entry.path)?;
+                }
+                self.refresh();
+                self.action = Some(DrawerAction::Deleted(entry.path));
+            }
+        }
+        Ok(())
+    }
+
+    // after the app moved the entry
+    pub fn renamed(&mut self, from: &Path, to: &Path) {
+        if self.expanded.remove(from) {
+            self.expanded.insert(to.to_path_buf());
+        }
+        self.refresh();
+        self.select_path(to);
+    }
+
+    fn is_expanded(&self, entry: &DrawerEntry) -> bool {
+        match &entry.tag {
+            Some(tag) => self.expanded_tags.contains(tag),
+            None => self.expanded.contains(&entry.path),
+        }
+    }
+
+    fn set_expanded(&mut self, entry: &DrawerEntry, expand: bool) {
+        match (&entry.tag, expand) {
+            (Some(tag), true) => self.expanded_tags.insert(tag.clone()),
+            (Some(tag), false) => self.expanded_tags.remove(tag),
+            (None, true) => self.expanded.insert(entry.path.clone()),
+            (None, false) => self.expanded.remove(&entry.path),
+        };
+        self.refresh();
+    }
+
+    // tags under the parent, with the notes of the expanded tags
+    fn read_tags(&self, parent: Option<&str>, depth: usize, entries: &mut Vec<DrawerEntry>) {
+        for (tag, count) in self.tags.iter() {
+            let (tag_parent, name) = match tag.rsplit_once('/') {
+                Some((tag_parent, name)) => (Some(tag_parent), name),
+                None => (None, tag.as_str()),
+            };
+            if tag_parent != parent {
+                continue;
+            }
+            entries.push(DrawerEntry {
+                path: PathBuf::new(),
+                name: format!("#{} ({})", name, count),
+                depth,
+                is_dir: true,
+                tag: Some(tag.clone()),
+            });
+            if !self.expanded_tags.contains(tag) {
+                continue;
+            }
+            self.read_tags(Some(tag), depth + 1, entries);
+            for (path, title) in self.tag_notes.get(tag).into_iter().flatten() {
+                entries.push(DrawerEntry {
+                    path: path.clone(),
+                    name: title.clone(),
+                    depth: depth + 1,
+                    is_dir: false,
+                    tag: None,
+                });
+            }
+        }
+    }
+
+    // new files are created in the selected dir, or next to the selected file.
+    fn target_dir(&self) -> PathBuf {
+        match self.selected_entry() {
+            Some(entry) if entry.is_dir => entry.path.clone(),
+            Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+
+    fn root_name(&self) -> String {
+        self.root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.root.to_string_lossy().to_string())
+    }
+
+    fn bottom_line(&self) -> Option<String> {
+        if let Some((kind, text)) = &self.input {
+            let label = match kind {
+                DrawerInput::NewFile => "new file: ",
+                DrawerInput::NewFolder => "new folder: ",
+                DrawerInput::Rename => "rename: ",
+                DrawerInput::Delete => "delete? (y/n) ",
+            };
+            return Some(format!("{}{}", label, text));
+        }
+        self.message.clone()
+    }
+
+    fn tree_height(&self) -> usize {
+        let mut rows = self.info.height.saturating_sub(1);
+        if self.bottom_line().is_some() {
+            rows = rows.saturating_sub(1);
+        }
+        rows
+    }
+}
+
+fn read_tree(
+    dir: &Path,
+    depth: usize,
+    expanded: &HashSet<PathBuf>,
+    entries: &mut Vec<DrawerEntry>,
+) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut children = read_dir
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .map(|e| (e.path(), e.path().is_dir()))
+        .collect::<Vec<(PathBuf, bool)>>();
+    // directories first, then by name
+    children.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (path, is_dir) in children {
+        entries.push(DrawerEntry {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path: path.clone(),
+            depth,
+            is_dir,
+            tag: None,
+        });
+        if is_dir && expanded.contains(&path) {
+            read_tree(&path, depth + 1, expanded, entries);
+        }
+    }
+}
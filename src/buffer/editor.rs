@@ -1,23 +1,95 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
 use termion::event::{Event, Key};
-use unicode_width::UnicodeWidthChar;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 // use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use super::{
     buffer::{Cursor, ViewBuffer, ViewBufferInfo},
+    snippet::{parse_snippet, Snippet, TabStop},
     text::TextBuffer,
 };
+use crate::note::fold::Blocks;
+use crate::note::front_matter;
+use crate::note::link::{find_links, Link};
+use crate::note::list::{
+    item_end, next_marker, parse_item, renumber, set_checkbox, set_number, siblings,
+};
+use crate::note::outline;
+use crate::note::tag::is_tag_char;
+use crate::note::task::fix_due_dates;
+use crate::note::time::DateTime;
+
+const COMPLETION_ROWS: usize = 8;
+const INDENT: usize = 4; // spaces of Tab
 
 // EditorBuffer内で管理するカーソルのX位置とアプリコアに渡すX位置は異なる。
 // 例えば、カーソルを一つ下の行に移動したとき、元のカーソルのX位置より行が短かった場合は、カーソルはその行の行末に移動するだろう。
 // このときアプリケーションのコアクラスには表示するためのカーソル位置を渡すが、EditorBufferのカーソルX位置はそのままになる。
 // そうすることで次に行を移動したときにカーソル位置を復元できる。
 
+// フロントマターは折りたためる。折りたたんでいる間は1行の要約で表示し、
+// カーソルがフロントマターの中に入ったときだけ展開する。
+// Alt-z(Alt-Shift-zですべて展開)で見出しのセクション、子のあるリストの項目、コードブロックも折りたためる。
+// こちらは最初の行に要約を付けて表示し、上下の移動では畳んだ行を飛び越える。
+// 編集で行がずれたら折りたたみの行も合わせてずらし、編集などでカーソルが中に入ったら展開する。
+
+// "#" に続けて入力している間はタグの候補を、"[[" の後ではノートのタイトルを
+// カーソルの下に表示する。[[リンク]] は下線付きで表示する。
+
+// カーソルの前の単語がスニペットのトリガーならTabで展開する。展開した後はTab/Shift-Tabで
+// タブストップを移動し、最後に $0 (なければスニペットの末尾) に着いたら終わる。
+// タブストップの外に出たときや行数が変わったときも終わる。
+
+// リストの項目でEnterを押すと次の項目の記号を入れ(番号は振り直す)、空の項目では記号を消してリストを終える。
+// リストの項目の上のTab/Shift-Tabは、カーソルの位置ではなく子の項目ごと字下げを増やす/減らす。
+// Ctrl-dでその行(選択中は選択した行すべて)のチェックボックスを切り替える。
+
+// Alt-K/Alt-Jで上下の行にカーソルを足し、Alt-Nで選択した文字列の次の出現にカーソルを足す。
+// カーソルが複数ある間は、リストやスニペット、補完は使わずにそのまま入力する。
+// チェックボックスの切り替えとセクションの移動もしない。Escで一つに戻す。
+
+// candidates for the word before the cursor
+#[derive(Debug, Clone)]
+struct Completion {
+    start: usize,         // x of the word
+    prefix: &'static str, // before the word, "#" or "[["
+    suffix: &'static str, // added after the item
+    items: Vec<String>,
+    selected: usize,
+}
+
+// tab stops of the expanded snippet
+#[derive(Debug, Clone)]
+struct SnippetSession {
+    stops: Vec<TabStop>, // in the text, in order of appearance
+    order: Vec<usize>,   // numbers of the stops to visit, 0 is the last
+    current: usize,      // index in order
+    line_count: usize,   // of the text, to see how the last key changed it
+    line_len: usize,     // of the line of the current stop
+}
+
 #[derive(Debug, Clone)]
 pub struct EditorBuffer {
     pub text: TextBuffer,
     pub top: usize, // 表示されている最上行
     pub top_wrap: usize,
     pub info: ViewBufferInfo,
+    pub path: Option<PathBuf>,
+    pub crlf: bool, // line ending of the file
+    pub bom: bool,
+    pub fold_front_matter: bool,
+    folds: Vec<(usize, usize)>, // first and last lines of the blocks folded with Alt-z
+    folded: Vec<(usize, usize)>, // the folds and the folded front matter shown now
+    fold_base: Option<(usize, Vec<Vec<char>>)>, // revision and text the folds are on
+    pub tags: Vec<String>,      // completed after "#"
+    pub titles: Vec<String>,    // of notes, completed after "[["
+    pub snippets: Vec<Snippet>,
+    completion: Option<Completion>,
+    snippet: Option<SnippetSession>,
+    saved_revision: usize,
 }
 
 impl ViewBuffer for EditorBuffer {
@@ -27,16 +99,17 @@ impl ViewBuffer for EditorBuffer {
         self.info.focus = focus;
     }
     fn update_view(&mut self, event: termion::event::Event) {
-        if self.info.focus {
-            match event {
+        let cursor_y = self.text.get_cursor_pos().y;
+        if self.info.focus && !self.update_completion(&event) {
+            match event.clone() {
                 Event::Key(Key::Char('\n')) => {
-                    self.text.enter();
+                    self.enter();
                 }
                 Event::Key(Key::Char('\t')) => {
-                    self.text.input(' ');
-                    self.text.input(' ');
-                    self.text.input(' ');
-                    self.text.input(' ');
+                    self.tab();
+                }
+                Event::Key(Key::BackTab) => {
+                    self.back_tab();
                 }
                 Event::Key(Key::Char(c)) => {
                     self.text.input(c);
@@ -59,27 +132,65 @@ impl ViewBuffer for EditorBuffer {
                 Event::Key(Key::Down) => {
                     self.text.down(false);
                 }
-                Event::Unsupported(c) => {
-                    if c == vec![27, 91, 49, 59, 50, 65] {
-                        // Shift Up
-                        self.text.up(true);
-                    } else if c == vec![27, 91, 49, 59, 50, 66] {
-                        // Shift Down
-                        self.text.down(true);
-                    } else if c == vec![27, 91, 49, 59, 50, 67] {
-                        // Shift Right
-                        self.text.right(true);
-                    } else if c == vec![27, 91, 49, 59, 50, 68] {
-                        // Shift Left
-                        self.text.left(true);
-                    }
-                }
+                Event::Unsupported(_) => match shift_arrow(&event) {
+                    Some(Key::Up) => self.text.up(true),
+                    Some(Key::Down) => self.text.down(true),
+                    Some(Key::Right) => self.text.right(true),
+                    Some(Key::Left) => self.text.left(true),
+                    _ => {}
+                },
                 Event::Key(Key::Ctrl('a')) => {
                     self.text.select_all();
                 }
+                Event::Key(Key::Ctrl('z')) => {
+                    self.text.undo();
+                }
+                Event::Key(Key::Ctrl('y')) => {
+                    self.text.redo();
+                }
+                Event::Key(Key::Alt('m')) => {
+                    self.set_fold_front_matter(!self.fold_front_matter);
+                }
+                Event::Key(Key::Ctrl('d')) if self.text.cursor_count() == 1 => {
+                    self.toggle_tasks();
+                }
+                Event::Key(Key::Alt('z')) => {
+                    self.toggle_fold();
+                }
+                Event::Key(Key::Alt('Z')) => {
+                    self.unfold_all();
+                }
+                Event::Key(Key::Alt('K')) => {
+                    self.text.add_cursor(false);
+                }
+                Event::Key(Key::Alt('J')) => {
+                    self.text.add_cursor(true);
+                }
+                Event::Key(Key::Alt('N')) => {
+                    self.text.add_next_occurrence();
+                }
+                Event::Key(Key::Esc) => {
+                    self.text.clear_cursors();
+                }
                 _ => {}
             }
+            match event {
+                Event::Key(Key::Char('\t')) | Event::Key(Key::BackTab) => {}
+                Event::Key(Key::Ctrl('z')) | Event::Key(Key::Ctrl('y')) => self.snippet = None,
+                _ if self.text.cursor_count() > 1 => self.snippet = None,
+                _ => self.track_snippet(),
+            }
+            self.completion = match event {
+                _ if self.text.cursor_count() > 1 => None,
+                Event::Key(Key::Char(c)) if c != '\n' && c != '\t' => self.complete(),
+                Event::Key(Key::Backspace) if self.completion.is_some() => self.complete(),
+                _ => None,
+            };
+        }
+        if !self.info.focus {
+            self.completion = None;
         }
+        self.update_folds(&event, cursor_y);
         // calc lines and build view buffer !!!
         // most difficult point in this project .
 
@@ -87,7 +198,11 @@ impl ViewBuffer for EditorBuffer {
         if self.text.text.len() <= self.top {
             self.top = self.text.text.len() - 1;
         }
-        let wrap_count = self.split_line_by_width(self.top).len() - 1;
+        if let Some((first, _)) = self.hidden_in(self.top) {
+            self.top = first;
+            self.top_wrap = 0;
+        }
+        let wrap_count = self.line_rows(self.top).len().saturating_sub(1);
         if wrap_count < self.top_wrap {
             self.top_wrap = wrap_count;
         }
@@ -113,26 +228,30 @@ impl ViewBuffer for EditorBuffer {
             }
         }
 
-        // build view buffer
-        let mut splited_lines = vec![];
+        // build view buffer. rows have the line and the char index where they start.
+        let mut splited_lines: Vec<(usize, usize, Vec<char>)> = vec![];
         for i in self.top..self.text.text.len() {
             if i == text_cursor.y {
+                let x = self.get_wrap_x(&text_cursor, wrap_count);
                 if i == self.top {
                     self.info.cursor = Cursor {
-                        x: 0, // wip : must calc x pos
+                        x,
                         y: splited_lines.len() + wrap_count - self.top_wrap,
                     };
                 } else {
                     self.info.cursor = Cursor {
-                        x: 0, // wip : must calc x pos
+                        x,
                         y: splited_lines.len() + wrap_count,
                     };
                 }
             }
-            if i == self.top {
-                splited_lines.append(&mut self.split_line_by_width(i)[self.top_wrap..].to_vec());
-            } else {
-                splited_lines.append(&mut self.split_line_by_width(i));
+            let skip = if i == self.top { self.top_wrap } else { 0 };
+            let rows = self.line_rows(i);
+            let mut offset = rows.iter().take(skip).map(|r| r.len()).sum::<usize>();
+            for row in rows.into_iter().skip(skip) {
+                let len = row.len();
+                splited_lines.push((i, offset, row));
+                offset += len;
             }
             if splited_lines.len() >= self.info.height {
                 break;
@@ -144,9 +263,10 @@ impl ViewBuffer for EditorBuffer {
 
         let view_buffer = splited_lines
             .into_iter()
-            .map(|l| l.into_iter().collect::<String>())
+            .map(|(i, offset, row)| self.style_row(i, offset, &row))
             .collect::<Vec<String>>();
         self.info.buffer = view_buffer;
+        self.show_completion();
         // wip : まだ、行の不足分をスペースで埋める処理をしてない。
     }
     fn get_view(&self) -> Vec<String> {
@@ -159,6 +279,24 @@ impl ViewBuffer for EditorBuffer {
         let cursor = self.info.cursor.clone();
         return (cursor.x, cursor.y);
     }
+
+    fn get_commands(&self) -> Vec<(String, Event)> {
+        [
+            ("Undo", Key::Ctrl('z')),
+            ("Redo", Key::Ctrl('y')),
+            ("Select All", Key::Ctrl('a')),
+            ("Toggle Front Matter", Key::Alt('m')),
+            ("Toggle Task", Key::Ctrl('d')),
+            ("Toggle Fold", Key::Alt('z')),
+            ("Unfold All", Key::Alt('Z')),
+            ("Add Cursor Above", Key::Alt('K')),
+            ("Add Cursor Below", Key::Alt('J')),
+            ("Add Next Occurrence", Key::Alt('N')),
+        ]
+        .into_iter()
+        .map(|(name, key)| (name.to_string(), Event::Key(key)))
+        .collect()
+    }
 }
 
 impl EditorBuffer {
@@ -174,9 +312,883 @@ impl EditorBuffer {
                 cursor: Cursor { x: 0, y: 0 },
                 buffer: vec![],
             },
+            path: None,
+            crlf: false,
+            bom: false,
+            fold_front_matter: false,
+            folds: vec![],
+            folded: vec![],
+            fold_base: None,
+            tags: vec![],
+            titles: vec![],
+            snippets: vec![],
+            completion: None,
+            snippet: None,
+            saved_revision: 0,
+        }
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let text = if path.exists() {
+            fs::read_to_string(path)?
+        } else {
+            String::new()
+        };
+        let bom = text.starts_with('\u{feff}');
+        let text = text.trim_start_matches('\u{feff}');
+        let mut editor = EditorBuffer::new(text);
+        editor.path = Some(path.to_path_buf());
+        editor.crlf = text.contains("\r\n");
+        editor.bom = bom;
+        Ok(editor)
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no file name"));
+        };
+        if self.is_modified() {
+            self.touch_front_matter();
+            self.fix_due_dates();
+        }
+        let line_ending = if self.crlf { "\r\n" } else { "\n" };
+        let mut content = self.join_lines(line_ending);
+        content.push_str(line_ending);
+        if self.bom {
+            content.insert(0, '\u{feff}');
+        }
+        fs::write(path, content)?;
+        self.saved_revision = self.text.revision();
+        Ok(())
+    }
+
+    // write @today and @tomorrow of the tasks as dates
+    fn fix_due_dates(&mut self) {
+        let content = self.content();
+        let fixed = fix_due_dates(&content, &DateTime::today());
+        if fixed == content {
+            return;
+        }
+        let lines = fixed
+            .split('\n')
+            .map(|l| l.chars().collect())
+            .collect::<Vec<Vec<char>>>();
+        // the cursor moves with the words rewritten before it
+        let cursor = self.text.get_cursor_pos();
+        let old = self.text.text[cursor.y].split(|c| *c == ' ');
+        let new = lines[cursor.y].split(|c| *c == ' ');
+        let (mut x, mut end) = (cursor.x, 0);
+        for (a, b) in old.zip(new) {
+            end += a.len();
+            if end > cursor.x {
+                break;
+            }
+            x = x + b.len() - a.len();
+            end += 1;
+        }
+        self.replace_text(lines, x, cursor.y);
+    }
+
+    // set "updated" in the front matter to now.
+    // the date only style of the field, or of "created" for a new field, is kept.
+    fn touch_front_matter(&mut self) {
+        let Some(front) = self.text.front_matter() else {
+            return;
+        };
+        let now = DateTime::now();
+        let value = match front.updated.as_ref().or(front.created.as_ref()) {
+            Some(updated) if updated.len() == 10 => now.format("%Y-%m-%d"),
+            _ => now.format("%Y-%m-%d %H:%M"),
+        };
+        if front.updated.as_ref() == Some(&value) {
+            return;
+        }
+        let lines = self.text.text[..front.line_count()]
+            .iter()
+            .map(|l| l.iter().collect::<String>())
+            .collect::<Vec<String>>();
+        // "modified" is the other name of the field
+        let key = if front.updated.is_some() && !lines.iter().any(|l| l.starts_with("updated:")) {
+            "modified"
+        } else {
+            "updated"
+        };
+        if let Some(lines) = front_matter::set_field(&lines, key, &value) {
+            let lines = lines.into_iter().map(|l| l.chars().collect()).collect();
+            self.text.replace_lines(0, front.line_count(), lines);
+        }
+    }
+
+    // fold the front matter. the cursor is moved out of it to fold it.
+    pub fn set_fold_front_matter(&mut self, fold: bool) {
+        self.fold_front_matter = fold;
+        if let Some(front) = self.text.front_matter() {
+            let cursor = self.text.get_cursor_pos();
+            if fold && cursor.y <= front.end && front.end + 1 < self.text.text.len() {
+                self.text.set_cursor(0, front.end + 1);
+            }
         }
     }
 
+    // fold the block around the cursor, or unfold the one on the cursor line
+    pub fn toggle_fold(&mut self) {
+        let cursor = self.text.get_cursor_pos();
+        if let Some(i) = self.folds.iter().position(|(first, _)| *first == cursor.y) {
+            self.folds.remove(i);
+            return;
+        }
+        let blocks = Blocks::new(&self.text.text);
+        let Some(first) = blocks.enclosing(&self.text.text, cursor.y) else {
+            return;
+        };
+        let last = blocks.end(&self.text.text, first).unwrap();
+        self.folds.push((first, last));
+        self.folds.sort();
+        if first != cursor.y {
+            self.text.set_cursor(0, first);
+        }
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+    }
+
+    // move the folds with the lines an edit inserted or removed, and find the lines
+    // they hide. the cursor steps over a fold when it moved, and opens it otherwise.
+    // the blocks are found again only when the text has changed.
+    fn update_folds(&mut self, event: &Event, cursor_y: usize) {
+        let revision = self.text.revision();
+        if self
+            .fold_base
+            .as_ref()
+            .is_some_and(|(base, _)| *base != revision)
+        {
+            let (_, old) = self.fold_base.take().unwrap();
+            self.shift_folds(&old);
+        }
+        self.folded = self.folds.clone();
+        let moved = match event {
+            Event::Key(Key::Up | Key::Down | Key::Left | Key::Right) => Some(false),
+            _ => shift_arrow(event).map(|_| true),
+        };
+        while let Some((first, last)) = self.hidden_in(self.text.get_cursor_pos().y) {
+            let y = self.text.get_cursor_pos().y;
+            match moved {
+                Some(select) if y > cursor_y && last + 1 < self.text.text.len() => {
+                    while self.text.get_cursor_pos().y <= last {
+                        self.text.down(select);
+                    }
+                }
+                Some(select) => {
+                    while self.text.get_cursor_pos().y > first {
+                        self.text.up(select);
+                    }
+                }
+                None => {
+                    self.folds
+                        .retain(|(first, last)| !(*first < y && y <= *last));
+                    self.folded = self.folds.clone();
+                }
+            }
+        }
+        if let Some(front) = self.text.front_matter() {
+            let y = self.text.get_cursor_pos().y;
+            if self.fold_front_matter && y > front.end && !self.folds.iter().any(|f| f.0 == 0) {
+                self.folded.insert(0, (0, front.end));
+            }
+        }
+        if self.folds.is_empty() {
+            self.fold_base = None;
+        } else if self.fold_base.is_none() {
+            self.fold_base = Some((revision, self.text.text.clone()));
+        }
+    }
+
+    // keep the folds on their lines after the text changed from old,
+    // and find where their blocks end now
+    fn shift_folds(&mut self, old: &[Vec<char>]) {
+        let new = &self.text.text;
+        let prefix = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let old_end = old.len() - suffix;
+        let new_end = new.len() - suffix;
+        let blocks = Blocks::new(new);
+        self.folds = self
+            .folds
+            .iter()
+            .filter_map(|(first, _)| {
+                if *first < prefix || old_end == new_end {
+                    Some(*first)
+                } else if *first >= old_end {
+                    Some(first + new_end - old_end)
+                } else {
+                    None
+                }
+            })
+            .filter_map(|first| blocks.end(new, first).map(|last| (first, last)))
+            .collect();
+    }
+
+    // the outermost fold which hides the line
+    fn hidden_in(&self, line: usize) -> Option<(usize, usize)> {
+        self.folded
+            .iter()
+            .filter(|(first, last)| *first < line && line <= *last)
+            .min_by_key(|(first, _)| *first)
+            .copied()
+    }
+
+    // text of the buffer with LF line endings
+    pub fn content(&self) -> String {
+        self.join_lines("\n")
+    }
+
+    fn join_lines(&self, line_ending: &str) -> String {
+        self.text
+            .text
+            .iter()
+            .map(|l| l.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join(line_ending)
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.text.revision() != self.saved_revision
+    }
+
+    // empty buffer without file, which can be replaced by an opened note
+    pub fn is_scratch(&self) -> bool {
+        self.path.is_none() && !self.is_modified() && self.text.text == vec![vec![]]
+    }
+
+    pub fn encoding(&self) -> &str {
+        if self.bom {
+            "UTF-8 BOM"
+        } else {
+            "UTF-8"
+        }
+    }
+
+    pub fn line_ending(&self) -> &str {
+        if self.crlf {
+            "CRLF"
+        } else {
+            "LF"
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            None => String::from("[scratch]"),
+        }
+    }
+
+    // keys for the open completion. returns true if the key is used.
+    fn update_completion(&mut self, event: &Event) -> bool {
+        let Some(completion) = &mut self.completion else {
+            return false;
+        };
+        let len = completion.items.len();
+        match event {
+            Event::Key(Key::Up) => completion.selected = (completion.selected + len - 1) % len,
+            Event::Key(Key::Down) => completion.selected = (completion.selected + 1) % len,
+            Event::Key(Key::Char('\t')) | Event::Key(Key::Char('\n')) => {
+                let start = completion.start;
+                let suffix = completion.suffix.chars().collect::<Vec<char>>();
+                let item = completion.items[completion.selected]
+                    .chars()
+                    .collect::<Vec<char>>();
+                self.completion = None;
+                let cursor = self.text.get_cursor_pos();
+                for _ in start..cursor.x {
+                    self.text.back();
+                }
+                for c in item {
+                    self.text.input(c);
+                }
+                // the suffix may be typed already
+                let cursor = self.text.get_cursor_pos();
+                if self.text.text[cursor.y][cursor.x..].starts_with(&suffix) {
+                    for _ in suffix.iter() {
+                        self.text.right(false);
+                    }
+                } else {
+                    for c in suffix {
+                        self.text.input(c);
+                    }
+                }
+            }
+            Event::Key(Key::Esc) => self.completion = None,
+            _ => return false,
+        }
+        true
+    }
+
+    // next tab stop, a snippet, the list item or four spaces
+    fn tab(&mut self) {
+        if self.text.cursor_count() == 1
+            && (self.jump_snippet(true) || self.expand_snippet() || self.indent_item(false))
+        {
+            return;
+        }
+        for _ in 0..INDENT {
+            self.text.input(' ');
+        }
+    }
+
+    // previous tab stop, or outdent the list item
+    fn back_tab(&mut self) {
+        if self.text.cursor_count() == 1 && !self.jump_snippet(false) {
+            self.indent_item(true);
+        }
+    }
+
+    // continue the list, or end it on an empty item
+    fn enter(&mut self) {
+        let cursor = self.text.get_cursor_pos();
+        let line = self.text.text[cursor.y].clone();
+        let item = match parse_item(&line) {
+            Some(item)
+                if !self.text.is_selecting()
+                    && self.text.cursor_count() == 1
+                    && cursor.x >= item.content =>
+            {
+                item
+            }
+            _ => {
+                self.text.enter();
+                return;
+            }
+        };
+        let mut lines = self.text.text.clone();
+        if line[item.content..].iter().all(|c| c.is_whitespace()) {
+            lines[cursor.y] = vec![];
+            self.replace_text(lines, 0, cursor.y);
+            return;
+        }
+        let mut next = next_marker(&line, &item);
+        let x = next.len();
+        next.extend(line[cursor.x..].iter().skip_while(|c| **c == ' '));
+        lines[cursor.y].truncate(cursor.x);
+        lines.insert(cursor.y + 1, next);
+        renumber(&mut lines, cursor.y + 1);
+        self.replace_text(lines, x, cursor.y + 1);
+    }
+
+    // move the list item and its children by one indent. false if the line is no item.
+    fn indent_item(&mut self, outdent: bool) -> bool {
+        let cursor = self.text.get_cursor_pos();
+        if self.text.is_selecting() {
+            return false;
+        }
+        let Some(item) = parse_item(&self.text.text[cursor.y]) else {
+            return false;
+        };
+        let mut lines = self.text.text.clone();
+        let end = item_end(&lines, cursor.y);
+        for line in lines[cursor.y..end].iter_mut() {
+            if !outdent {
+                line.splice(0..0, [' '; INDENT]);
+            } else if line.first() == Some(&'\t') {
+                line.remove(0);
+            } else {
+                let spaces = line.iter().take(INDENT).take_while(|c| **c == ' ').count();
+                line.drain(..spaces);
+            }
+        }
+        if item.number.is_some() {
+            // the number follows the item before it in the new list, or starts from 1
+            let number = siblings(&lines, cursor.y)
+                .into_iter()
+                .rfind(|i| *i < cursor.y)
+                .and_then(|i| parse_item(&lines[i])?.number)
+                .map_or(1, |n| n + 1);
+            set_number(&mut lines[cursor.y], number);
+            renumber(&mut lines, cursor.y);
+            // and the list which the item left
+            if lines
+                .get(end)
+                .and_then(|line| parse_item(line))
+                .is_some_and(|next| next.indent == item.indent)
+            {
+                renumber(&mut lines, end);
+            }
+        }
+        let x = (cursor.x + lines[cursor.y].len()).saturating_sub(self.text.text[cursor.y].len());
+        self.replace_text(lines, x, cursor.y);
+        true
+    }
+
+    // check the tasks on the line or the selected lines, or uncheck them if all are done.
+    // lines which are not tasks become tasks.
+    fn toggle_tasks(&mut self) {
+        let (start, end) = self.text.selected_lines();
+        let selecting = self.text.is_selecting();
+        let mut lines = self.text.text.clone();
+        let targets = (start..=end)
+            .filter(|y| lines[*y].iter().any(|c| !c.is_whitespace()))
+            .collect::<Vec<usize>>();
+        if targets.is_empty() {
+            return;
+        }
+        // lines without a checkbox become open tasks
+        let checkbox = |line: &[char]| parse_item(line).and_then(|item| item.checkbox);
+        let done = !targets
+            .iter()
+            .filter_map(|y| checkbox(&lines[*y]))
+            .all(|done| done);
+        for y in targets {
+            let done = checkbox(&lines[y]).is_some() && done;
+            set_checkbox(&mut lines[y], done);
+        }
+        let cursor = self.text.get_cursor_pos();
+        let x = (cursor.x + lines[cursor.y].len()).saturating_sub(self.text.text[cursor.y].len());
+        self.replace_text(lines, x, cursor.y);
+        if selecting {
+            let x = self.text.text[end].len();
+            self.text
+                .select_range(Cursor { x: 0, y: start }, Cursor { x, y: end });
+        }
+    }
+
+    // swap the section of the heading on the line with the one before or after it.
+    // the cursor goes to the heading.
+    pub fn move_section(&mut self, line: usize, up: bool) -> bool {
+        let mut lines = self.text.text.clone();
+        let Some(y) = outline::move_section(&mut lines, line, up) else {
+            return false;
+        };
+        self.replace_text(lines, 0, y);
+        true
+    }
+
+    // change the level of the heading on the line and the headings under it
+    pub fn shift_section(&mut self, line: usize, delta: isize) -> bool {
+        let mut lines = self.text.text.clone();
+        if !outline::shift_section(&mut lines, line, delta) {
+            return false;
+        }
+        let cursor = self.text.get_cursor_pos();
+        let x = (cursor.x + lines[cursor.y].len()).saturating_sub(self.text.text[cursor.y].len());
+        self.replace_text(lines, x, cursor.y);
+        true
+    }
+
+    // one edit which changes the text into the lines, then the cursor is moved
+    fn replace_text(&mut self, lines: Vec<Vec<char>>, x: usize, y: usize) {
+        let old = &self.text.text;
+        let prefix = old
+            .iter()
+            .zip(lines.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old
+            .iter()
+            .rev()
+            .zip(lines.iter().rev())
+            .take(old.len().min(lines.len()) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if prefix < old.len().max(lines.len()) {
+            let end = old.len() - suffix;
+            let new_lines = lines[prefix..lines.len() - suffix].to_vec();
+            self.text.replace_lines(prefix, end, new_lines);
+        }
+        self.text.set_cursor(x, y);
+    }
+
+    // replace the trigger before the cursor with its snippet. false if there is none.
+    fn expand_snippet(&mut self) -> bool {
+        if self.text.is_selecting() {
+            return false;
+        }
+        let cursor = self.text.get_cursor_pos();
+        let line = self.text.text[cursor.y].clone();
+        let start = line[..cursor.x]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1);
+        let word = line[start..cursor.x].iter().collect::<String>();
+        let Some(snippet) = self
+            .snippets
+            .iter()
+            .rev()
+            .find(|s| !word.is_empty() && s.trigger == word)
+        else {
+            return false;
+        };
+        let (body, mut stops) = parse_snippet(&snippet.body);
+        let last = body.len() - 1;
+        if !stops.iter().any(|s| s.number == 0) {
+            let x = body[last].len();
+            stops.push(TabStop {
+                number: 0,
+                y: last,
+                start: x,
+                end: x,
+            });
+        }
+        // following lines get the indent of the line
+        let indent = line
+            .iter()
+            .take_while(|c| c.is_whitespace())
+            .cloned()
+            .collect::<Vec<char>>();
+        let offset = |y: usize| if y == 0 { start } else { indent.len() };
+        let mut lines = vec![];
+        for (i, body_line) in body.into_iter().enumerate() {
+            let mut new_line = if i == 0 {
+                line[..start].to_vec()
+            } else {
+                indent.clone()
+            };
+            new_line.extend(body_line);
+            lines.push(new_line);
+        }
+        lines[last].extend_from_slice(&line[cursor.x..]);
+        self.text.replace_lines(cursor.y, cursor.y + 1, lines);
+        let stops = stops
+            .into_iter()
+            .map(|s| TabStop {
+                y: cursor.y + s.y,
+                start: s.start + offset(s.y),
+                end: s.end + offset(s.y),
+                ..s
+            })
+            .collect::<Vec<TabStop>>();
+        let mut order = stops
+            .iter()
+            .map(|s| s.number)
+            .filter(|n| *n != 0)
+            .collect::<Vec<usize>>();
+        order.sort();
+        order.dedup();
+        order.push(0);
+        self.snippet = Some(SnippetSession {
+            stops,
+            order,
+            current: 0,
+            line_count: 0,
+            line_len: 0,
+        });
+        self.select_stop();
+        true
+    }
+
+    // move to the next or previous tab stop. false if no snippet is expanded.
+    fn jump_snippet(&mut self, forward: bool) -> bool {
+        let Some(session) = &mut self.snippet else {
+            return false;
+        };
+        if forward {
+            session.current += 1;
+        } else {
+            session.current = session.current.saturating_sub(1);
+        }
+        self.select_stop();
+        true
+    }
+
+    // select the placeholder of the current stop. the snippet ends on $0.
+    fn select_stop(&mut self) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+        let number = session.order[session.current];
+        let Some(stop) = session.stops.iter().find(|s| s.number == number).cloned() else {
+            self.snippet = None;
+            return;
+        };
+        session.line_count = self.text.text.len();
+        session.line_len = self.text.text[stop.y].len();
+        self.text.select_range(
+            Cursor {
+                x: stop.start,
+                y: stop.y,
+            },
+            Cursor {
+                x: stop.end,
+                y: stop.y,
+            },
+        );
+        if number == 0 {
+            self.snippet = None;
+        }
+    }
+
+    // follow the edit in the current stop and copy it to the mirrors.
+    // the snippet ends when the cursor leaves the stop.
+    fn track_snippet(&mut self) {
+        let Some(mut session) = self.snippet.take() else {
+            return;
+        };
+        let number = session.order[session.current];
+        // mirrors come after the first stop with the number
+        let Some(p) = session.stops.iter().position(|s| s.number == number) else {
+            return;
+        };
+        let primary = session.stops[p].clone();
+        let cursor = self.text.get_cursor_pos();
+        if self.text.text.len() != session.line_count || cursor.y != primary.y {
+            return;
+        }
+        let delta = self.text.text[primary.y].len() as isize - session.line_len as isize;
+        let end = primary.end as isize + delta;
+        if end < primary.start as isize || cursor.x < primary.start || cursor.x as isize > end {
+            return;
+        }
+        shift_stops(&mut session.stops, p, delta);
+        session.stops[p].end = end as usize;
+        let value = self.text.text[primary.y][primary.start..end as usize].to_vec();
+        for i in 0..session.stops.len() {
+            let mirror = session.stops[i].clone();
+            if i == p || mirror.number != number {
+                continue;
+            }
+            self.text.text[mirror.y].splice(mirror.start..mirror.end, value.iter().cloned());
+            let delta = value.len() as isize - (mirror.end - mirror.start) as isize;
+            session.stops[i].end = mirror.start + value.len();
+            shift_stops(&mut session.stops, i, delta);
+        }
+        session.line_len = self.text.text[primary.y].len();
+        self.snippet = Some(session);
+    }
+
+    fn complete(&self) -> Option<Completion> {
+        self.complete_link().or_else(|| self.complete_tag())
+    }
+
+    // titles for "[[word" before the cursor
+    fn complete_link(&self) -> Option<Completion> {
+        let cursor = self.text.get_cursor_pos();
+        let line = &self.text.text[cursor.y][..cursor.x];
+        let open = (0..line.len().saturating_sub(1))
+            .rev()
+            .find(|i| line[*i] == '[' && line[*i + 1] == '[')?;
+        let word = &line[open + 2..];
+        if word.iter().any(|c| matches!(c, '[' | ']' | '|' | '#')) {
+            return None;
+        }
+        let word = word.iter().collect::<String>().to_lowercase();
+        let items = rank(&self.titles, &word, |t| t.to_lowercase());
+        if items.is_empty() {
+            return None;
+        }
+        Some(Completion {
+            start: open + 2,
+            prefix: "[[",
+            suffix: "]]",
+            items,
+            selected: 0,
+        })
+    }
+
+    // tags starting with the "#word" before the cursor, then tags containing it
+    fn complete_tag(&self) -> Option<Completion> {
+        let cursor = self.text.get_cursor_pos();
+        let line = &self.text.text[cursor.y][..cursor.x];
+        let start = line
+            .iter()
+            .rposition(|c| !is_tag_char(*c))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        if start == 0 || start == cursor.x || line[start - 1] != '#' {
+            return None;
+        }
+        if start >= 2 && !line[start - 2].is_whitespace() {
+            return None;
+        }
+        let word = line[start..].iter().collect::<String>().to_lowercase();
+        let mut items = rank(&self.tags, &word, |t| t.clone());
+        items.retain(|t| *t != word);
+        if items.is_empty() {
+            return None;
+        }
+        Some(Completion {
+            start,
+            prefix: "#",
+            suffix: "",
+            items,
+            selected: 0,
+        })
+    }
+
+    // draw the candidates under the cursor, or over it at the bottom of the view
+    fn show_completion(&mut self) {
+        let Some(completion) = &self.completion else {
+            return;
+        };
+        let cursor = self.text.get_cursor_pos();
+        let typed = self.text.text[cursor.y]
+            [completion.start - completion.prefix.chars().count()..cursor.x]
+            .iter()
+            .map(|c| c.width().unwrap_or(2))
+            .sum::<usize>();
+        let width = completion
+            .items
+            .iter()
+            .map(|i| i.width() + completion.prefix.width() + 2)
+            .max()
+            .unwrap_or(0)
+            .min(self.info.width);
+        let x = self
+            .info
+            .cursor
+            .x
+            .saturating_sub(typed)
+            .min(self.info.width.saturating_sub(width));
+        let rows = completion.items.len();
+        let top = if self.info.cursor.y + 1 + rows <= self.info.height {
+            self.info.cursor.y + 1
+        } else {
+            self.info.cursor.y.saturating_sub(rows)
+        };
+        while self.info.buffer.len() < (top + rows).min(self.info.height) {
+            self.info.buffer.push(String::new());
+        }
+        for (i, item) in completion.items.iter().enumerate() {
+            let Some(line) = self.info.buffer.get_mut(top + i) else {
+                break;
+            };
+            let label = format!(" {}{}", completion.prefix, item)
+                .chars()
+                .take(width)
+                .collect::<String>();
+            let label = format!("{}{}", label, " ".repeat(width - label.width()));
+            let label = if i == completion.selected {
+                format!(
+                    "{}{}{}",
+                    termion::style::Invert,
+                    label,
+                    termion::style::Reset
+                )
+            } else {
+                format!(
+                    "{}{}{}",
+                    termion::style::Faint,
+                    label,
+                    termion::style::Reset
+                )
+            };
+            *line = overlay(line, x, &label, width);
+        }
+    }
+
+    // link under the cursor
+    pub fn link_at_cursor(&self) -> Option<Link> {
+        let cursor = self.text.get_cursor_pos();
+        find_links(&self.text.text[cursor.y], cursor.y)
+            .into_iter()
+            .find(|l| l.start <= cursor.x && cursor.x < l.end)
+    }
+
+    // the row as a string, with links underlined and the other cursors inverted
+    fn style_row(&self, index: usize, offset: usize, row: &[char]) -> String {
+        let marks = self
+            .text
+            .extra_cursors()
+            .into_iter()
+            .filter(|c| c.y == index)
+            .map(|c| c.x)
+            .collect::<Vec<usize>>();
+        let folded = self.folded.iter().any(|(first, _)| *first == index);
+        if marks.is_empty() && (folded || !row.contains(&'[') && offset == 0) {
+            return row.iter().collect();
+        }
+        let links = if folded {
+            vec![]
+        } else {
+            find_links(&self.text.text[index], index)
+        };
+        let style = format!(
+            "{}{}",
+            termion::style::Underline,
+            termion::color::Fg(termion::color::Cyan)
+        );
+        let mut result = String::new();
+        let mut styled = false;
+        for (k, c) in row.iter().enumerate() {
+            let x = offset + k;
+            let in_link = links.iter().any(|l| l.start <= x && x < l.end);
+            if in_link != styled {
+                if in_link {
+                    result.push_str(&style);
+                } else {
+                    result.push_str(termion::style::Reset.as_ref());
+                }
+                styled = in_link;
+            }
+            if marks.contains(&x) {
+                result.push_str(&format!(
+                    "{}{}{}",
+                    termion::style::Invert,
+                    c,
+                    termion::style::NoInvert
+                ));
+            } else {
+                result.push(*c);
+            }
+        }
+        if styled {
+            result.push_str(termion::style::Reset.as_ref());
+        }
+        let end = offset + row.len();
+        if end == self.text.text[index].len() && marks.contains(&end) {
+            result.push_str(&format!(
+                "{} {}",
+                termion::style::Invert,
+                termion::style::NoInvert
+            ));
+        }
+        result
+    }
+
+    // rows of the line on the screen. the folded front matter is one row, and
+    // other folds are their first line with the number of lines they hide.
+    fn line_rows(&self, index: usize) -> Vec<Vec<char>> {
+        if self.hidden_in(index).is_some() {
+            return vec![];
+        }
+        let Some(last) = self
+            .folded
+            .iter()
+            .filter(|(first, _)| *first == index)
+            .map(|(_, last)| *last)
+            .max()
+        else {
+            return self.split_line_by_width(index);
+        };
+        if index == 0 && self.text.front_matter().is_some_and(|f| f.end == last) {
+            let summary = format!("--- front matter ({} lines) ---", last + 1);
+            return vec![summary.chars().take(self.info.width).collect()];
+        }
+        let mut rows = self.split_line_by_width(index);
+        let summary = format!(" ... ({} lines)", last - index)
+            .chars()
+            .collect::<Vec<char>>();
+        let row = rows.last_mut().unwrap();
+        let width = row.iter().map(|c| c.width().unwrap_or(2)).sum::<usize>();
+        if width + summary.len() <= self.info.width {
+            row.extend(summary);
+        } else {
+            rows.push(summary.into_iter().take(self.info.width).collect());
+        }
+        rows
+    }
+
     // please set width and height before this function done.
     fn split_line_by_width(&self, index: usize) -> Vec<Vec<char>> {
         let target = self.text.text[index].clone();
@@ -222,11 +1234,25 @@ impl EditorBuffer {
         return wrap_count;
     }
 
+    // display x of the cursor inside its wrapped row
+    fn get_wrap_x(&self, cursor: &Cursor, wrap_count: usize) -> usize {
+        let splited = self.split_line_by_width(cursor.y);
+        let start = splited[..wrap_count.min(splited.len())]
+            .iter()
+            .map(|l| l.len())
+            .sum::<usize>()
+            .min(cursor.x);
+        self.text.text[cursor.y][start..cursor.x]
+            .iter()
+            .map(|c| c.width().unwrap_or(2))
+            .sum()
+    }
+
     // return (top, wrap)
     fn calc_top_from_bottom(&self, bottom: usize, wrap: usize) -> (usize, usize) {
         let mut line_count = wrap + 1;
         for i in (0..bottom).rev() {
-            let crnt_line = self.split_line_by_width(i);
+            let crnt_line = self.line_rows(i);
             line_count += crnt_line.len();
             if line_count >= self.info.height {
                 return (i, line_count - self.info.height);
@@ -235,3 +1261,78 @@ impl EditorBuffer {
         return (0, 0);
     }
 }
+
+// Shift and an arrow key, which termion doesn't parse
+fn shift_arrow(event: &Event) -> Option<Key> {
+    let Event::Unsupported(c) = event else {
+        return None;
+    };
+    match c.as_slice() {
+        [27, 91, 49, 59, 50, 65] => Some(Key::Up),
+        [27, 91, 49, 59, 50, 66] => Some(Key::Down),
+        [27, 91, 49, 59, 50, 67] => Some(Key::Right),
+        [27, 91, 49, 59, 50, 68] => Some(Key::Left),
+        _ => None,
+    }
+}
+
+// move the stops after the i-th one in its line by delta chars
+fn shift_stops(stops: &mut [TabStop], i: usize, delta: isize) {
+    let y = stops[i].y;
+    for stop in stops.iter_mut().skip(i + 1).filter(|s| s.y == y) {
+        stop.start = (stop.start as isize + delta) as usize;
+        stop.end = (stop.end as isize + delta) as usize;
+    }
+}
+
+// items starting with the word, then items containing it
+fn rank(items: &[String], word: &str, key: impl Fn(&String) -> String) -> Vec<String> {
+    let (mut found, rest): (Vec<&String>, Vec<&String>) =
+        items.iter().partition(|i| key(i).starts_with(word));
+    found.extend(rest.into_iter().filter(|i| key(i).contains(word)));
+    found.into_iter().take(COMPLETION_ROWS).cloned().collect()
+}
+
+// put the text over the line at the display column x. width is of the text.
+// escape sequences in the line are kept, and take no columns.
+fn overlay(line: &str, x: usize, text: &str, width: usize) -> String {
+    let mut result = String::new();
+    if x == 0 {
+        result.push_str(text);
+    }
+    let mut column = 0; // columns of the line passed
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            result.push(c);
+            for e in chars.by_ref() {
+                result.push(e);
+                if e.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        let w = c.width().unwrap_or(0);
+        let end = column + w;
+        if end <= x || column >= x + width {
+            result.push(c);
+        } else if column < x {
+            // a wide char cut at the start of the text
+            result.push(' ');
+            result.push_str(text);
+        } else if end > x + width {
+            // a wide char cut at the end of the text
+            result.push(' ');
+        }
+        if end == x && w > 0 {
+            result.push_str(text);
+        }
+        column = end;
+    }
+    if column < x {
+        result.push_str(&" ".repeat(x - column));
+        result.push_str(text);
+    }
+    result
+}
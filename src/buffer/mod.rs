@@ -1,3 +1,14 @@
+pub mod agenda;
+pub mod backlinks;
 pub mod buffer;
+pub mod drawer;
 pub mod editor;
+pub mod outline;
+pub mod picker;
+pub mod preview;
+pub mod prompt;
+pub mod snippet;
+pub mod status;
+pub mod tab;
+pub mod tasks;
 pub mod text;
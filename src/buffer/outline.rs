@@ -0,0 +1,158 @@
+use termion::event::{Event, Key};
+use unicode_width::UnicodeWidthChar;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+use crate::note::outline::{current_heading, Heading};
+
+// OutlineBufferは開いているノートの見出しを階層ごとに字下げして表示するペイン。
+// カーソルのあるセクションの見出しを太字にし、フォーカスがないときは選択もそれに合わせる。
+// Enterで見出しに移動し、K/Jでセクションを前後と入れ替え、</>で見出しのレベルを上げ下げする。
+// ノートの書き換えはtake_actionで受け取ったAppがエディタに頼む。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutlineAction {
+    Jump(usize),         // line of the heading
+    Move(usize, bool),   // line and true for up
+    Shift(usize, isize), // line and the change of the level
+}
+
+#[derive(Debug, Clone)]
+pub struct OutlineBuffer {
+    pub headings: Vec<Heading>,
+    pub current: Option<usize>, // heading of the section with the cursor
+    pub selected: usize,
+    top: usize, // first row
+    pub info: ViewBufferInfo,
+    action: Option<OutlineAction>,
+}
+
+impl ViewBuffer for OutlineBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, event: Event) {
+        if self.info.focus {
+            let line = self.headings.get(self.selected).map(|h| h.line);
+            match event {
+                Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                Event::Key(Key::Down) | Event::Key(Key::Char('j'))
+                    if self.selected + 1 < self.headings.len() =>
+                {
+                    self.selected += 1;
+                }
+                Event::Key(Key::Char('\n')) => self.action = line.map(OutlineAction::Jump),
+                Event::Key(Key::Char('K')) => {
+                    self.action = line.map(|l| OutlineAction::Move(l, true));
+                }
+                Event::Key(Key::Char('J')) => {
+                    self.action = line.map(|l| OutlineAction::Move(l, false));
+                }
+                Event::Key(Key::Char('<')) => {
+                    self.action = line.map(|l| OutlineAction::Shift(l, -1));
+                }
+                Event::Key(Key::Char('>')) => {
+                    self.action = line.map(|l| OutlineAction::Shift(l, 1));
+                }
+                _ => {}
+            }
+        }
+
+        let min_level = self.headings.iter().map(|h| h.level).min().unwrap_or(1);
+        let mut rows = vec![format!("Outline ({})", self.headings.len())];
+        for (i, heading) in self.headings.iter().enumerate() {
+            let indent = "  ".repeat(heading.level - min_level);
+            rows.push(self.entry_row(i, &format!(" {}{}", indent, heading.title)));
+        }
+
+        let height = self.info.height.max(1);
+        let selected_row = self.selected + 1;
+        if self.selected == 0 {
+            self.top = 0;
+        } else if selected_row < self.top {
+            self.top = selected_row;
+        } else if selected_row + 1 > self.top + height {
+            self.top = selected_row + 1 - height;
+        }
+        self.info.buffer = rows.into_iter().skip(self.top).take(height).collect();
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+
+    fn get_commands(&self) -> Vec<(String, Event)> {
+        [
+            ("Go to Heading", Key::Char('\n')),
+            ("Move Section Up", Key::Char('K')),
+            ("Move Section Down", Key::Char('J')),
+            ("Promote Heading", Key::Char('<')),
+            ("Demote Heading", Key::Char('>')),
+        ]
+        .into_iter()
+        .map(|(name, key)| (name.to_string(), Event::Key(key)))
+        .collect()
+    }
+}
+
+impl OutlineBuffer {
+    pub fn new() -> Self {
+        OutlineBuffer {
+            headings: vec![],
+            current: None,
+            selected: 0,
+            top: 0,
+            info: ViewBufferInfo {
+                width: 30,
+                height: 10,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            action: None,
+        }
+    }
+
+    pub fn take_action(&mut self) -> Option<OutlineAction> {
+        self.action.take()
+    }
+
+    // the headings of the note and the line of the cursor. the selection
+    // follows the cursor unless the pane is focused, or when `follow` is set.
+    pub fn set_outline(&mut self, headings: Vec<Heading>, line: usize, follow: bool) {
+        self.current = current_heading(&headings, line);
+        self.headings = headings;
+        if follow || !self.info.focus {
+            self.selected = self.current.unwrap_or(0);
+        }
+        self.selected = self.selected.min(self.headings.len().saturating_sub(1));
+    }
+
+    // cut to the width. the selected row is inverted and the current one is bold.
+    fn entry_row(&self, i: usize, text: &str) -> String {
+        let mut width = 0;
+        let cut = text
+            .chars()
+            .take_while(|c| {
+                width += c.width().unwrap_or(0);
+                width <= self.info.width
+            })
+            .collect::<String>();
+        if i == self.selected && self.info.focus {
+            format!("{}{}{}", termion::style::Invert, cut, termion::style::Reset)
+        } else if Some(i) == self.current {
+            format!("{}{}{}", termion::style::Bold, cut, termion::style::Reset)
+        } else {
+            cut
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use termion::event::{Event, Key};
+use unicode_width::UnicodeWidthStr;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+use crate::search::fuzzy::{fuzzy_match, FuzzyMatch};
+
+// PickerBufferは候補の一覧から一つを選ぶためのペイン。
+// 一行目が入力欄で、入力した文字列で候補をあいまい検索して、良く一致する順に並べる。
+// 選択結果はtake_actionでAppが受け取る。
+// 候補はadd_itemsで後から追加できる。入力が前の入力に文字を足しただけなら、前の結果の中だけを探す。
+
+#[derive(Debug, Clone)]
+pub struct PickerItem {
+    pub label: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickerAction {
+    Select(usize), // index of items
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub struct PickerBuffer {
+    pub title: String,
+    pub items: Vec<PickerItem>,
+    pub query: String,
+    pub filtered: Vec<usize>,
+    pub matches: Vec<FuzzyMatch>, // of each filtered item
+    pub match_detail: bool,       // search the detail as well as the label
+    filtered_query: String,
+    pub selected: usize,
+    pub top: usize,
+    pub info: ViewBufferInfo,
+    action: Option<PickerAction>,
+}
+
+impl ViewBuffer for PickerBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, event: Event) {
+        if self.info.focus {
+            match event {
+                Event::Key(Key::Esc) | Event::Key(Key::Ctrl('c')) => {
+                    self.action = Some(PickerAction::Cancel);
+                }
+                Event::Key(Key::Char('\n')) => {
+                    if let Some(i) = self.filtered.get(self.selected) {
+                        self.action = Some(PickerAction::Select(*i));
+                    }
+                }
+                Event::Key(Key::Up) | Event::Key(Key::Ctrl('p')) => {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                Event::Key(Key::Down) | Event::Key(Key::Ctrl('n'))
+                    if self.selected + 1 < self.filtered.len() =>
+                {
+                    self.selected += 1;
+                }
+                Event::Key(Key::Backspace) => {
+                    self.query.pop();
+                    self.filter();
+                }
+                Event::Key(Key::Char(c)) if c != '\t' => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                _ => {}
+            }
+        }
+
+        let rows = self.info.height.saturating_sub(1);
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if rows > 0 && self.selected >= self.top + rows {
+            self.top = self.selected + 1 - rows;
+        }
+
+        let mut view = vec![format!(
+            "{} ({}/{}) > {}",
+            self.title,
+            self.filtered.len(),
+            self.items.len(),
+            self.query
+        )];
+        for (row, i) in self.filtered.iter().enumerate().skip(self.top).take(rows) {
+            let item = &self.items[*i];
+            let space = self
+                .info
+                .width
+                .saturating_sub(item.label.width() + item.detail.width() + 2)
+                .max(1);
+            let label_len = item.label.chars().count() + 1;
+            let positions = &self.matches[row].positions;
+            let line = format!(
+                " {}{}{}",
+                highlight(&item.label, positions, 0),
+                " ".repeat(space),
+                highlight(&item.detail, positions, label_len)
+            );
+            if row == self.selected {
+                view.push(format!(
+                    "{}{}{}",
+                    termion::style::Invert,
+                    line,
+                    termion::style::Reset
+                ));
+            } else {
+                view.push(line);
+            }
+        }
+        self.info.cursor = Cursor {
+            x: view[0].width(),
+            y: 0,
+        };
+        self.info.buffer = view;
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+}
+
+impl PickerBuffer {
+    pub fn new(title: &str, items: Vec<PickerItem>) -> Self {
+        let mut picker = PickerBuffer {
+            title: title.to_string(),
+            items,
+            query: String::new(),
+            filtered: vec![],
+            matches: vec![],
+            match_detail: false,
+            filtered_query: String::new(),
+            selected: 0,
+            top: 0,
+            info: ViewBufferInfo {
+                width: 100,
+                height: 10,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            action: None,
+        };
+        picker.filter();
+        picker
+    }
+
+    pub fn take_action(&mut self) -> Option<PickerAction> {
+        self.action.take()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if let Some(i) = self.filtered.iter().position(|i| *i == index) {
+            self.selected = i;
+        }
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        self.filtered.get(self.selected).copied()
+    }
+
+    // append items, keeping the query and the selection
+    pub fn add_items(&mut self, items: Vec<PickerItem>) {
+        let start = self.items.len();
+        self.items.extend(items);
+        let found = (start..self.items.len())
+            .filter_map(|i| self.match_item(i).map(|m| (i, m)))
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            return;
+        }
+        let selected = self.selected_index();
+        let mut all = self
+            .filtered
+            .drain(..)
+            .zip(self.matches.drain(..))
+            .chain(found)
+            .collect::<Vec<_>>();
+        self.sort(&mut all);
+        (self.filtered, self.matches) = all.into_iter().unzip();
+        if let Some(i) = selected {
+            self.select(i);
+        }
+    }
+
+    fn filter(&mut self) {
+        // a longer query only matches items which matched the shorter one
+        let narrowing =
+            !self.filtered_query.is_empty() && self.query.starts_with(&self.filtered_query);
+        let candidates = if narrowing {
+            std::mem::take(&mut self.filtered)
+        } else {
+            (0..self.items.len()).collect()
+        };
+        let mut found = candidates
+            .into_iter()
+            .filter_map(|i| self.match_item(i).map(|m| (i, m)))
+            .collect::<Vec<_>>();
+        self.sort(&mut found);
+        (self.filtered, self.matches) = found.into_iter().unzip();
+        self.filtered_query = self.query.clone();
+        self.selected = 0;
+        self.top = 0;
+    }
+
+    fn match_item(&self, index: usize) -> Option<FuzzyMatch> {
+        let item = &self.items[index];
+        if self.match_detail {
+            fuzzy_match(&self.query, &format!("{} {}", item.label, item.detail))
+        } else {
+            fuzzy_match(&self.query, &item.label)
+        }
+    }
+
+    // better score first, then shorter label, then the original order.
+    // without a query the items keep their order.
+    fn sort(&self, list: &mut [(usize, FuzzyMatch)]) {
+        if self.query.is_empty() {
+            list.sort_by_key(|(i, _)| *i);
+            return;
+        }
+        list.sort_by(|a, b| {
+            b.1.score
+                .cmp(&a.1.score)
+                .then(self.label_len(a.0).cmp(&self.label_len(b.0)))
+                .then(a.0.cmp(&b.0))
+        });
+    }
+
+    fn label_len(&self, index: usize) -> usize {
+        self.items[index].label.chars().count()
+    }
+}
+
+// positions are counted from offset
+fn highlight(label: &str, positions: &[usize], offset: usize) -> String {
+    let mut line = String::new();
+    for (i, c) in label.chars().enumerate() {
+        if positions.contains(&(i + offset)) {
+            line.push_str(&format!(
+                "{}{}{}",
+                termion::color::Fg(termion::color::Yellow),
+                c,
+                termion::color::Fg(termion::color::Reset)
+            ));
+        } else {
+            line.push(c);
+        }
+    }
+    line
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use termion::event::Event;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+
+// PreviewBufferはファイルの先頭を読み取り専用で表示する。
+// ファイル検索や全文検索で選んでいる候補の中身を見せるのに使う。
+// 行を指定すると、その行が見えるようにスクロールして反転表示する。
+// ファイルではない行(名前の変更で書き換えるリンクの一覧など)も表示できる。
+
+#[derive(Debug, Clone)]
+pub struct PreviewBuffer {
+    pub path: Option<PathBuf>,
+    pub lines: Vec<String>,
+    pub mark: Option<usize>, // line to show
+    pub info: ViewBufferInfo,
+}
+
+impl ViewBuffer for PreviewBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, _event: Event) {
+        let mut view = vec![];
+        if let Some(path) = &self.path {
+            view.push(format!(
+                "{}{}{}",
+                termion::style::Invert,
+                path.to_string_lossy(),
+                termion::style::Reset
+            ));
+        }
+        let top = self.mark.map(|m| m.saturating_sub(3)).unwrap_or(0);
+        for (i, line) in self
+            .lines
+            .iter()
+            .enumerate()
+            .skip(top)
+            .take(self.info.height.saturating_sub(1))
+        {
+            // tabs would break the cell layout
+            let line = line.replace('\t', "    ");
+            if Some(i) == self.mark {
+                view.push(format!(
+                    "{}{}{}",
+                    termion::style::Invert,
+                    line,
+                    termion::style::Reset
+                ));
+            } else {
+                view.push(line);
+            }
+        }
+        self.info.buffer = view;
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+}
+
+impl PreviewBuffer {
+    pub fn new() -> Self {
+        PreviewBuffer {
+            path: None,
+            lines: vec![],
+            mark: None,
+            info: ViewBufferInfo {
+                width: 100,
+                height: 10,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+        }
+    }
+
+    // read the head of the file, until some lines after the mark.
+    // does nothing if the file is already shown.
+    pub fn show(&mut self, path: &Path, mark: Option<usize>) {
+        if self.path.as_deref() == Some(path) && self.mark == mark {
+            return;
+        }
+        let max_lines = mark.unwrap_or(0) + 200;
+        self.lines = match fs::File::open(path) {
+            Ok(file) => BufReader::new(file)
+                .lines()
+                .take(max_lines)
+                .map_while(|l| l.ok())
+                .map(|l| l.trim_end_matches('\r').to_string())
+                .collect(),
+            Err(e) => vec![e.to_string()],
+        };
+        self.path = Some(path.to_path_buf());
+        self.mark = mark;
+    }
+
+    // show lines which are not in a file, with the title as the header
+    pub fn show_lines(&mut self, title: &str, lines: Vec<String>) {
+        self.path = Some(PathBuf::from(title));
+        self.lines = lines;
+        self.mark = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.path = None;
+        self.lines.clear();
+        self.mark = None;
+    }
+}
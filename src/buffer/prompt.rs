@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use termion::event::{Event, Key};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+use super::text::TextBuffer;
+
+// PromptBufferは一行だけの入力欄。コマンドや検索語、確認の入力に使う。
+// 入力はTextBufferに任せて、履歴と補完だけをここで扱う。
+// 補完候補はAppが作るので、Tabが押されたらCompleteを返してset_completionsを待つ。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptAction {
+    Submit(String),
+    Complete(String),
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptBuffer {
+    pub label: String,
+    pub text: TextBuffer,
+    pub info: ViewBufferInfo,
+    single_key: bool, // submit with one key (y/n)
+    history_key: String,
+    histories: HashMap<String, Vec<String>>,
+    history_index: Option<usize>,
+    completions: Vec<String>,
+    completion_index: Option<usize>,
+    scroll: usize,
+    action: Option<PromptAction>,
+}
+
+impl ViewBuffer for PromptBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, event: Event) {
+        if self.info.focus {
+            if event != Event::Key(Key::Char('\t')) {
+                self.completions.clear();
+                self.completion_index = None;
+            }
+            match event {
+                Event::Key(Key::Esc) | Event::Key(Key::Ctrl('c')) => {
+                    self.action = Some(PromptAction::Cancel);
+                }
+                Event::Key(Key::Char(c)) if self.single_key => {
+                    self.action = Some(PromptAction::Submit(c.to_string()));
+                }
+                Event::Key(Key::Char('\n')) => {
+                    let text = self.get_text();
+                    let history = self.histories.entry(self.history_key.clone()).or_default();
+                    if !text.is_empty() && history.last() != Some(&text) {
+                        history.push(text.clone());
+                    }
+                    self.action = Some(PromptAction::Submit(text));
+                }
+                Event::Key(Key::Char('\t')) => self.complete(),
+                Event::Key(Key::Char(c)) => self.text.input(c),
+                Event::Key(Key::Backspace) => self.text.back(),
+                Event::Key(Key::Delete) => self.text.delete(),
+                Event::Key(Key::Left) => self.text.left(false),
+                Event::Key(Key::Right) => self.text.right(false),
+                Event::Key(Key::Home) | Event::Key(Key::Ctrl('a')) => self.text.set_cursor(0, 0),
+                Event::Key(Key::End) | Event::Key(Key::Ctrl('e')) => {
+                    self.text.set_cursor(usize::MAX, 0)
+                }
+                Event::Key(Key::Up) => self.history(true),
+                Event::Key(Key::Down) => self.history(false),
+                _ => {}
+            }
+        }
+
+        // scroll the input horizontally to keep the cursor visible
+        let label_width = self.label.width();
+        let space = self.info.width.saturating_sub(label_width + 1).max(1);
+        let line = &self.text.text[0];
+        let cursor = self.text.get_cursor_pos().x;
+        if cursor < self.scroll {
+            self.scroll = cursor;
+        }
+        while line[self.scroll..cursor]
+            .iter()
+            .map(|c| c.width().unwrap_or(0))
+            .sum::<usize>()
+            > space
+        {
+            self.scroll += 1;
+        }
+        let visible = line[self.scroll..].iter().collect::<String>();
+        let mut view = format!("{}{}", self.label, visible);
+        if self.completions.len() > 1 {
+            view.push_str(&format!(
+                "  {}[{}]{}",
+                termion::style::Faint,
+                self.completions.join(" "),
+                termion::style::Reset
+            ));
+        }
+        self.info.cursor = Cursor {
+            x: label_width
+                + line[self.scroll..cursor]
+                    .iter()
+                    .map(|c| c.width().unwrap_or(0))
+                    .sum::<usize>(),
+            y: 0,
+        };
+        self.info.buffer = vec![view];
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+}
+
+impl PromptBuffer {
+    pub fn new() -> Self {
+        PromptBuffer {
+            label: String::new(),
+            text: TextBuffer::default(),
+            info: ViewBufferInfo {
+                width: 100,
+                height: 1,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            single_key: false,
+            history_key: String::new(),
+            histories: HashMap::new(),
+            history_index: None,
+            completions: vec![],
+            completion_index: None,
+            scroll: 0,
+            action: None,
+        }
+    }
+
+    // start a new input. inputs with the same history_key share the history.
+    pub fn open(&mut self, label: &str, text: &str, history_key: &str) {
+        self.label = label.to_string();
+        self.set_text(text);
+        self.single_key = false;
+        self.history_key = history_key.to_string();
+        self.history_index = None;
+        self.completions.clear();
+        self.completion_index = None;
+        self.scroll = 0;
+        self.action = None;
+    }
+
+    // ask a question answered with one key
+    pub fn confirm(&mut self, label: &str) {
+        self.open(label, "", "");
+        self.single_key = true;
+    }
+
+    pub fn take_action(&mut self) -> Option<PromptAction> {
+        self.action.take()
+    }
+
+    pub fn get_text(&self) -> String {
+        self.text.text[0].iter().collect()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = TextBuffer::new(text);
+        self.text.set_cursor(usize::MAX, 0);
+    }
+
+    // candidates are whole input strings
+    pub fn set_completions(&mut self, completions: Vec<String>) {
+        match completions.len() {
+            0 => {}
+            1 => self.set_text(&completions[0]),
+            _ => {
+                let prefix = common_prefix(&completions);
+                if prefix.chars().count() > self.get_text().chars().count() {
+                    self.set_text(&prefix);
+                }
+                self.completions = completions;
+            }
+        }
+    }
+
+    fn complete(&mut self) {
+        if self.completions.is_empty() {
+            self.action = Some(PromptAction::Complete(self.get_text()));
+            return;
+        }
+        // cycle candidates
+        let i = match self.completion_index {
+            Some(i) => (i + 1) % self.completions.len(),
+            None => 0,
+        };
+        self.completion_index = Some(i);
+        let text = self.completions[i].clone();
+        self.set_text(&text);
+    }
+
+    fn history(&mut self, older: bool) {
+        let Some(history) = self.histories.get(&self.history_key) else {
+            return;
+        };
+        if history.is_empty() {
+            return;
+        }
+        let index = match (self.history_index, older) {
+            (None, true) => Some(history.len() - 1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < history.len() => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        let text = match index {
+            Some(i) => history[i].clone(),
+            None => String::new(),
+        };
+        self.history_index = index;
+        self.set_text(&text);
+    }
+}
+
+fn common_prefix(list: &[String]) -> String {
+    let mut prefix = list[0].chars().collect::<Vec<char>>();
+    for s in list.iter().skip(1) {
+        let len = prefix
+            .iter()
+            .zip(s.chars())
+            .take_while(|(a, b)| *a == b)
+            .count();
+        prefix.truncate(len);
+    }
+    prefix.into_iter().collect()
+}
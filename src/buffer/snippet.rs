@@ -0,0 +1,160 @@
+// スニペット。トリガーの単語を入力してTabを押すと本文に展開する。
+// 本文の $1 や ${1:初期値} はタブストップで、Tab/Shift-Tabで順に移動する。$0 は最後の位置。
+// 同じ番号のタブストップはミラーで、最初の一つに入力した内容が他にも反映される。
+// \$ は $ そのもの。設定では \n で改行、\t でタブを書く。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub trigger: String,
+    pub body: String,
+}
+
+// a tab stop in the expanded text. y is the line from the first line of the
+// snippet, start and end are char indexes in the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabStop {
+    pub number: usize,
+    pub y: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// the text of the snippet and its tab stops, in order of appearance.
+// a mirror without a default takes the default of another stop with the number.
+pub fn parse_snippet(body: &str) -> (Vec<Vec<char>>, Vec<TabStop>) {
+    let fields = parse_fields(body);
+    let default_of = |number: usize| {
+        fields
+            .iter()
+            .find_map(|f| match f {
+                Field::Stop(n, text) if *n == number && !text.is_empty() => Some(text.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    };
+    let mut lines = vec![vec![]];
+    let mut stops = vec![];
+    for field in fields.iter() {
+        match field {
+            Field::Text(text) => {
+                for c in text.chars() {
+                    if c == '\n' {
+                        lines.push(vec![]);
+                    } else {
+                        lines.last_mut().unwrap().push(c);
+                    }
+                }
+            }
+            Field::Stop(number, _) => {
+                let y = lines.len() - 1;
+                let start = lines[y].len();
+                lines[y].extend(default_of(*number).chars().filter(|c| *c != '\n'));
+                stops.push(TabStop {
+                    number: *number,
+                    y,
+                    start,
+                    end: lines[y].len(),
+                });
+            }
+        }
+    }
+    (lines, stops)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Text(String),
+    Stop(usize, String), // number and default
+}
+
+fn parse_fields(body: &str) -> Vec<Field> {
+    let chars = body.chars().collect::<Vec<char>>();
+    let mut fields = vec![];
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\'
+            && chars
+                .get(i + 1)
+                .is_some_and(|c| matches!(c, '$' | '}' | '\\'))
+        {
+            text.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if chars[i] != '$' {
+            text.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let braced = chars.get(i + 1) == Some(&'{');
+        let digits_start = i + 1 + braced as usize;
+        let digits = chars[digits_start..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>();
+        let Ok(number) = digits.parse::<usize>() else {
+            text.push('$');
+            i += 1;
+            continue;
+        };
+        let mut j = digits_start + digits.len();
+        let mut default = String::new();
+        if braced {
+            if chars.get(j) == Some(&':') {
+                j += 1;
+                while j < chars.len() && chars[j] != '}' {
+                    if chars[j] == '\\'
+                        && chars
+                            .get(j + 1)
+                            .is_some_and(|c| matches!(c, '$' | '}' | '\\'))
+                    {
+                        j += 1;
+                    }
+                    default.push(chars[j]);
+                    j += 1;
+                }
+            }
+            if chars.get(j) != Some(&'}') {
+                text.push('$');
+                i += 1;
+                continue;
+            }
+            j += 1;
+        }
+        if !text.is_empty() {
+            fields.push(Field::Text(std::mem::take(&mut text)));
+        }
+        fields.push(Field::Stop(number, default));
+        i = j;
+    }
+    if !text.is_empty() {
+        fields.push(Field::Text(text));
+    }
+    fields
+}
+
+// snippets for every note, which the config can replace
+pub fn default_snippets() -> Vec<(String, Snippet)> {
+    [
+        ("md", "todo", "- [ ] $0"),
+        ("md", "link", "[${1:text}](${2:url})$0"),
+        ("md", "code", "```${1:lang}\n$0\n```"),
+        (
+            "md",
+            "table",
+            "| ${1:a} | ${2:b} |\n| --- | --- |\n| $0 |  |",
+        ),
+    ]
+    .into_iter()
+    .map(|(ext, trigger, body)| {
+        (
+            ext.to_string(),
+            Snippet {
+                trigger: trigger.to_string(),
+                body: body.to_string(),
+            },
+        )
+    })
+    .collect()
+}
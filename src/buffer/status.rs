@@ -0,0 +1,123 @@
+use termion::event::Event;
+use unicode_width::UnicodeWidthStr;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+
+// StatusBufferは画面下の一行に現在のノートの情報を表示する。
+// 内容はAppがset_statusで毎回渡す。メッセージは次のキー入力まで表示する。
+
+#[derive(Debug, Clone, Default)]
+pub struct StatusInfo {
+    pub mode: String,
+    pub name: String,
+    pub modified: bool,
+    pub line: usize,       // 1 origin
+    pub column: usize,     // display column, 1 origin
+    pub char_index: usize, // 1 origin
+    pub selection: usize,  // selected chars
+    pub words: usize,
+    pub encoding: String,
+    pub line_ending: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusBuffer {
+    pub status: StatusInfo,
+    pub info: ViewBufferInfo,
+    message: Option<(String, bool)>, // (text, is error)
+}
+
+impl ViewBuffer for StatusBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, _event: Event) {
+        let status = &self.status;
+        let left = match &self.message {
+            Some((message, _)) => format!(" {}", message),
+            None => format!(
+                " {} {}{}",
+                status.mode,
+                status.name,
+                if status.modified { " [+]" } else { "" }
+            ),
+        };
+        let mut right = format!("Ln {}, Col {}", status.line, status.column);
+        if status.column != status.char_index {
+            right.push_str(&format!(" ({})", status.char_index));
+        }
+        if status.selection > 0 {
+            right.push_str(&format!(" | {} selected", status.selection));
+        }
+        right.push_str(&format!(
+            " | {} words | {} | {} ",
+            status.words, status.encoding, status.line_ending
+        ));
+
+        let space = self
+            .info
+            .width
+            .saturating_sub(left.width() + right.width())
+            .max(1);
+        let line = match &self.message {
+            Some((_, true)) => format!(
+                "{}{}{}{}{}",
+                termion::color::Fg(termion::color::Red),
+                left,
+                termion::color::Fg(termion::color::Reset),
+                " ".repeat(space),
+                right
+            ),
+            _ => format!("{}{}{}", left, " ".repeat(space), right),
+        };
+        self.info.buffer = vec![format!(
+            "{}{}{}",
+            termion::style::Invert,
+            line,
+            termion::style::Reset
+        )];
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        (0, 0)
+    }
+}
+
+impl StatusBuffer {
+    pub fn new() -> Self {
+        StatusBuffer {
+            status: StatusInfo::default(),
+            info: ViewBufferInfo {
+                width: 100,
+                height: 1,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            message: None,
+        }
+    }
+
+    pub fn set_status(&mut self, status: StatusInfo) {
+        self.status = status;
+    }
+
+    pub fn set_message(&mut self, message: &str) {
+        self.message = Some((message.to_string(), false));
+    }
+
+    pub fn set_error(&mut self, message: &str) {
+        self.message = Some((message.to_string(), true));
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+}
@@ -0,0 +1,93 @@
+use termion::event::Event;
+use unicode_width::UnicodeWidthStr;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+
+// TabBufferは開いているノートの一覧を一行で表示する。
+// 内容はAppがset_tabsで毎回渡す。
+
+#[derive(Debug, Clone)]
+pub struct Tab {
+    pub name: String,
+    pub modified: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TabBuffer {
+    pub tabs: Vec<Tab>,
+    pub current: usize,
+    pub info: ViewBufferInfo,
+}
+
+impl ViewBuffer for TabBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, _event: Event) {
+        let labels = self
+            .tabs
+            .iter()
+            .map(|t| format!(" {}{} ", t.name, if t.modified { "*" } else { "" }))
+            .collect::<Vec<String>>();
+
+        // scroll so that the current tab is visible
+        let mut start = 0;
+        while start < self.current
+            && labels[start..=self.current]
+                .iter()
+                .map(|l| l.width() + 1)
+                .sum::<usize>()
+                > self.info.width
+        {
+            start += 1;
+        }
+
+        let mut line = String::new();
+        for (i, label) in labels.iter().enumerate().skip(start) {
+            if i == self.current {
+                line.push_str(&format!(
+                    "{}{}{}",
+                    termion::style::Invert,
+                    label,
+                    termion::style::Reset
+                ));
+            } else {
+                line.push_str(label);
+            }
+            line.push('|');
+        }
+        self.info.buffer = vec![line];
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        (0, 0)
+    }
+}
+
+impl TabBuffer {
+    pub fn new() -> Self {
+        TabBuffer {
+            tabs: vec![],
+            current: 0,
+            info: ViewBufferInfo {
+                width: 100,
+                height: 1,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+        }
+    }
+
+    pub fn set_tabs(&mut self, tabs: Vec<Tab>, current: usize) {
+        self.tabs = tabs;
+        self.current = current;
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+
+use termion::event::{Event, Key};
+use unicode_width::UnicodeWidthChar;
+
+use super::buffer::{Cursor, ViewBuffer, ViewBufferInfo};
+use crate::note::task::Task;
+
+// TasksBufferは保管庫の未完了のタスクを表示するペイン。
+// タスクはノートごとにまとめ、ノートのタイトルの下に一行ずつ並べる。
+// タグで絞り込んでいるときは見出しにタグを出す。
+// Enterで選んだタスクの行を開く。開く操作はtake_actionでAppが受け取る。
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TasksAction {
+    Open(PathBuf, usize), // relative path and line
+}
+
+#[derive(Debug, Clone)]
+pub struct TasksBuffer {
+    pub tag: Option<String>, // filter
+    pub tasks: Vec<Task>,
+    pub selected: usize,
+    top: usize, // first row
+    pub info: ViewBufferInfo,
+    action: Option<TasksAction>,
+}
+
+impl ViewBuffer for TasksBuffer {
+    fn set_view_info(&mut self, width: usize, height: usize, focus: bool) {
+        self.info.width = width;
+        self.info.height = height;
+        self.info.focus = focus;
+    }
+
+    fn update_view(&mut self, event: Event) {
+        if self.info.focus {
+            match event {
+                Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                Event::Key(Key::Down) | Event::Key(Key::Char('j'))
+                    if self.selected + 1 < self.tasks.len() =>
+                {
+                    self.selected += 1;
+                }
+                Event::Key(Key::Char('\n')) => {
+                    if let Some(task) = self.selected_task() {
+                        self.action = Some(TasksAction::Open(task.path.clone(), task.line));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut rows = vec![match &self.tag {
+            Some(tag) => format!("Tasks #{} ({})", tag, self.tasks.len()),
+            None => format!("Tasks ({})", self.tasks.len()),
+        }];
+        let mut selected_row = 0;
+        for (i, task) in self.tasks.iter().enumerate() {
+            if i == 0 || self.tasks[i - 1].path != task.path {
+                rows.push(self.entry_row(None, &format!(" {}", task.title)));
+            }
+            if i == self.selected {
+                selected_row = rows.len();
+            }
+            rows.push(self.entry_row(Some(i), &format!("   [ ] {}", task.text)));
+        }
+
+        let height = self.info.height.max(1);
+        if selected_row < self.top {
+            // show the title of the note with its first task
+            self.top = selected_row.saturating_sub(1);
+        } else if selected_row + 1 > self.top + height {
+            self.top = selected_row + 1 - height;
+        }
+        if self.tasks.is_empty() {
+            self.top = 0;
+        }
+        self.info.buffer = rows.into_iter().skip(self.top).take(height).collect();
+    }
+
+    fn get_view(&self) -> Vec<String> {
+        self.info.buffer.clone()
+    }
+
+    fn get_cursor_pos(&self) -> (usize, usize) {
+        let cursor = self.info.cursor.clone();
+        (cursor.x, cursor.y)
+    }
+
+    fn get_commands(&self) -> Vec<(String, Event)> {
+        vec![(String::from("Open Task"), Event::Key(Key::Char('\n')))]
+    }
+}
+
+impl TasksBuffer {
+    pub fn new() -> Self {
+        TasksBuffer {
+            tag: None,
+            tasks: vec![],
+            selected: 0,
+            top: 0,
+            info: ViewBufferInfo {
+                width: 40,
+                height: 10,
+                focus: false,
+                cursor: Cursor { x: 0, y: 0 },
+                buffer: vec![],
+            },
+            action: None,
+        }
+    }
+
+    pub fn take_action(&mut self) -> Option<TasksAction> {
+        self.action.take()
+    }
+
+    // the selection stays on the same task if it is still listed
+    pub fn set_tasks(&mut self, tag: Option<String>, tasks: Vec<Task>) {
+        let selected = self
+            .selected_task()
+            .map(|t| (t.path.clone(), t.line))
+            .filter(|_| self.tag == tag);
+        if self.tag != tag {
+            self.top = 0;
+        }
+        self.tag = tag;
+        self.tasks = tasks;
+        self.selected = selected
+            .and_then(|(path, line)| {
+                self.tasks
+                    .iter()
+                    .position(|t| t.path == path && t.line == line)
+            })
+            .unwrap_or(self.selected.min(self.tasks.len().saturating_sub(1)));
+    }
+
+    pub fn selected_task(&self) -> Option<&Task> {
+        self.tasks.get(self.selected)
+    }
+
+    // cut to the width, inverted if it is the selected task
+    fn entry_row(&self, i: Option<usize>, text: &str) -> String {
+        let text = text.replace('\t', "    ");
+        let mut width = 0;
+        let cut = text
+            .chars()
+            .take_while(|c| {
+                width += c.width().unwrap_or(0);
+                width <= self.info.width
+            })
+            .collect::<String>();
+        if i == Some(self.selected) && self.info.focus {
+            format!("{}{}{}", termion::style::Invert, cut, termion::style::Reset)
+        } else {
+            cut
+        }
+    }
+}
@@ -1,10 +1,42 @@
 use super::buffer::{Clop, Cursor};
+use crate::note::front_matter::{self, FrontMatter};
+
+const HISTORY_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditKind {
+    Input,
+    Back,
+    Delete,
+    Other,
+}
+
+// text and cursors before an edit.
+// revision is the id of the text state, used to check unsaved changes.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    text: Vec<Vec<char>>,
+    cursor: Cursor,
+    sub_cursor: Cursor,
+    revision: usize,
+}
+
+// 複数のカーソル。cursor/sub_cursor がメインのカーソルで、それ以外のカーソルと選択の始点を extra に持つ。
+// 入力や削除、移動はすべてのカーソルで行う。テキストの後ろのカーソルから順に編集し、
+// 編集の済んだカーソルはテキストの末尾からの位置で覚えておくと、その前の編集でずれない。
+// 選択が重なったカーソルは一つにまとめる。複数のカーソルでの編集も一回のアンドゥで戻る。
 
 #[derive(Debug, Clone)]
 pub struct TextBuffer {
     pub text: Vec<Vec<char>>,
     cursor: Cursor,
     sub_cursor: Cursor,
+    extra: Vec<(Cursor, Cursor)>, // other cursors and their selection starts
+    history: Vec<Snapshot>,
+    future: Vec<Snapshot>,
+    last_edit: Option<EditKind>,
+    revision: usize,
+    revision_count: usize,
 }
 
 impl Default for TextBuffer {
@@ -13,6 +45,12 @@ impl Default for TextBuffer {
             text: vec![vec![]],
             cursor: Cursor { x: 0, y: 0 },
             sub_cursor: Cursor { x: 0, y: 0 },
+            extra: vec![],
+            history: vec![],
+            future: vec![],
+            last_edit: None,
+            revision: 0,
+            revision_count: 0,
         }
     }
 }
@@ -25,12 +63,11 @@ impl TextBuffer {
             .map(|l| l.chars().map(|c| c).collect())
             .collect::<Vec<Vec<char>>>();
         if text_vec.len() == 0 {
-            text_vec = vec![];
+            text_vec = vec![vec![]];
         }
         TextBuffer {
             text: text_vec,
-            cursor: Cursor { x: 0, y: 0 },
-            sub_cursor: Cursor { x: 0, y: 0 },
+            ..Default::default()
         }
     }
 
@@ -49,6 +86,15 @@ impl TextBuffer {
     }
 
     pub fn input(&mut self, c: char) {
+        self.for_each_cursor(|t| t.input_at(c));
+    }
+
+    fn input_at(&mut self, c: char) {
+        if c.is_whitespace() {
+            self.save_history(EditKind::Other);
+        } else {
+            self.save_history(EditKind::Input);
+        }
         if self.is_selecting() {
             self.delete_range_text();
         }
@@ -59,6 +105,11 @@ impl TextBuffer {
     }
 
     pub fn left(&mut self, with_select: bool) {
+        self.for_each_cursor(|t| t.left_at(with_select));
+    }
+
+    fn left_at(&mut self, with_select: bool) {
+        self.last_edit = None;
         self.fix_cursor_pos();
         if self.cursor.x > 0 {
             self.cursor.x -= 1;
@@ -72,6 +123,11 @@ impl TextBuffer {
     }
 
     pub fn right(&mut self, with_select: bool) {
+        self.for_each_cursor(|t| t.right_at(with_select));
+    }
+
+    fn right_at(&mut self, with_select: bool) {
+        self.last_edit = None;
         self.fix_cursor_pos();
         if self.cursor.x < self.text[self.cursor.y].len() {
             self.cursor.x += 1;
@@ -85,6 +141,11 @@ impl TextBuffer {
     }
 
     pub fn up(&mut self, with_select: bool) {
+        self.for_each_cursor(|t| t.up_at(with_select));
+    }
+
+    fn up_at(&mut self, with_select: bool) {
+        self.last_edit = None;
         if self.cursor.y > 0 {
             self.cursor.y -= 1;
         } else {
@@ -96,6 +157,11 @@ impl TextBuffer {
     }
 
     pub fn down(&mut self, with_select: bool) {
+        self.for_each_cursor(|t| t.down_at(with_select));
+    }
+
+    fn down_at(&mut self, with_select: bool) {
+        self.last_edit = None;
         if self.cursor.y < self.text.len() - 1 {
             self.cursor.y += 1;
         } else {
@@ -107,6 +173,15 @@ impl TextBuffer {
     }
 
     pub fn back(&mut self) {
+        self.for_each_cursor(|t| t.back_at());
+    }
+
+    fn back_at(&mut self) {
+        let cursor = self.get_cursor_pos();
+        if self.selection_len() == 0 && cursor.x == 0 && cursor.y == 0 {
+            return;
+        }
+        self.save_history(EditKind::Back);
         self.fix_cursor_pos();
         if self.is_selecting() {
             self.delete_range_text();
@@ -127,6 +202,16 @@ impl TextBuffer {
     }
 
     pub fn delete(&mut self) {
+        self.for_each_cursor(|t| t.delete_at());
+    }
+
+    fn delete_at(&mut self) {
+        let cursor = self.get_cursor_pos();
+        let last = self.text.len() - 1;
+        if self.selection_len() == 0 && cursor.y == last && cursor.x == self.text[last].len() {
+            return;
+        }
+        self.save_history(EditKind::Delete);
         self.fix_cursor_pos();
         if self.is_selecting() {
             self.delete_range_text();
@@ -144,6 +229,11 @@ impl TextBuffer {
     }
 
     pub fn enter(&mut self) {
+        self.for_each_cursor(|t| t.enter_at());
+    }
+
+    fn enter_at(&mut self) {
+        self.save_history(EditKind::Other);
         if self.is_selecting() {
             self.delete_range_text();
         }
@@ -157,7 +247,139 @@ impl TextBuffer {
         self.close_cursor_range();
     }
 
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.history.pop() else {
+            return false;
+        };
+        let current = self.snapshot();
+        self.future.push(current);
+        self.restore(snapshot);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.future.pop() else {
+            return false;
+        };
+        let current = self.snapshot();
+        self.history.push(current);
+        self.restore(snapshot);
+        true
+    }
+
+    // id of the current text state. same id means same text.
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    // number of selected chars (line breaks are counted as one char)
+    pub fn selection_len(&self) -> usize {
+        let range = self.get_range_text();
+        if range.is_empty() {
+            return 0;
+        }
+        range.iter().map(|l| l.len()).sum::<usize>() + range.len() - 1
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.text
+            .iter()
+            .map(|l| l.iter().collect::<String>().split_whitespace().count())
+            .sum()
+    }
+
+    // the "---" block at the top of the text
+    pub fn front_matter(&self) -> Option<FrontMatter> {
+        if !self.text[0].starts_with(&['-', '-', '-']) {
+            return None;
+        }
+        let lines = self
+            .text
+            .iter()
+            .take(front_matter::MAX_LINES)
+            .map(|l| l.iter().collect::<String>())
+            .collect::<Vec<String>>();
+        front_matter::parse(&lines)
+    }
+
+    // replace the lines start..end as one undo step.
+    // the cursor stays on the same text when it is outside of the lines.
+    pub fn replace_lines(&mut self, start: usize, end: usize, lines: Vec<Vec<char>>) {
+        self.save_history(EditKind::Other);
+        self.last_edit = None;
+        self.extra.clear();
+        let inserted = lines.len();
+        self.text.splice(start..end, lines);
+        if self.text.is_empty() {
+            self.text.push(vec![]);
+        }
+        if self.cursor.y >= end {
+            self.cursor.y = self.cursor.y + inserted - (end - start);
+        } else if self.cursor.y >= start {
+            self.cursor.y = start + (self.cursor.y - start).min(inserted.saturating_sub(1));
+        }
+        self.cursor.y = self.cursor.y.min(self.text.len() - 1);
+        self.close_cursor_range();
+        self.fix_cursor_pos();
+    }
+
+    // move the cursor (clamped into the text) and close the selection
+    pub fn set_cursor(&mut self, x: usize, y: usize) {
+        self.last_edit = None;
+        self.extra.clear();
+        let y = y.min(self.text.len() - 1);
+        self.cursor = Cursor {
+            x: x.min(self.text[y].len()),
+            y,
+        };
+        self.close_cursor_range();
+    }
+
+    // select from start to end. the cursor is placed on end.
+    pub fn select_range(&mut self, start: Cursor, end: Cursor) {
+        self.set_cursor(start.x, start.y);
+        let start = self.cursor.clone();
+        self.set_cursor(end.x, end.y);
+        self.sub_cursor = start;
+    }
+
+    // find the query after the cursor (wrapping around the end).
+    // returns the start and end of the match.
+    pub fn find(&self, query: &str, from: Cursor) -> Option<(Cursor, Cursor)> {
+        let query = query.chars().collect::<Vec<char>>();
+        if query.is_empty() {
+            return None;
+        }
+        let len = self.text.len();
+        for i in 0..=len {
+            let y = (from.y + i) % len;
+            let line = &self.text[y];
+            let start = if i == 0 { from.x.min(line.len()) } else { 0 };
+            let end = if i == len {
+                from.x.min(line.len())
+            } else {
+                line.len()
+            };
+            if end < query.len() || start > end - query.len() {
+                continue;
+            }
+            for x in start..=(end - query.len()) {
+                if line[x..x + query.len()] == query[..] {
+                    return Some((
+                        Cursor { x, y },
+                        Cursor {
+                            x: x + query.len(),
+                            y,
+                        },
+                    ));
+                }
+            }
+        }
+        None
+    }
+
     pub fn select_all(&mut self) {
+        self.extra.clear();
         self.sub_cursor = Cursor { x: 0, y: 0 };
         self.cursor = Cursor {
             x: self.text[self.text.len() - 1].len(),
@@ -165,6 +387,120 @@ impl TextBuffer {
         };
     }
 
+    pub fn cursor_count(&self) -> usize {
+        self.extra.len() + 1
+    }
+
+    // positions of the cursors other than the main one
+    pub fn extra_cursors(&self) -> Vec<Cursor> {
+        self.extra
+            .iter()
+            .map(|(cursor, _)| Cursor {
+                x: cursor.x.min(self.text[cursor.y].len()),
+                y: cursor.y,
+            })
+            .collect()
+    }
+
+    pub fn clear_cursors(&mut self) {
+        self.extra.clear();
+    }
+
+    // add a cursor on the line above the first cursor or below the last one.
+    // the new cursor becomes the main one.
+    pub fn add_cursor(&mut self, below: bool) {
+        let mut cursors = self
+            .extra
+            .iter()
+            .map(|(c, _)| c.clone())
+            .collect::<Vec<_>>();
+        cursors.push(self.cursor.clone());
+        let from = if below {
+            cursors.into_iter().max_by_key(pos)
+        } else {
+            cursors.into_iter().min_by_key(pos)
+        }
+        .unwrap();
+        let y = match below {
+            true if from.y + 1 < self.text.len() => from.y + 1,
+            false if from.y > 0 => from.y - 1,
+            _ => return,
+        };
+        self.last_edit = None;
+        self.extra
+            .push((self.cursor.clone(), self.sub_cursor.clone()));
+        self.cursor = Cursor { x: from.x, y };
+        self.close_cursor_range();
+        self.merge_cursors();
+    }
+
+    // select the word at the cursor, or add a cursor selecting the next
+    // occurrence of the selected text which has no cursor yet
+    pub fn add_next_occurrence(&mut self) {
+        self.last_edit = None;
+        self.fix_cursor_pos();
+        if !self.is_selecting() {
+            let line = &self.text[self.cursor.y];
+            let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+            let start = self.cursor.x
+                - line[..self.cursor.x]
+                    .iter()
+                    .rev()
+                    .take_while(|c| is_word(c))
+                    .count();
+            let end = self.cursor.x
+                + line[self.cursor.x..]
+                    .iter()
+                    .take_while(|c| is_word(c))
+                    .count();
+            if start < end {
+                self.sub_cursor = Cursor {
+                    x: start,
+                    y: self.cursor.y,
+                };
+                self.cursor = Cursor {
+                    x: end,
+                    y: self.cursor.y,
+                };
+            }
+            return;
+        }
+        let range = self.get_range_text();
+        if range.len() != 1 {
+            return;
+        }
+        let query = range[0].iter().collect::<String>();
+        let mut from = if pos(&self.cursor) > pos(&self.sub_cursor) {
+            self.cursor.clone()
+        } else {
+            self.sub_cursor.clone()
+        };
+        for _ in 0..=self.extra.len() {
+            let Some((start, end)) = self.find(&query, from) else {
+                return;
+            };
+            let taken = self
+                .extra
+                .iter()
+                .chain([(self.cursor.clone(), self.sub_cursor.clone())].iter())
+                .any(|(c, s)| pos(c).min(pos(s)) == pos(&start));
+            if !taken {
+                self.extra
+                    .push((self.cursor.clone(), self.sub_cursor.clone()));
+                self.sub_cursor = start;
+                self.cursor = end;
+                return;
+            }
+            from = end;
+        }
+    }
+
+    // the first and last line of the selection, or the line of the cursor
+    pub fn selected_lines(&self) -> (usize, usize) {
+        let (a, b) = (self.cursor.y, self.sub_cursor.y);
+        (a.min(b), a.max(b))
+    }
+
     pub fn is_selecting(&self) -> bool {
         return self.cursor.x != self.sub_cursor.x || self.cursor.y != self.sub_cursor.y;
     }
@@ -285,6 +621,148 @@ impl TextBuffer {
         }
     }
 
+    // run the edit or move at every cursor as one undo step
+    fn for_each_cursor(&mut self, edit: impl Fn(&mut Self)) {
+        if self.extra.is_empty() {
+            edit(self);
+            return;
+        }
+        let before = self.snapshot();
+        let depth = self.history.len();
+        let mut cursors = std::mem::take(&mut self.extra)
+            .into_iter()
+            .map(|(cursor, sub_cursor)| (cursor, sub_cursor, false))
+            .collect::<Vec<(Cursor, Cursor, bool)>>();
+        cursors.push((self.cursor.clone(), self.sub_cursor.clone(), true));
+        cursors.sort_by_key(|(c, s, _)| std::cmp::Reverse(pos(c).min(pos(s))));
+        let mut done = vec![];
+        for (cursor, sub_cursor, main) in cursors {
+            self.cursor = cursor;
+            self.sub_cursor = sub_cursor;
+            edit(self);
+            done.push((
+                self.end_offset(&self.cursor),
+                self.end_offset(&self.sub_cursor),
+                main,
+            ));
+        }
+        if self.history.len() > depth {
+            self.history.truncate(depth);
+            self.history.push(before);
+        }
+        for (cursor, sub_cursor, main) in done {
+            let (cursor, sub_cursor) = (
+                self.cursor_at_offset(cursor),
+                self.cursor_at_offset(sub_cursor),
+            );
+            if main {
+                self.cursor = cursor;
+                self.sub_cursor = sub_cursor;
+            } else {
+                self.extra.push((cursor, sub_cursor));
+            }
+        }
+        self.merge_cursors();
+    }
+
+    // the cursor as lines from the last line and chars from the end of its line,
+    // and how far the remembered column is past the end of the line.
+    // an edit before the cursor doesn't change them.
+    fn end_offset(&self, cursor: &Cursor) -> (usize, usize, usize) {
+        let len = self.text[cursor.y].len();
+        (
+            self.text.len() - 1 - cursor.y,
+            len - cursor.x.min(len),
+            cursor.x.saturating_sub(len),
+        )
+    }
+
+    fn cursor_at_offset(&self, (y, x, past): (usize, usize, usize)) -> Cursor {
+        let y = (self.text.len() - 1).saturating_sub(y);
+        Cursor {
+            x: self.text[y].len().saturating_sub(x) + past,
+            y,
+        }
+    }
+
+    // make one cursor of the ones at the same place or with overlapping selections
+    fn merge_cursors(&mut self) {
+        let mut cursors = std::mem::take(&mut self.extra)
+            .into_iter()
+            .map(|(cursor, sub_cursor)| (cursor, sub_cursor, false))
+            .collect::<Vec<(Cursor, Cursor, bool)>>();
+        cursors.push((self.cursor.clone(), self.sub_cursor.clone(), true));
+        cursors.sort_by_key(|(c, s, _)| pos(c).min(pos(s)));
+        let mut merged: Vec<(Cursor, Cursor, bool)> = vec![];
+        for (cursor, sub_cursor, main) in cursors {
+            let (start, end) = (
+                pos(&cursor).min(pos(&sub_cursor)),
+                pos(&cursor).max(pos(&sub_cursor)),
+            );
+            if let Some(last) = merged.last_mut() {
+                let (last_start, last_end) = (
+                    pos(&last.0).min(pos(&last.1)),
+                    pos(&last.0).max(pos(&last.1)),
+                );
+                if start < last_end || start == last_start || start == end && start == last_end {
+                    if end > last_end {
+                        if pos(&last.0) >= pos(&last.1) {
+                            last.0 = Cursor { x: end.1, y: end.0 };
+                        } else {
+                            last.1 = Cursor { x: end.1, y: end.0 };
+                        }
+                    }
+                    last.2 |= main;
+                    continue;
+                }
+            }
+            merged.push((cursor, sub_cursor, main));
+        }
+        for (cursor, sub_cursor, main) in merged {
+            if main {
+                self.cursor = cursor;
+                self.sub_cursor = sub_cursor;
+            } else {
+                self.extra.push((cursor, sub_cursor));
+            }
+        }
+    }
+
+    // store the state before an edit.
+    // continuous edits of the same kind are merged into one undo step.
+    fn save_history(&mut self, kind: EditKind) {
+        let merge = kind != EditKind::Other && self.last_edit == Some(kind) && !self.is_selecting();
+        if !merge {
+            let snapshot = self.snapshot();
+            self.history.push(snapshot);
+            if self.history.len() > HISTORY_LIMIT {
+                self.history.remove(0);
+            }
+        }
+        self.last_edit = Some(kind);
+        self.future.clear();
+        self.revision_count += 1;
+        self.revision = self.revision_count;
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            cursor: self.cursor.clone(),
+            sub_cursor: self.sub_cursor.clone(),
+            revision: self.revision,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.cursor = snapshot.cursor;
+        self.sub_cursor = snapshot.sub_cursor;
+        self.revision = snapshot.revision;
+        self.last_edit = None;
+        self.extra.clear();
+    }
+
     fn close_cursor_range(&mut self) {
         self.sub_cursor = self.cursor.clone();
     }
@@ -310,3 +788,8 @@ impl TextBuffer {
         }
     }
 }
+
+// a cursor as (y, x) to compare positions
+fn pos(cursor: &Cursor) -> (usize, usize) {
+    (cursor.y, cursor.x)
+}
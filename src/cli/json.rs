@@ -0,0 +1,45 @@
+// --json で出力するためのJSONの組み立て。出力するだけなのでパーサはない。
+
+pub enum Value {
+    Str(String),
+    Num(u64),
+    List(Vec<String>), // of strings
+    Raw(String),       // already in json
+}
+
+pub fn string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+pub fn object(fields: Vec<(&str, Value)>) -> String {
+    let fields = fields
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Str(s) => string(&s),
+                Value::Num(n) => n.to_string(),
+                Value::List(items) => array(items.iter().map(|i| string(i)).collect()),
+                Value::Raw(json) => json,
+            };
+            format!("{}:{}", string(key), value)
+        })
+        .collect::<Vec<String>>();
+    format!("{{{}}}", fields.join(","))
+}
+
+pub fn array(items: Vec<String>) -> String {
+    format!("[{}]", items.join(","))
+}
@@ -0,0 +1,463 @@
+pub mod json;
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::app::app::Startup;
+use crate::app::config::Config;
+use crate::note::daily::parse_day;
+use crate::note::rename::RenamePlan;
+use crate::note::task::open_tasks;
+use crate::note::time::DateTime;
+use crate::note::vault::{NoteMeta, Vault};
+use crate::search::index::Index;
+use json::Value;
+
+// TUIを使わないサブコマンド。シェルスクリプトから使えるように、
+// --json で機械向けの出力をして、終了コードで結果を返す。
+// 終了コードは grep と同じで、0 が成功、1 が見つからない、2 がエラー。
+
+pub const USAGE: &str = "usage: notes-cli [DIR | FILE]
+       notes-cli new [--json] TITLE
+       notes-cli add [--json] [--to NAME] [TEXT]
+       notes-cli today [--json] [DAY]
+       notes-cli list [--json] [--tag TAG]
+       notes-cli tags [--json]
+       notes-cli tasks [--json] [--tag TAG]
+       notes-cli cat NAME
+       notes-cli search [--json] QUERY
+       notes-cli edit NAME
+       notes-cli rm [--json] NAME
+       notes-cli mv [--json] [--dry-run] NAME NEW_PATH
+
+QUERY has words (all of them must match), \"phrases\", prefix*, OR, NOT or -word,
+(groups) and fields: title:word tag:name path:word.
+NAME is a path in the vault, with or without the extension, or the title of a note.
+TAG is a #tag in notes or in the front matter. nested tags are listed with the parent.
+tasks lists open \"- [ ]\" tasks as PATH:LINE:TEXT, by note. --json adds the due date
+and priority of due:YYYY-MM-DD and !high, !medium or !low. @today and @tomorrow
+are written as due:YYYY-MM-DD when the note is saved or captured.
+add appends TEXT, or stdin without TEXT, to the inbox note or NAME with a timestamp.
+mv rewrites [[links]] and relative links to the note. --dry-run only shows them.
+today prints the daily note of DAY (today, yesterday, tomorrow, +N, -N or YYYY-MM-DD),
+which is created from the template if missing.
+text piped into the tui is opened as a new note.
+exit status is 0 on success, 1 if nothing is found and 2 on errors.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    NotFound(String),
+    Usage(String),
+    Failed(String),
+    Closed, // stdout was closed by the reader, like `| head`
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Closed => 0,
+            CliError::NotFound(_) => 1,
+            CliError::Usage(_) | CliError::Failed(_) => 2,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            CliError::NotFound(m) | CliError::Failed(m) => m.clone(),
+            CliError::Usage(m) => format!("{}\n{}", m, USAGE),
+            CliError::Closed => String::new(),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::BrokenPipe {
+            return CliError::Closed;
+        }
+        CliError::Failed(e.to_string())
+    }
+}
+
+// what the arguments ask for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cli {
+    Tui(Startup),
+    Vault(PathBuf), // the tui with another vault
+    Help,
+    New(String),
+    Add(String, Option<String>), // text (empty for stdin) and the target note
+    Today(String),               // the day, empty for today
+    List(Option<String>),        // notes with the tag, or all notes
+    Tags,
+    Tasks(Option<String>), // open tasks with the tag, or all of them
+    Cat(String),
+    Search(String),
+    Edit(String),
+    Rm(String),
+    Mv(String, String, bool), // with true, only shows the links to rewrite
+}
+
+pub fn parse_args(args: &[String]) -> Result<(Cli, bool), CliError> {
+    let mut json = false;
+    let mut to = None;
+    let mut tag = None;
+    let mut dry_run = false;
+    let mut rest = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok((Cli::Help, json)),
+            "--json" => json = true,
+            "--dry-run" => dry_run = true,
+            "--to" => match args.next() {
+                Some(name) => to = Some(name.clone()),
+                None => return Err(CliError::Usage(String::from("--to: name required"))),
+            },
+            "--tag" => match args.next() {
+                Some(name) => tag = Some(name.clone()),
+                None => return Err(CliError::Usage(String::from("--tag: tag required"))),
+            },
+            arg if arg.starts_with("--") => {
+                return Err(CliError::Usage(format!("unknown option: {}", arg)))
+            }
+            arg => rest.push(arg),
+        }
+    }
+    if to.is_some() && rest.first() != Some(&"add") {
+        return Err(CliError::Usage(String::from("--to is only for add")));
+    }
+    if tag.is_some() && !matches!(rest.first(), Some(&"list") | Some(&"tasks")) {
+        return Err(CliError::Usage(String::from(
+            "--tag is only for list and tasks",
+        )));
+    }
+    if dry_run && rest.first() != Some(&"mv") {
+        return Err(CliError::Usage(String::from("--dry-run is only for mv")));
+    }
+    let cli = match rest.as_slice() {
+        [] => Cli::Tui(Startup::Vault),
+        ["help"] => Cli::Help,
+        ["new", title @ ..] if !title.is_empty() => Cli::New(title.join(" ")),
+        ["add", text @ ..] => Cli::Add(text.join(" "), to),
+        ["today"] => Cli::Today(String::new()),
+        ["today", day] => Cli::Today(day.to_string()),
+        ["list"] => Cli::List(tag),
+        ["tags"] => Cli::Tags,
+        ["tasks"] => Cli::Tasks(tag),
+        ["cat", name] => Cli::Cat(name.to_string()),
+        ["search", query @ ..] if !query.is_empty() => Cli::Search(query.join(" ")),
+        ["edit", name] => Cli::Edit(name.to_string()),
+        ["rm", name] => Cli::Rm(name.to_string()),
+        ["mv", from, to] => Cli::Mv(from.to_string(), to.to_string(), dry_run),
+        [command, ..] if is_subcommand(command) => {
+            return Err(CliError::Usage(format!("{}: wrong arguments", command)))
+        }
+        // a directory is opened as the vault, a file is opened alone
+        [path] if Path::new(path).is_dir() => Cli::Vault(PathBuf::from(path)),
+        [path] => Cli::Tui(Startup::File(PathBuf::from(path))),
+        _ => return Err(CliError::Usage(String::from("too many arguments"))),
+    };
+    Ok((cli, json))
+}
+
+fn is_subcommand(name: &str) -> bool {
+    [
+        "new", "add", "today", "list", "tags", "tasks", "cat", "search", "edit", "rm", "mv",
+    ]
+    .contains(&name)
+}
+
+// run a subcommand. returns how to start the tui if it is needed.
+pub fn run(args: &[String], config: &mut Config) -> Result<Option<Startup>, CliError> {
+    let (cli, json) = parse_args(args)?;
+    match cli {
+        Cli::Help => {
+            println!("{}", USAGE);
+            return Ok(None);
+        }
+        Cli::Tui(startup) => return Ok(Some(startup)),
+        Cli::Vault(dir) => {
+            config.notes_dir = dir;
+            return Ok(Some(Startup::Vault));
+        }
+        _ => {}
+    }
+    let mut vault = Vault::open(&config.notes_dir, &config.extensions, config.name_style)?;
+    let mut out = io::stdout().lock();
+    match cli {
+        Cli::New(title) => {
+            let path = vault.create_note(&title)?;
+            let meta = find_one(&vault, &path.to_string_lossy())?.clone();
+            if json {
+                writeln!(out, "{}", note_json(&meta))?;
+            } else {
+                writeln!(out, "{}", meta.path.to_string_lossy())?;
+            }
+        }
+        Cli::Today(day) => {
+            let Some(date) = parse_day(&day, &DateTime::now()) else {
+                return Err(CliError::Usage(format!("invalid day: {}", day)));
+            };
+            let path = vault.create_daily(&config.daily, &date)?;
+            let meta = find_one(&vault, &path.to_string_lossy())?;
+            if json {
+                writeln!(out, "{}", note_json(meta))?;
+            } else {
+                writeln!(out, "{}", meta.path.to_string_lossy())?;
+            }
+        }
+        Cli::Add(text, to) => {
+            let text = if !text.is_empty() {
+                text
+            } else if !termion::is_tty(&io::stdin()) {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                return Err(CliError::Usage(String::from("add: text required")));
+            };
+            if text.trim().is_empty() {
+                return Err(CliError::Usage(String::from("add: empty text")));
+            }
+            let target = to.unwrap_or_else(|| config.inbox.clone());
+            let path = vault.capture(&target, &text, &DateTime::now())?;
+            let meta = find_one(&vault, &path.to_string_lossy())?;
+            if json {
+                writeln!(out, "{}", note_json(meta))?;
+            } else {
+                writeln!(out, "{}", meta.path.to_string_lossy())?;
+            }
+        }
+        Cli::List(tag) => {
+            let notes = match &tag {
+                Some(tag) => vault.tagged(tag),
+                None => vault.notes().iter().collect(),
+            };
+            if json {
+                let notes = notes.iter().map(|n| note_json(n)).collect();
+                writeln!(out, "{}", json::array(notes))?;
+            } else {
+                for note in notes.iter() {
+                    writeln!(out, "{}\t{}", note.path.to_string_lossy(), note.title)?;
+                }
+            }
+            if let (Some(tag), true) = (tag, notes.is_empty()) {
+                return Err(CliError::NotFound(format!("no note has #{}", tag)));
+            }
+        }
+        Cli::Tags => {
+            let tags = vault.tags();
+            if json {
+                let tags = tags
+                    .iter()
+                    .map(|(tag, count)| {
+                        json::object(vec![
+                            ("tag", Value::Str(tag.clone())),
+                            ("notes", Value::Num(*count as u64)),
+                        ])
+                    })
+                    .collect();
+                writeln!(out, "{}", json::array(tags))?;
+            } else {
+                for (tag, count) in tags.iter() {
+                    writeln!(out, "#{}\t{}", tag, count)?;
+                }
+            }
+        }
+        Cli::Tasks(tag) => {
+            let tasks = open_tasks(&vault, tag.as_deref());
+            if json {
+                let tasks = tasks
+                    .iter()
+                    .map(|task| {
+                        let mut fields = vec![
+                            ("path", Value::Str(task.path.to_string_lossy().to_string())),
+                            ("title", Value::Str(task.title.clone())),
+                            ("line", Value::Num(task.line as u64 + 1)),
+                            ("text", Value::Str(task.text.clone())),
+                            (
+                                "priority",
+                                Value::Str(format!("{:?}", task.priority).to_lowercase()),
+                            ),
+                        ];
+                        if let Some(due) = task.due {
+                            fields.push(("due", Value::Str(due.format("%Y-%m-%d"))));
+                        }
+                        json::object(fields)
+                    })
+                    .collect();
+                writeln!(out, "{}", json::array(tasks))?;
+            } else {
+                for task in tasks.iter() {
+                    writeln!(
+                        out,
+                        "{}:{}:{}",
+                        task.path.to_string_lossy(),
+                        task.line + 1,
+                        task.text
+                    )?;
+                }
+            }
+            if tasks.is_empty() {
+                return Err(CliError::NotFound(match tag {
+                    Some(tag) => format!("no open task has #{}", tag),
+                    None => String::from("no open task"),
+                }));
+            }
+        }
+        Cli::Cat(name) => {
+            let meta = find_one(&vault, &name)?;
+            let content = fs::read_to_string(vault.root.join(&meta.path))?;
+            write!(out, "{}", content)?;
+        }
+        Cli::Search(query) => {
+            let mut index = Index::open(&vault.root)?;
+            index.sync(&vault)?;
+            let hits = index
+                .search(&query, usize::MAX)
+                .map_err(|e| CliError::Failed(format!("search: {}", e)))?;
+            if json {
+                let hits = hits
+                    .iter()
+                    .map(|hit| {
+                        json::object(vec![
+                            ("path", Value::Str(hit.path.to_string_lossy().to_string())),
+                            ("line", Value::Num(hit.line as u64 + 1)),
+                            ("column", Value::Num(hit.column as u64 + 1)),
+                            ("score", Value::Num(hit.score as u64)),
+                            ("text", Value::Str(hit.text.clone())),
+                        ])
+                    })
+                    .collect();
+                writeln!(out, "{}", json::array(hits))?;
+            } else {
+                for hit in hits.iter() {
+                    writeln!(
+                        out,
+                        "{}:{}:{}",
+                        hit.path.to_string_lossy(),
+                        hit.line + 1,
+                        hit.text
+                    )?;
+                }
+            }
+            if hits.is_empty() {
+                return Err(CliError::NotFound(format!("no match: {}", query)));
+            }
+        }
+        Cli::Edit(name) => {
+            let path = vault.root.join(&find_one(&vault, &name)?.path);
+            return Ok(Some(Startup::File(path)));
+        }
+        Cli::Rm(name) => {
+            let meta = find_one(&vault, &name)?.clone();
+            vault.remove(&meta.path)?;
+            if json {
+                writeln!(out, "{}", note_json(&meta))?;
+            }
+        }
+        Cli::Mv(from, to, dry_run) => {
+            let from = find_one(&vault, &from)?.path.clone();
+            let to = move_target(&vault, &from, &to);
+            let plan = vault.plan_rename(&from, &to)?;
+            if dry_run {
+                if json {
+                    let fields = vec![
+                        ("from", Value::Str(plan.from.to_string_lossy().to_string())),
+                        ("to", Value::Str(plan.to.to_string_lossy().to_string())),
+                        ("rewrites", Value::Raw(rewrites_json(&plan))),
+                    ];
+                    writeln!(out, "{}", json::object(fields))?;
+                } else {
+                    for line in plan.preview() {
+                        writeln!(out, "{}", line)?;
+                    }
+                }
+                return Ok(None);
+            }
+            let path = vault.apply_rename(&plan)?;
+            let meta = find_one(&vault, &path.to_string_lossy())?;
+            if json {
+                let mut fields = note_fields(meta);
+                fields.push(("rewrites", Value::Raw(rewrites_json(&plan))));
+                writeln!(out, "{}", json::object(fields))?;
+            } else {
+                writeln!(out, "{}", meta.path.to_string_lossy())?;
+                if plan.change_count() > 0 {
+                    eprintln!(
+                        "updated {} links in {} notes",
+                        plan.change_count(),
+                        plan.files.len()
+                    );
+                }
+            }
+        }
+        Cli::Tui(_) | Cli::Vault(_) | Cli::Help => {}
+    }
+    Ok(None)
+}
+
+// a folder ("dir/" or an existing one) keeps the file name
+fn move_target(vault: &Vault, from: &Path, to: &str) -> PathBuf {
+    let target = Path::new(to);
+    let relative = target.strip_prefix(&vault.root).unwrap_or(target);
+    if to.ends_with('/') || vault.root.join(relative).is_dir() {
+        relative.join(from.file_name().unwrap_or_default())
+    } else {
+        target.to_path_buf()
+    }
+}
+
+fn find_one<'a>(vault: &'a Vault, name: &str) -> Result<&'a NoteMeta, CliError> {
+    let relative = Path::new(name)
+        .strip_prefix(&vault.root)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| name.to_string());
+    let found = vault.find(&relative);
+    match found.as_slice() {
+        [] => Err(CliError::NotFound(format!("no such note: {}", name))),
+        [note] => Ok(note),
+        notes => Err(CliError::Failed(format!(
+            "{} is ambiguous: {}",
+            name,
+            notes
+                .iter()
+                .map(|n| n.path.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+fn note_json(note: &NoteMeta) -> String {
+    json::object(note_fields(note))
+}
+
+fn note_fields(note: &NoteMeta) -> Vec<(&'static str, Value)> {
+    vec![
+        ("path", Value::Str(note.path.to_string_lossy().to_string())),
+        ("title", Value::Str(note.title.clone())),
+        ("tags", Value::List(note.tags.clone())),
+        ("aliases", Value::List(note.aliases.clone())),
+        ("modified", Value::Num(note.modified)),
+        ("size", Value::Num(note.size)),
+    ]
+}
+
+// the changed lines of the rename, as a json array
+fn rewrites_json(plan: &RenamePlan) -> String {
+    let mut items = vec![];
+    for file in plan.files.iter() {
+        for change in file.changes.iter() {
+            items.push(json::object(vec![
+                ("path", Value::Str(file.path.to_string_lossy().to_string())),
+                ("line", Value::Num(change.line as u64 + 1)),
+                ("before", Value::Str(change.before.clone())),
+                ("after", Value::Str(change.after.clone())),
+            ]));
+        }
+    }
+    json::array(items)
+}
@@ -1,28 +1,101 @@
 mod app;
 mod buffer;
+mod cli;
+mod note;
+mod search;
 
-use app::app::App;
-use std::io::{stdin, stdout};
+use app::app::{App, Startup};
+use app::config::Config;
+use cli::CliError;
+use std::env;
+use std::io::{stdin, stdout, Read};
+use std::process;
 use termion;
 use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 
 fn main() {
-    let stdin = stdin();
+    let mut config = Config::load().unwrap_or_else(|e| exit_with(&e, 2));
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    // subcommands run without the tui
+    let mut startup = match cli::run(&args, &mut config) {
+        Ok(Some(startup)) => startup,
+        Ok(None) => return,
+        Err(CliError::Closed) => return,
+        Err(e) => exit_with(&e.message(), e.exit_code()),
+    };
+    if !termion::is_tty(&stdout()) {
+        exit_with("stdout is not a terminal", 2);
+    }
+    // piped text is opened as a new note, and keys are read from the tty
+    let input: Box<dyn Read + Send> = if termion::is_tty(&stdin()) {
+        Box::new(stdin())
+    } else {
+        if startup != Startup::Vault {
+            exit_with("piped text can only be opened in the vault", 2);
+        }
+        let mut text = String::new();
+        stdin()
+            .read_to_string(&mut text)
+            .unwrap_or_else(|e| exit_with(&e.to_string(), 2));
+        startup = Startup::Text(text);
+        Box::new(termion::get_tty().unwrap_or_else(|e| exit_with(&e.to_string(), 2)))
+    };
+    let mut app = App::setup(config, startup).unwrap_or_else(|e| exit_with(&e.to_string(), 2));
+
     let stdout = MouseTerminal::from(
         stdout().into_raw_mode().unwrap(), // .into_alternate_screen()
                                            // .unwrap(), // wip wip
     );
-    let mut app = App::setup();
-    app.run(stdin, stdout);
+    app.run(input, stdout);
+}
+
+fn exit_with(message: &str, code: i32) -> ! {
+    eprintln!("notes-cli: {}", message);
+    process::exit(code);
 }
 
 #[cfg(test)]
 mod test {
-    use crate::buffer::buffer::ViewBuffer;
+    use crate::app::app::Startup;
+    use crate::app::command::{complete_command, parse_command, Command};
+    use crate::app::config::Config;
+    use crate::app::layout::{Direction, Layout, Node, Rect, SplitSize};
+    use crate::buffer::agenda::{AgendaAction, AgendaBuffer};
+    use crate::buffer::backlinks::{BacklinksAction, BacklinksBuffer};
+    use crate::buffer::buffer::{Cursor, ViewBuffer};
+    use crate::buffer::drawer::{DrawerAction, DrawerBuffer, DrawerMode};
     use crate::buffer::editor::EditorBuffer;
+    use crate::buffer::outline::{OutlineAction, OutlineBuffer};
+    use crate::buffer::picker::{PickerBuffer, PickerItem};
+    use crate::buffer::prompt::{PromptAction, PromptBuffer};
+    use crate::buffer::snippet::{parse_snippet, TabStop};
+    use crate::buffer::tasks::{TasksAction, TasksBuffer};
     use crate::buffer::text::TextBuffer;
+    use crate::cli::json::{self, Value};
+    use crate::cli::{self, parse_args, Cli};
+    use crate::note::backlink::{backlinks, unlinked_mentions};
+    use crate::note::daily::{parse_day, Daily};
+    use crate::note::fold::Blocks;
+    use crate::note::front_matter::{self, FrontMatter};
+    use crate::note::link::{find_heading, find_links, find_mention, find_url_ranges, Link};
+    use crate::note::list::{parse_item, renumber, set_checkbox};
+    use crate::note::outline::{headings, move_section, shift_section};
+    use crate::note::tag::{extract_tags, tag_ancestors};
+    use crate::note::task::{
+        agenda, complete_task, find_tasks, fix_due_dates, open_tasks, parse_meta, postpone_task,
+        Priority,
+    };
+    use crate::note::template::{self, list_templates};
+    use crate::note::time::DateTime;
+    use crate::note::vault::{capture_item, slugify, NameStyle, Vault};
+    use crate::note::walk::{glob_match, walk_note_paths, walk_notes_async};
+    use crate::search::fuzzy::fuzzy_match;
+    use crate::search::index::{tokenize, tokenize_query, Index};
+    use crate::search::query::{parse_query, Field, Query};
+    use std::fs;
     use std::io::{stdin, stdout, Write};
+    use std::path::{Path, PathBuf};
     use termion;
     use termion::event::{Event, Key};
     use termion::input::{MouseTerminal, TermRead};
@@ -215,4 +288,1546 @@ mod test {
             stdout.flush().unwrap();
         }
     }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("note-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn drawer_tree() {
+        let root = temp_dir("drawer");
+        fs::create_dir_all(root.join("diary")).unwrap();
+        fs::write(root.join("diary/a.md"), "a").unwrap();
+        fs::write(root.join("memo.md"), "memo").unwrap();
+
+        let mut drawer = DrawerBuffer::new(&root);
+        drawer.set_view_info(30, 10, true);
+        assert_eq!(drawer.entries.len(), 2);
+        assert!(drawer.entries[0].is_dir); // directories first
+
+        drawer.update_view(Event::Key(Key::Right));
+        assert_eq!(drawer.entries.len(), 3);
+        drawer.update_view(Event::Key(Key::Down));
+        drawer.update_view(Event::Key(Key::Char('\n')));
+        assert_eq!(
+            drawer.take_action(),
+            Some(DrawerAction::Open(root.join("diary/a.md")))
+        );
+
+        // new file next to the selected one
+        drawer.update_view(Event::Key(Key::Char('a')));
+        for c in "b.md\n".chars() {
+            drawer.update_view(Event::Key(Key::Char(c)));
+        }
+        assert!(root.join("diary/b.md").exists());
+        assert_eq!(
+            drawer.take_action(),
+            Some(DrawerAction::Open(root.join("diary/b.md")))
+        );
+        // names can't leave the notes root
+        for name in ["../../x.md\n", "/tmp/x.md\n"] {
+            drawer.update_view(Event::Key(Key::Char('a')));
+            for c in name.chars() {
+                drawer.update_view(Event::Key(Key::Char(c)));
+            }
+            assert!(drawer.get_view().last().unwrap().contains("outside"));
+            assert_eq!(drawer.take_action(), None);
+        }
+        assert!(!root.join("../x.md").exists());
+
+        drawer.update_view(Event::Key(Key::Char('d')));
+        drawer.update_view(Event::Key(Key::Char('y')));
+        assert!(!root.join("diary/b.md").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn layout_calc_and_compose() {
+        let mut layout = Layout::new(
+            Node::split(
+                Direction::Horizontal,
+                SplitSize::First(4),
+                Node::Pane(0),
+                Node::split(
+                    Direction::Vertical,
+                    SplitSize::Ratio(0.5),
+                    Node::Pane(1),
+                    Node::Pane(2),
+                ),
+            ),
+            0,
+        );
+        let rects = layout.calc(10, 5);
+        assert_eq!(
+            rects[0],
+            (
+                0,
+                Rect {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 5
+                }
+            )
+        );
+        assert_eq!(rects[1].1.x, 5);
+        assert_eq!(rects[1].1.height + rects[2].1.height, 4);
+
+        let views = vec![
+            (0, vec!["abcdef".to_string()]),
+            (1, vec!["あいう".to_string()]),
+            (2, vec![]),
+        ];
+        let screen = layout.compose(10, 5, &views);
+        assert_eq!(screen[0], "abcd│あい ");
+        assert_eq!(screen[2], "    │─────");
+
+        layout.resize(0, Direction::Horizontal, 2);
+        assert_eq!(layout.calc(10, 5)[0].1.width, 6);
+        layout.toggle(0);
+        assert_eq!(layout.calc(10, 5)[0].1.width, 10);
+        layout.focus_next();
+        assert_eq!(layout.focus, 1);
+    }
+
+    #[test]
+    fn text_undo_redo() {
+        let mut editor = EditorBuffer::new("");
+        editor.set_view_info(20, 10, true);
+        // nothing to remove is no edit
+        editor.update_view(Event::Key(Key::Backspace));
+        editor.update_view(Event::Key(Key::Delete));
+        assert!(!editor.is_modified());
+        assert!(!editor.text.undo());
+        for c in "ab cd".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        assert!(editor.is_modified());
+        editor.update_view(Event::Key(Key::Ctrl('z')));
+        assert_eq!(editor.text.text[0].iter().collect::<String>(), "ab ");
+        editor.update_view(Event::Key(Key::Ctrl('z')));
+        editor.update_view(Event::Key(Key::Ctrl('z')));
+        assert_eq!(editor.text.text[0].iter().collect::<String>(), "");
+        assert!(!editor.is_modified());
+        editor.update_view(Event::Key(Key::Ctrl('y')));
+        assert_eq!(editor.text.text[0].iter().collect::<String>(), "ab");
+        assert!(editor.is_modified());
+    }
+
+    #[test]
+    fn editor_keeps_line_ending() {
+        let dir = temp_dir("line-ending");
+        let path = dir.join("crlf.md");
+        fs::write(&path, "\u{feff}# title\r\nbody\r\n").unwrap();
+        let mut editor = EditorBuffer::open(&path).unwrap();
+        assert_eq!(editor.line_ending(), "CRLF");
+        assert_eq!(editor.encoding(), "UTF-8 BOM");
+        assert_eq!(editor.text.word_count(), 3);
+        editor.text.select_all();
+        assert_eq!(editor.text.selection_len(), 12);
+        editor.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\u{feff}# title\r\nbody\r\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn command_parse_and_complete() {
+        assert_eq!(parse_command("120"), Ok(Command::Goto(120)));
+        assert_eq!(
+            parse_command("open diary/a.md"),
+            Ok(Command::Open("diary/a.md".to_string()))
+        );
+        assert!(parse_command("open").is_err());
+        assert!(parse_command("foo").is_err());
+
+        let root = temp_dir("complete");
+        fs::create_dir_all(root.join("diary")).unwrap();
+        fs::write(root.join("diary/a.md"), "").unwrap();
+        fs::write(root.join("diary/ab.md"), "").unwrap();
+        assert_eq!(complete_command("sa", &root), vec!["saveas"]);
+        assert_eq!(complete_command("open di", &root), vec!["open diary/"]);
+        assert_eq!(
+            complete_command("open diary/a", &root),
+            vec!["open diary/a.md", "open diary/ab.md"]
+        );
+
+        let mut prompt = PromptBuffer::new();
+        prompt.set_view_info(40, 1, true);
+        prompt.open(": ", "open diary/a", "command");
+        prompt.update_view(Event::Key(Key::Char('\t')));
+        let Some(PromptAction::Complete(text)) = prompt.take_action() else {
+            panic!("no completion request");
+        };
+        prompt.set_completions(complete_command(&text, &root));
+        prompt.update_view(Event::Key(Key::Char('\t')));
+        assert_eq!(prompt.get_text(), "open diary/a.md");
+        prompt.update_view(Event::Key(Key::Char('\n')));
+        assert_eq!(
+            prompt.take_action(),
+            Some(PromptAction::Submit("open diary/a.md".to_string()))
+        );
+        prompt.open(": ", "", "command");
+        prompt.update_view(Event::Key(Key::Up));
+        assert_eq!(prompt.get_text(), "open diary/a.md");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn text_find() {
+        let mut text = TextBuffer::new("foo bar\nbar baz");
+        let (start, end) = text.find("bar", text.get_cursor_pos()).unwrap();
+        assert_eq!((start.x, start.y, end.x), (4, 0, 7));
+        text.select_range(start, end);
+        assert_eq!(text.selection_len(), 3);
+        let (start, _) = text.find("bar", text.get_cursor_pos()).unwrap();
+        assert_eq!((start.x, start.y), (0, 1));
+        let (start, _) = text.find("foo", text.get_cursor_pos()).unwrap();
+        assert_eq!((start.x, start.y), (0, 0)); // wrap around
+        assert!(text.find("qux", text.get_cursor_pos()).is_none());
+    }
+
+    #[test]
+    fn fuzzy_ranking() {
+        let m = fuzzy_match("sv", "Save").unwrap();
+        assert_eq!(m.positions, vec![0, 2]);
+        assert!(fuzzy_match("sx", "Save").is_none());
+        // word starts are better than the middle of words
+        let m = fuzzy_match("nb", "Next Buffer").unwrap();
+        assert_eq!(m.positions, vec![0, 5]);
+    }
+
+    #[test]
+    fn note_walk_ignore() {
+        let glob = |p: &str, t: &str| {
+            glob_match(
+                &p.chars().collect::<Vec<char>>(),
+                &t.chars().collect::<Vec<char>>(),
+            )
+        };
+        assert!(glob("*.md", "memo.md"));
+        assert!(!glob("*.md", "a/memo.md"));
+        assert!(glob("a/**/b.md", "a/b.md"));
+        assert!(glob("a/**/b.md", "a/x/y/b.md"));
+        assert!(glob("memo?.[0-9]", "memo1.5"));
+        assert!(!glob("memo?.[!0-9]", "memo1.5"));
+
+        let dir = temp_dir("walk");
+        fs::create_dir_all(dir.join("notes/draft")).unwrap();
+        fs::create_dir_all(dir.join("build")).unwrap();
+        fs::write(dir.join(".gitignore"), "build/\n*.tmp.md\n/top.md\n").unwrap();
+        fs::write(dir.join("notes/.ignore"), "draft/*\n!draft/keep.md\n").unwrap();
+        fs::write(dir.join("top.md"), "").unwrap();
+        fs::write(dir.join("a.md"), "text\n# Alpha\n").unwrap();
+        fs::write(dir.join("x.tmp.md"), "").unwrap();
+        fs::write(dir.join("image.png"), "").unwrap();
+        fs::write(dir.join("build/b.md"), "").unwrap();
+        fs::write(dir.join("notes/top.md"), "").unwrap();
+        fs::write(dir.join("notes/draft/drop.md"), "").unwrap();
+        fs::write(dir.join("notes/draft/keep.md"), "").unwrap();
+
+        let extensions = vec![String::from("md")];
+        let paths = walk_note_paths(&dir, &extensions);
+        assert_eq!(
+            paths,
+            vec!["a.md", "notes/top.md", "notes/draft/keep.md"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        walk_notes_async(&dir, &extensions, tx);
+        let files = rx.iter().flatten().collect::<Vec<_>>();
+        assert_eq!(
+            files.iter().map(|f| f.path.clone()).collect::<Vec<_>>(),
+            paths
+        );
+        assert_eq!(files[0].title, "Alpha");
+        assert_eq!(files[1].title, "top");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn picker_add_items() {
+        let item = |label: &str, detail: &str| PickerItem {
+            label: label.to_string(),
+            detail: detail.to_string(),
+        };
+        let mut picker = PickerBuffer::new("files", vec![item("daily/0101.md", "New Year")]);
+        picker.match_detail = true;
+        picker.set_view_info(80, 10, true);
+        for c in "year".chars() {
+            picker.update_view(Event::Key(Key::Char(c)));
+        }
+        assert_eq!(picker.filtered, vec![0]);
+
+        // items found later are filtered by the current query
+        picker.add_items(vec![item("year.md", ""), item("memo.md", "")]);
+        assert_eq!(picker.filtered, vec![1, 0]);
+        picker.update_view(Event::Key(Key::Down));
+        assert_eq!(picker.selected_index(), Some(0));
+        picker.add_items(vec![item("years.md", "")]);
+        assert_eq!(picker.selected_index(), Some(0)); // the selection is kept
+
+        picker.update_view(Event::Key(Key::Char('s')));
+        assert_eq!(picker.filtered, vec![3]);
+        picker.update_view(Event::Key(Key::Backspace));
+        assert_eq!(picker.filtered.len(), 3);
+    }
+
+    #[test]
+    fn vault_notes() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  会議 メモ 2024 "), "会議-メモ-2024");
+        assert_eq!(slugify("!!!"), "");
+
+        let mut config = Config::default();
+        // not the directory the command runs in
+        assert!(config.notes_dir.ends_with("notes"));
+        assert_ne!(
+            Some(&config.notes_dir),
+            std::env::current_dir().ok().as_ref()
+        );
+        config
+            .parse("# comment\nnotes_dir = /tmp/notes\nextensions = md, .txt\nnew_note_name = timestamp\n")
+            .unwrap();
+        assert_eq!(config.notes_dir, PathBuf::from("/tmp/notes"));
+        assert_eq!(config.extensions, vec!["md", "txt"]);
+        assert_eq!(config.name_style, NameStyle::Timestamp);
+        assert!(config.parse("drawer_width = wide").is_err());
+        assert!(config.parse("color = red").is_err());
+
+        let time = DateTime::from_unix(0);
+        assert_eq!(time.format("%Y%%").len(), 5);
+        assert!(DateTime::now().year >= 2024);
+
+        let dir = temp_dir("vault");
+        fs::write(dir.join("old.md"), "# Old\n").unwrap();
+        let extensions = vec![String::from("md")];
+        let mut vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        assert_eq!(vault.notes().len(), 1);
+        assert_eq!(vault.notes()[0].title, "Old");
+        assert!(dir.join(".note/cache").exists());
+
+        let path = vault.create_note("Weekly Review").unwrap();
+        assert_eq!(path, dir.join("weekly-review.md"));
+        assert_eq!(
+            vault.new_note_path("Weekly Review"),
+            dir.join("weekly-review-2.md")
+        );
+        let name = vault.new_note_path("");
+        assert_eq!(name.file_name().unwrap().len(), "20240101-093000.md".len());
+
+        // titles come from the cache while files are unchanged
+        let vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let titles = vault
+            .notes()
+            .iter()
+            .map(|n| n.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Old", "Weekly Review"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cli_args_and_json() {
+        let args = |line: &str| {
+            let args = line
+                .split_whitespace()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>();
+            parse_args(&args)
+        };
+        assert_eq!(args("").unwrap(), (Cli::Tui(Startup::Vault), false));
+        assert_eq!(
+            args("new Weekly Review --json").unwrap(),
+            (Cli::New(String::from("Weekly Review")), true)
+        );
+        assert_eq!(
+            args("mv a b").unwrap().0,
+            Cli::Mv(String::from("a"), String::from("b"), false)
+        );
+        assert_eq!(args("cat").unwrap_err().exit_code(), 2);
+        assert_eq!(args("list --all").unwrap_err().exit_code(), 2);
+        assert_eq!(
+            args("memo.md").unwrap().0,
+            Cli::Tui(Startup::File(PathBuf::from("memo.md")))
+        );
+
+        assert_eq!(json::string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+        assert_eq!(
+            json::object(vec![
+                ("path", Value::Str(String::from("a.md"))),
+                ("line", Value::Num(3)),
+            ]),
+            r#"{"path":"a.md","line":3}"#
+        );
+
+        let dir = temp_dir("cli");
+        let extensions = vec![String::from("md")];
+        let mut vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        vault.create_note("Plan").unwrap();
+        fs::create_dir_all(dir.join("old")).unwrap();
+        fs::write(dir.join("old/plan.md"), "# Old Plan\n").unwrap();
+        vault.refresh().unwrap();
+        assert_eq!(vault.find("plan.md").len(), 1);
+        assert_eq!(vault.find("plan").len(), 1); // the path wins over the file name
+        assert_eq!(vault.find("old plan")[0].path, PathBuf::from("old/plan.md"));
+
+        let plan = vault
+            .plan_rename(Path::new("plan.md"), Path::new("done/plan"))
+            .unwrap();
+        let moved = vault.apply_rename(&plan).unwrap();
+        assert_eq!(moved, dir.join("done/plan.md"));
+        assert!(vault.plan_rename(&moved, Path::new("old/plan")).is_err());
+        assert_eq!(vault.find("plan").len(), 2);
+        vault.remove(Path::new("old/plan.md")).unwrap();
+        assert!(!dir.join("old/plan.md").exists());
+
+        // moving into a folder keeps the file name
+        let mut config = Config {
+            notes_dir: dir.clone(),
+            extensions: extensions.clone(),
+            ..Default::default()
+        };
+        let mut run = |line: &str| {
+            let args = line
+                .split_whitespace()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>();
+            cli::run(&args, &mut config)
+        };
+        fs::write(dir.join("memo.md"), "# Memo\n").unwrap();
+        run("mv memo.md archive/").unwrap();
+        assert!(dir.join("archive/memo.md").exists());
+        run("mv archive/memo.md old").unwrap();
+        assert!(dir.join("old/memo.md").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn quick_capture() {
+        let now = DateTime {
+            year: 2024,
+            month: 1,
+            day: 2,
+            hour: 9,
+            minute: 5,
+            second: 0,
+            weekday: 2,
+        };
+        assert_eq!(
+            capture_item("first\n\n third \n", &now),
+            "- 2024-01-02 09:05 first\n\n   third\n"
+        );
+
+        let dir = temp_dir("capture");
+        let extensions = vec![String::from("md")];
+        let mut vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let path = vault.capture("Inbox", "milk", &now).unwrap();
+        assert_eq!(path, dir.join("inbox.md"));
+        fs::write(&path, "# Inbox\n\n- old").unwrap();
+        vault.capture("inbox", "eggs", &now).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Inbox\n\n- old\n- 2024-01-02 09:05 eggs\n"
+        );
+        assert_eq!(vault.find("Inbox").len(), 1);
+        // tasks of the captured text get the date of the capture
+        vault
+            .capture("inbox", "shopping\n- [ ] bread @tomorrow", &now)
+            .unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("  - [ ] bread due:2024-01-03\n"));
+        // and the ones of a saved note get today
+        let note = dir.join("inbox.md");
+        let mut editor = EditorBuffer::open(&note).unwrap();
+        editor.text.set_cursor(0, 0);
+        for c in "- [ ] call @today".chars() {
+            editor.text.input(c);
+        }
+        editor.text.enter();
+        editor.save().unwrap();
+        let due = format!("- [ ] call due:{}", DateTime::today().format("%Y-%m-%d"));
+        assert!(fs::read_to_string(&note).unwrap().contains(&due));
+        // the cursor moves only with the dates written before it
+        for (x, saved_x) in [(8, 8), (14, 22)] {
+            editor.text.set_cursor(0, 0);
+            for c in "- [ ] a @today b".chars() {
+                editor.text.input(c);
+            }
+            editor.text.enter();
+            editor.text.set_cursor(x, 0);
+            editor.save().unwrap();
+            assert_eq!(editor.text.get_cursor_pos().x, saved_x);
+        }
+
+        let args = vec![String::from("--to"), String::from("inbox")];
+        assert_eq!(parse_args(&args).unwrap_err().exit_code(), 2);
+        let args = vec![String::from("add"), String::from("--to"), String::from("x")];
+        assert_eq!(
+            parse_args(&args).unwrap().0,
+            Cli::Add(String::new(), Some(String::from("x")))
+        );
+
+        // piped text is not a scratch buffer which opened notes replace
+        assert!(!EditorBuffer::new("piped").is_scratch());
+        assert!(EditorBuffer::new("").is_scratch());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn full_text_index() {
+        assert_eq!(
+            tokenize("Rust's 会議室"),
+            vec!["rust", "s", "会議", "議室", "室"]
+        );
+        assert_eq!(
+            tokenize_query("会 rust"),
+            vec![(String::from("会"), true), (String::from("rust"), false)]
+        );
+        assert!(parse_query("a (b").is_err());
+        assert!(parse_query("\"open").is_err());
+        assert!(parse_query("color:\"red\"").is_err());
+        assert_eq!(
+            parse_query("a OR b -c").unwrap(),
+            Query::Or(vec![
+                Query::Phrase {
+                    field: Field::Body,
+                    tokens: vec![(String::from("a"), false)],
+                    text: String::from("a"),
+                },
+                Query::And(vec![
+                    Query::Phrase {
+                        field: Field::Body,
+                        tokens: vec![(String::from("b"), false)],
+                        text: String::from("b"),
+                    },
+                    Query::Not(Box::new(Query::Phrase {
+                        field: Field::Body,
+                        tokens: vec![(String::from("c"), false)],
+                        text: String::from("c"),
+                    })),
+                ]),
+            ])
+        );
+
+        let dir = temp_dir("index");
+        fs::create_dir_all(dir.join("work")).unwrap();
+        fs::write(
+            dir.join("rust.md"),
+            "# Rust Notes\n\nownership and borrowing\n#lang/rust\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("work/meeting.md"),
+            "# 会議\n\n明日の会議室を予約する\nİİ borrow a room\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("misc.md"),
+            "# Misc\n\nborrowing is not ownership, ask a partner\n#lang\n",
+        )
+        .unwrap();
+        let extensions = vec![String::from("md")];
+        let mut vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let mut index = Index::open(&dir).unwrap();
+        index.sync(&vault).unwrap();
+        let paths = |index: &Index, query: &str| {
+            let mut paths = index
+                .search(query, 100)
+                .unwrap()
+                .into_iter()
+                .map(|h| h.path.to_string_lossy().to_string())
+                .collect::<Vec<String>>();
+            paths.sort();
+            paths
+        };
+        assert_eq!(
+            paths(&index, "\"ownership and borrowing\""),
+            vec!["rust.md"]
+        );
+        assert_eq!(
+            paths(&index, "borrow*"),
+            vec!["misc.md", "rust.md", "work/meeting.md"]
+        );
+        assert_eq!(paths(&index, "borrow"), vec!["work/meeting.md"]);
+        assert_eq!(paths(&index, "ownership -title:rust"), vec!["misc.md"]);
+        assert_eq!(
+            paths(&index, "会議室 OR title:misc"),
+            vec!["misc.md", "work/meeting.md"]
+        );
+        assert_eq!(paths(&index, "室"), vec!["work/meeting.md"]);
+        assert_eq!(paths(&index, "tag:lang"), vec!["misc.md", "rust.md"]);
+        assert_eq!(paths(&index, "tag:lang/rust"), vec!["rust.md"]);
+        assert_eq!(paths(&index, "path:work"), vec!["work/meeting.md"]);
+        // a prefix of the body doesn't match the terms of the other fields
+        assert_eq!(paths(&index, "pa*"), vec!["misc.md"]);
+        assert_eq!(paths(&index, "ti*"), Vec::<String>::new());
+        assert_eq!(paths(&index, "NOT borrowing"), vec!["work/meeting.md"]);
+
+        let hit = &index.search("予約", 10).unwrap()[0];
+        assert_eq!((hit.line, hit.column), (2, 7));
+        // "İ" is two chars in lowercase
+        let hit = &index.search("room", 10).unwrap()[0];
+        assert_eq!((hit.line, hit.column), (3, 12));
+
+        // the index is stored, and a saved note is indexed again
+        fs::write(dir.join("misc.md"), "# Misc\n\nnothing here\n").unwrap();
+        vault.update(&dir.join("misc.md")).unwrap();
+        index
+            .update(vault.get(Path::new("misc.md")).unwrap())
+            .unwrap();
+        assert_eq!(paths(&index, "ownership"), vec!["rust.md"]);
+        fs::remove_file(dir.join("rust.md")).unwrap();
+        vault.refresh().unwrap();
+        index.sync(&vault).unwrap();
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(paths(&index, "ownership OR nothing"), vec!["misc.md"]);
+        // tabs and line breaks in paths don't break the stored records
+        for name in ["a\tb.md", "c\nd.md", "e\\f.md"] {
+            fs::write(dir.join(name), "# Odd\n\nkumquat\n").unwrap();
+        }
+        vault.refresh().unwrap();
+        let mut index = Index::open(&dir).unwrap();
+        index.sync(&vault).unwrap();
+        fs::remove_file(dir.join("c\nd.md")).unwrap();
+        vault.refresh().unwrap();
+        index.sync(&vault).unwrap();
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(paths(&index, "kumquat"), vec!["a\tb.md", "e\\f.md"]);
+        assert_eq!(paths(&index, "ownership OR nothing"), vec!["misc.md"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn front_matter_metadata() {
+        let lines = [
+            "---",
+            "title: \"Weekly: Review\"",
+            "tags: [work, '#plan']",
+            "aliases:",
+            "  - review",
+            "  - WR",
+            "created: 2024-01-02",
+            "status: draft # comment",
+            "---",
+            "# Heading",
+        ];
+        let front = front_matter::parse(&lines).unwrap();
+        assert_eq!(
+            front,
+            FrontMatter {
+                title: Some(String::from("Weekly: Review")),
+                tags: vec![String::from("work"), String::from("plan")],
+                aliases: vec![String::from("review"), String::from("WR")],
+                created: Some(String::from("2024-01-02")),
+                updated: None,
+                other: vec![(String::from("status"), vec![String::from("draft")])],
+                end: 8,
+            }
+        );
+        assert!(front_matter::parse(&["# title", "---"]).is_none());
+        assert!(front_matter::parse(&["---", "title: unclosed"]).is_none());
+        let set = front_matter::set_field(&lines, "updated", "2024-02-03").unwrap();
+        assert_eq!(set[8], "updated: 2024-02-03");
+        let set = front_matter::set_field(&set, "updated", "2024-02-04").unwrap();
+        assert_eq!((set.len(), set[8].as_str()), (10, "updated: 2024-02-04"));
+
+        // the vault reads titles, tags and aliases from the front matter
+        let dir = temp_dir("front");
+        fs::write(dir.join("wr.md"), lines.join("\n")).unwrap();
+        fs::write(dir.join("plain.md"), "---\ntags: a b\n---\n# Plain\n").unwrap();
+        let extensions = vec![String::from("md")];
+        let vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let vault = Vault::open(&vault.root, &extensions, NameStyle::Slug).unwrap();
+        let note = vault.get(Path::new("wr.md")).unwrap();
+        assert_eq!(note.title, "Weekly: Review");
+        assert_eq!(note.tags, vec!["work", "plan"]);
+        assert_eq!(vault.find("wr")[0].path, PathBuf::from("wr.md"));
+        assert_eq!(vault.find("review")[0].path, PathBuf::from("wr.md"));
+        assert_eq!(vault.get(Path::new("plain.md")).unwrap().title, "Plain");
+        let mut index = Index::open(&dir).unwrap();
+        index.sync(&vault).unwrap();
+        assert_eq!(index.search("tag:plan", 10).unwrap().len(), 1);
+        assert_eq!(index.search("title:wr", 10).unwrap().len(), 1);
+
+        // saving a changed note updates the field, keeping the date only style
+        let mut editor = EditorBuffer::open(&dir.join("wr.md")).unwrap();
+        editor.set_fold_front_matter(true);
+        assert_eq!(editor.text.get_cursor_pos().y, 9);
+        editor.set_view_info(40, 5, true);
+        editor.update_view(Event::Key(Key::Char('!')));
+        assert_eq!(editor.get_view()[0], "--- front matter (9 lines) ---");
+        assert_eq!(editor.get_view()[1], "!# Heading");
+        editor.update_view(Event::Key(Key::Up));
+        assert_eq!(editor.get_view()[4], "---");
+        editor.save().unwrap();
+        let saved = fs::read_to_string(dir.join("wr.md")).unwrap();
+        let today = DateTime::now().format("%Y-%m-%d");
+        assert!(saved.contains(&format!(
+            "created: 2024-01-02\nstatus: draft # comment\nupdated: {}\n---\n",
+            today
+        )));
+        assert!(!editor.is_modified());
+        editor.save().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("wr.md")).unwrap(), saved);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            extract_tags("# Title\n#todo and #project/alpha, a#b #1 `#code`\n```\n#skip\n```\n"),
+            vec!["todo", "project/alpha"]
+        );
+        assert_eq!(tag_ancestors("a/b/c"), vec!["a", "a/b", "a/b/c"]);
+
+        let dir = temp_dir("tags");
+        fs::write(
+            dir.join("a.md"),
+            "---\ntags: [project]\n---\n# A\n#project/alpha #todo\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.md"), "# B\n#project/beta\n").unwrap();
+        fs::write(dir.join("c.md"), "# C\n#todo\n").unwrap();
+        let extensions = vec![String::from("md")];
+        let vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        assert_eq!(
+            vault.get(Path::new("a.md")).unwrap().tags,
+            vec!["project", "project/alpha", "todo"]
+        );
+        assert_eq!(
+            vault.tags(),
+            vec![
+                (String::from("project"), 2),
+                (String::from("project/alpha"), 1),
+                (String::from("project/beta"), 1),
+                (String::from("todo"), 2),
+            ]
+        );
+        assert_eq!(vault.tagged("#Project").len(), 2);
+        assert_eq!(vault.tagged("project/beta")[0].title, "B");
+        assert!(vault.tagged("proj").is_empty());
+        assert_eq!(
+            parse_args(&[
+                String::from("list"),
+                String::from("--tag"),
+                String::from("todo")
+            ])
+            .unwrap()
+            .0,
+            Cli::List(Some(String::from("todo")))
+        );
+        assert!(parse_args(&[
+            String::from("cat"),
+            String::from("--tag"),
+            String::from("a")
+        ])
+        .is_err());
+
+        // the drawer lists nested tags, and notes under the expanded tag
+        let mut drawer = DrawerBuffer::new(&dir);
+        drawer.set_tags(&vault);
+        drawer.set_view_info(30, 10, true);
+        drawer.update_view(Event::Key(Key::Char('t')));
+        assert_eq!(drawer.mode, DrawerMode::Tags);
+        assert_eq!(drawer.get_view()[2], "▸ #todo (2)");
+        drawer.update_view(Event::Key(Key::Char('\n')));
+        let names = drawer
+            .entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["#project (2)", "#alpha (1)", "#beta (1)", "A", "#todo (2)"]
+        );
+        drawer.update_view(Event::Key(Key::Char('d'))); // files are not deleted here
+        drawer.update_view(Event::Key(Key::Up));
+        for _ in 0..3 {
+            drawer.update_view(Event::Key(Key::Down));
+        }
+        drawer.update_view(Event::Key(Key::Char('\n')));
+        assert_eq!(
+            drawer.take_action(),
+            Some(DrawerAction::Open(dir.join("a.md")))
+        );
+
+        // "#" completes tags in the editor
+        let mut editor = EditorBuffer::new("");
+        editor.tags = vec![String::from("todo"), String::from("project/alpha")];
+        editor.set_view_info(30, 10, true);
+        for c in "x #pr".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        assert!(editor.get_view()[1].contains("#project/alpha"));
+        editor.update_view(Event::Key(Key::Char('\t')));
+        assert_eq!(editor.content(), "x #project/alpha");
+        assert_eq!(editor.get_view().len(), 1);
+        for c in " a#t".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        assert_eq!(editor.get_view().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wiki_links() {
+        let line = "see [[Note]] and [[other#Plan B|the plan]], `[[code]]` [[#top]] [[]]"
+            .chars()
+            .collect::<Vec<char>>();
+        let links = find_links(&line, 3);
+        assert_eq!(
+            links[1],
+            Link {
+                target: String::from("other"),
+                heading: Some(String::from("Plan B")),
+                alias: Some(String::from("the plan")),
+                line: 3,
+                start: 17,
+                end: 42,
+            }
+        );
+        let targets = links.iter().map(|l| l.target.as_str()).collect::<Vec<_>>();
+        assert_eq!(targets, vec!["Note", "other", ""]);
+        assert_eq!(
+            find_heading(&["# A", "#tag", "## Plan b"], "plan B"),
+            Some(2)
+        );
+
+        // links are underlined, and found under the cursor
+        let mut editor = EditorBuffer::new("a [[Note]] b");
+        editor.set_view_info(30, 5, true);
+        editor.update_view(Event::Key(Key::Right));
+        assert!(editor.link_at_cursor().is_none());
+        editor.update_view(Event::Key(Key::Right));
+        assert_eq!(editor.link_at_cursor().unwrap().target, "Note");
+        assert!(editor.get_view()[0].starts_with("a \u{1b}[4m"));
+
+        // "[[" completes titles and closes the link
+        let mut editor = EditorBuffer::new("");
+        editor.titles = vec![String::from("Weekly Review"), String::from("Reading List")];
+        editor.set_view_info(30, 5, true);
+        for c in "x [[re".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        assert!(editor.get_view()[1].contains("[[Reading List"));
+        assert!(editor.get_view()[2].contains("[[Weekly Review"));
+        editor.update_view(Event::Key(Key::Down));
+        editor.update_view(Event::Key(Key::Char('\n')));
+        assert_eq!(editor.content(), "x [[Weekly Review]]");
+        assert_eq!(editor.text.get_cursor_pos().x, 19);
+    }
+
+    #[test]
+    fn backlinks_and_mentions() {
+        assert_eq!(
+            find_mention("about Weekly review.", "weekly review"),
+            Some(6)
+        );
+        assert_eq!(find_mention("see [[Weekly Review]]", "Weekly Review"), None);
+        assert_eq!(find_mention("weekly reviews", "Weekly Review"), None);
+
+        let dir = temp_dir("backlinks");
+        fs::write(dir.join("weekly.md"), "# Weekly Review\n").unwrap();
+        fs::write(
+            dir.join("a.md"),
+            "# A\nfirst\nsee [[weekly]] and [[Weekly Review#Plan]]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.md"),
+            "# B\n```\nWeekly Review\n```\ndo the weekly review\n",
+        )
+        .unwrap();
+        let extensions = vec![String::from("md")];
+        let vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let path = dir.join("weekly.md");
+        let links = backlinks(&vault, &path);
+        assert_eq!(links.len(), 1);
+        assert_eq!(
+            (links[0].path.as_path(), links[0].line),
+            (Path::new("a.md"), 2)
+        );
+        let mut index = Index::open(&dir).unwrap();
+        index.sync(&vault).unwrap();
+        let candidates = index
+            .search("\"weekly review\"", 10)
+            .unwrap()
+            .into_iter()
+            .map(|h| h.path)
+            .collect::<Vec<PathBuf>>();
+        let mentions = unlinked_mentions(&vault, &path, &candidates);
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(
+            (mentions[0].path.as_path(), mentions[0].line),
+            (Path::new("b.md"), 4)
+        );
+
+        let mut buffer = BacklinksBuffer::new();
+        buffer.set_references(Some(path), links, mentions);
+        buffer.set_view_info(40, 10, true);
+        buffer.update_view(Event::Key(Key::Down));
+        assert_eq!(buffer.get_view()[0], "Backlinks (1)");
+        assert_eq!(buffer.get_view()[4], "Unlinked mentions (1)");
+        buffer.update_view(Event::Key(Key::Char('\n')));
+        assert_eq!(
+            buffer.take_action(),
+            Some(BacklinksAction::Open(PathBuf::from("b.md"), 4))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rename_rewrites_links() {
+        let line = "[a](x.md) `[b](y.md)` ![c](<my img.png>)"
+            .chars()
+            .collect::<Vec<_>>();
+        assert_eq!(find_url_ranges(&line), vec![(4, 8), (28, 38)]);
+
+        let dir = temp_dir("rename");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join("img")).unwrap();
+        fs::write(dir.join("img/a.png"), "").unwrap();
+        fs::write(dir.join("plan.md"), "# Plan\nsee ![img](img/a.png)\n").unwrap();
+        let a =
+            "[[plan]] [[plan#Goals|goals]] [[Plan]] [link](plan.md#goals)\n```\n[[plan]]\n```\n";
+        fs::write(dir.join("a.md"), a).unwrap();
+        fs::write(dir.join("sub/b.md"), "[p](../plan.md) [[plan.md]]\r\n").unwrap();
+        let extensions = vec![String::from("md")];
+        let mut vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+
+        let plan = vault
+            .plan_rename(Path::new("plan.md"), Path::new("done/goals"))
+            .unwrap();
+        assert_eq!(plan.to, PathBuf::from("done/goals.md"));
+        assert_eq!(plan.change_count(), 3);
+        assert_eq!(plan.preview()[1], "a.md:1");
+
+        // a failed move puts the rewritten notes back
+        fs::write(dir.join("done"), "").unwrap();
+        assert!(vault.apply_rename(&plan).is_err());
+        assert_eq!(fs::read_to_string(dir.join("a.md")).unwrap(), a);
+        fs::remove_file(dir.join("done")).unwrap();
+
+        vault.apply_rename(&plan).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("a.md")).unwrap(),
+            a.replacen("[[plan]]", "[[goals]]", 1)
+                .replace("[[plan#", "[[goals#")
+                .replace("(plan.md#", "(done/goals.md#")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("sub/b.md")).unwrap(),
+            "[p](../done/goals.md) [[done/goals.md]]\r\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("done/goals.md")).unwrap(),
+            "# Plan\nsee ![img](../img/a.png)\n"
+        );
+        assert_eq!(vault.linking(Path::new("done/goals.md")).len(), 2);
+
+        // a note changed after the preview is not overwritten
+        let plan = vault
+            .plan_rename(Path::new("done"), Path::new("archive"))
+            .unwrap();
+        assert_eq!(plan.moves[0].1, PathBuf::from("archive/goals.md"));
+        fs::write(dir.join("a.md"), "[[goals]] changed\n").unwrap();
+        assert!(vault.apply_rename(&plan).is_err());
+        assert!(dir.join("done/goals.md").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn daily_notes() {
+        let date = DateTime::date(2024, 2, 28).unwrap();
+        assert_eq!(date.weekday, 3);
+        assert_eq!(date.add_days(1).format("%Y-%m-%d %a"), "2024-02-29 Thu");
+        assert_eq!(date.add_days(2).format("%Y-%m-%d"), "2024-03-01");
+        assert_eq!(date.add_days(-59).format("%Y-%m-%d %a"), "2023-12-31 Sun");
+        assert!(DateTime::date(2023, 2, 29).is_none());
+        assert_eq!(date.week_start(1).format("%m-%d"), "02-26");
+        assert_eq!(date.week_start(0).format("%m-%d"), "02-25");
+        let new_year = DateTime::date(2024, 1, 1).unwrap();
+        assert_eq!((new_year.week_number(1), new_year.week_number(0)), (1, 0));
+        assert_eq!(parse_day("-1", &date), Some(date.add_days(-1)));
+        assert_eq!(parse_day("2024-01-01", &date), Some(new_year));
+        assert_eq!(parse_day("someday", &date), None);
+
+        let dir = temp_dir("daily");
+        let mut config = Config::default();
+        let content =
+            "daily_pattern = journal/%Y/%m-%d %a\ndaily_template = day.tpl\nweek_start = sun\n";
+        config.parse(content).unwrap();
+        assert!(config.parse("week_start = someday").is_err());
+        let daily = config.daily;
+        assert_eq!(daily.name(&date), "journal/2024/02-28 Wed");
+        assert_eq!(
+            daily.date_of(Path::new("journal/2024/02-28 Wed.md")),
+            Some(date)
+        );
+        assert_eq!(daily.date_of(Path::new("journal/2024/02-30 Fri.md")), None);
+        assert_eq!(daily.date_of(Path::new("journal/2024/notes.md")), None);
+
+        fs::write(
+            dir.join("day.tpl"),
+            "# {{date:%d.%m.%Y}}\nweek {{week}} from {{week_start}}\n[[{{yesterday}}]] {{other}}\n",
+        )
+        .unwrap();
+        let extensions = vec![String::from("md")];
+        let mut vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let path = vault.create_daily(&daily, &date).unwrap();
+        assert_eq!(path, dir.join("journal/2024/02-28 Wed.md"));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# 28.02.2024\nweek 08 from 2024-02-25\n[[journal/2024/02-27 Tue]] {{other}}\n"
+        );
+        fs::write(&path, "kept\n").unwrap();
+        vault.create_daily(&daily, &date).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kept\n");
+        let default = Daily::default();
+        assert_eq!(default.name(&date), "daily/2024-02-28");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn templates() {
+        let text =
+            "# {{title}}\n{{date}} {{time:%H}}h {{client}}\n- {{cursor}}\n{{ client }} {{title}}";
+        assert_eq!(template::variables(text), vec!["title", "client"]);
+        let now = DateTime::date(2024, 3, 5).unwrap();
+        let values = vec![
+            (String::from("title"), String::from("Kickoff")),
+            (String::from("client"), String::from("ACME")),
+        ];
+        let expanded = template::expand(text, &now, &values);
+        assert_eq!(
+            expanded.text,
+            "# Kickoff\n2024-03-05 00h ACME\n- \nACME Kickoff"
+        );
+        assert_eq!(expanded.cursor, Some((2, 2)));
+        let unknown = template::expand("{{x}} {{date", &now, &[]);
+        assert_eq!(
+            (unknown.text.as_str(), unknown.cursor),
+            ("{{x}} {{date", None)
+        );
+
+        let dir = temp_dir("templates");
+        let mut config = Config::default();
+        config.parse("templates_dir = tpl").unwrap();
+        let templates_dir = dir.join(&config.templates_dir);
+        assert!(list_templates(&templates_dir).is_empty());
+        fs::create_dir_all(&templates_dir).unwrap();
+        fs::write(templates_dir.join("meeting.md"), text).unwrap();
+        fs::write(templates_dir.join(".hidden"), "").unwrap();
+        fs::write(templates_dir.join("book.md"), "# {{title}}").unwrap();
+        assert_eq!(
+            list_templates(&templates_dir),
+            vec![
+                templates_dir.join("book.md"),
+                templates_dir.join("meeting.md")
+            ]
+        );
+        let extensions = vec![String::from("md")];
+        let mut vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let path = vault.create_note_with("Kickoff", &expanded.text).unwrap();
+        assert_eq!(path, dir.join("kickoff.md"));
+        assert_eq!(vault.get(&path).unwrap().title, "Kickoff");
+        // a template without a heading is named from the title too
+        let expanded = template::expand("{{date}}\n- ", &now, &[]);
+        let path = vault.create_note_with("Plain Day", &expanded.text).unwrap();
+        assert_eq!(path, dir.join("plain-day.md"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snippets() {
+        let (lines, stops) = parse_snippet("[${1:text}](${2:url}) $1 \\$3$0");
+        assert_eq!(
+            lines,
+            vec!["[text](url) text $3".chars().collect::<Vec<char>>()]
+        );
+        assert_eq!(
+            stops[2],
+            TabStop {
+                number: 1,
+                y: 0,
+                start: 12,
+                end: 16,
+            }
+        );
+        assert_eq!(stops[3].start, 19);
+
+        let mut config = Config::default();
+        config
+            .parse("snippet.md.meet = ## ${1:who}\\nwith $1: $2\nsnippet.*.sig = -- $0")
+            .unwrap();
+        let snippets = config.snippets_for(Some(Path::new("a.md")));
+        assert!(snippets.iter().any(|s| s.trigger == "todo"));
+        assert!(snippets.iter().any(|s| s.trigger == "sig"));
+        assert!(!config
+            .snippets_for(Some(Path::new("a.txt")))
+            .iter()
+            .any(|s| s.trigger == "meet"));
+
+        // the stops are selected in turn and the mirror follows the first one
+        let mut editor = EditorBuffer::new("");
+        editor.snippets = snippets;
+        editor.set_view_info(40, 10, true);
+        for c in "  meet\t".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        assert_eq!(editor.content(), "  ## who\n  with who: ");
+        for c in "Ann".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        assert_eq!(editor.content(), "  ## Ann\n  with Ann: ");
+        editor.update_view(Event::Key(Key::Char('\t')));
+        editor.update_view(Event::Key(Key::Char('x')));
+        editor.update_view(Event::Key(Key::BackTab));
+        editor.update_view(Event::Key(Key::Backspace));
+        assert_eq!(editor.content(), "  ## \n  with : x");
+        editor.update_view(Event::Key(Key::Char('\t')));
+        editor.update_view(Event::Key(Key::Char('\t')));
+        editor.update_view(Event::Key(Key::Char('!')));
+        assert_eq!(editor.content(), "  ## \n  with : x!");
+        // the snippet is over, so tab indents
+        editor.update_view(Event::Key(Key::Char('\t')));
+        assert_eq!(editor.content(), "  ## \n  with : x!    ");
+
+        // a default snippet
+        let mut editor = EditorBuffer::new("");
+        editor.snippets = config.snippets_for(None);
+        editor.set_view_info(40, 10, true);
+        for c in "link\t".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        editor.update_view(Event::Key(Key::Char('a')));
+        editor.update_view(Event::Key(Key::Char('\t')));
+        editor.update_view(Event::Key(Key::Char('b')));
+        editor.update_view(Event::Key(Key::Char('\t')));
+        editor.update_view(Event::Key(Key::Char('c')));
+        assert_eq!(editor.content(), "[a](b)c");
+    }
+
+    #[test]
+    fn list_editing() {
+        let item = parse_item(&"  3) [x] done".chars().collect::<Vec<char>>()).unwrap();
+        assert_eq!(
+            (
+                item.indent,
+                item.bullet,
+                item.number,
+                item.checkbox,
+                item.content
+            ),
+            (2, ')', Some(3), Some(true), 9)
+        );
+        assert!(parse_item(&"**bold**".chars().collect::<Vec<char>>()).is_none());
+        assert!(parse_item(&"2024. year".chars().collect::<Vec<char>>()).is_some());
+        let mut lines = ["3. a", "    - x", "7. b", "", "1. c"]
+            .iter()
+            .map(|l| l.chars().collect::<Vec<char>>())
+            .collect::<Vec<Vec<char>>>();
+        renumber(&mut lines, 2);
+        assert_eq!(lines[2], "4. b".chars().collect::<Vec<char>>());
+        assert_eq!(lines[4], "1. c".chars().collect::<Vec<char>>());
+
+        let type_keys = |editor: &mut EditorBuffer, keys: &str| {
+            for c in keys.chars() {
+                editor.update_view(Event::Key(Key::Char(c)));
+            }
+        };
+        let mut editor = EditorBuffer::new("1. one\n2. two");
+        editor.set_view_info(40, 10, true);
+        editor.text.set_cursor(6, 0);
+        type_keys(&mut editor, "\nmid");
+        assert_eq!(editor.content(), "1. one\n2. mid\n3. two");
+        // tab nests the item and its children, renumbering both lists
+        editor.update_view(Event::Key(Key::Char('\t')));
+        assert_eq!(editor.content(), "1. one\n    1. mid\n2. two");
+        assert_eq!(editor.text.get_cursor_pos().x, 10);
+        editor.text.set_cursor(3, 0);
+        editor.update_view(Event::Key(Key::Char('\t')));
+        assert_eq!(editor.content(), "    1. one\n        1. mid\n2. two");
+        editor.update_view(Event::Key(Key::BackTab));
+        editor.text.set_cursor(10, 1);
+        editor.update_view(Event::Key(Key::BackTab));
+        assert_eq!(editor.content(), "1. one\n2. mid\n3. two");
+        editor.text.undo();
+        assert_eq!(editor.content(), "1. one\n    1. mid\n2. two");
+
+        // checkboxes continue unchecked and an empty item ends the list
+        let mut editor = EditorBuffer::new("- [x] task");
+        editor.set_view_info(40, 10, true);
+        editor.text.set_cursor(10, 0);
+        type_keys(&mut editor, "\nnext\n\n");
+        assert_eq!(editor.content(), "- [x] task\n- [ ] next\n");
+        type_keys(&mut editor, "text\t");
+        assert_eq!(editor.content(), "- [x] task\n- [ ] next\ntext    ");
+    }
+
+    #[test]
+    fn tasks() {
+        let mut line = "  * item".chars().collect::<Vec<char>>();
+        set_checkbox(&mut line, false);
+        assert_eq!(line.iter().collect::<String>(), "  * [ ] item");
+        set_checkbox(&mut line, true);
+        assert_eq!(line.iter().collect::<String>(), "  * [x] item");
+        let mut line = "text".chars().collect::<Vec<char>>();
+        set_checkbox(&mut line, false);
+        assert_eq!(line.iter().collect::<String>(), "- [ ] text");
+        assert_eq!(
+            find_tasks(
+                "---\ntags: [a]\n---\n- [ ] one\n```\n- [ ] code\n```\n1. [X] two\n-  [ ] no"
+            ),
+            vec![
+                (3, false, String::from("one")),
+                (7, true, String::from("two"))
+            ]
+        );
+
+        // toggling the selected lines checks all of them unless all are done.
+        // a line without a checkbox becomes an open task.
+        let mut editor = EditorBuffer::new("- [x] a\nb\n\n- [ ] c");
+        editor.set_view_info(40, 10, true);
+        editor
+            .text
+            .select_range(Cursor { x: 0, y: 0 }, Cursor { x: 1, y: 3 });
+        editor.update_view(Event::Key(Key::Ctrl('d')));
+        assert_eq!(editor.content(), "- [x] a\n- [ ] b\n\n- [x] c");
+        editor.update_view(Event::Key(Key::Ctrl('d')));
+        assert_eq!(editor.content(), "- [x] a\n- [x] b\n\n- [x] c");
+        editor.update_view(Event::Key(Key::Ctrl('d')));
+        assert_eq!(editor.content(), "- [ ] a\n- [ ] b\n\n- [ ] c");
+        editor.text.set_cursor(7, 3);
+        editor.update_view(Event::Key(Key::Ctrl('d')));
+        assert_eq!(editor.content(), "- [ ] a\n- [ ] b\n\n- [x] c");
+        assert_eq!(editor.text.get_cursor_pos().x, 7);
+
+        let dir = temp_dir("tasks");
+        fs::write(
+            dir.join("work.md"),
+            "# Work\n#job\n- [ ] report\n- [x] mail\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("home.md"),
+            "# Home\n- [ ] shop\n- [ ] fix #job/side\n",
+        )
+        .unwrap();
+        let extensions = vec![String::from("md")];
+        let vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let tasks = open_tasks(&vault, None);
+        assert_eq!(
+            tasks
+                .iter()
+                .map(|t| (t.title.as_str(), t.line, t.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Home", 1, "shop"),
+                ("Home", 2, "fix #job/side"),
+                ("Work", 2, "report")
+            ]
+        );
+        let tagged = open_tasks(&vault, Some("#job"));
+        assert_eq!(tagged.len(), 2);
+
+        let mut pane = TasksBuffer::new();
+        pane.set_tasks(Some(String::from("job")), tagged);
+        pane.set_view_info(30, 10, true);
+        pane.update_view(Event::Key(Key::Down));
+        assert_eq!(pane.get_view()[0], "Tasks #job (2)");
+        assert_eq!(pane.get_view()[3], " Work");
+        pane.update_view(Event::Key(Key::Char('\n')));
+        assert_eq!(
+            pane.take_action(),
+            Some(TasksAction::Open(PathBuf::from("work.md"), 2))
+        );
+        assert_eq!(
+            parse_args(&[
+                String::from("tasks"),
+                String::from("--tag"),
+                String::from("job")
+            ])
+            .unwrap()
+            .0,
+            Cli::Tasks(Some(String::from("job")))
+        );
+        let error = parse_args(&[String::from("tasks"), String::from("extra")]).unwrap_err();
+        assert!(error.message().starts_with("tasks: wrong arguments\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn agenda_view() {
+        let today = DateTime::date(2026, 10, 19).unwrap();
+        assert_eq!(
+            parse_meta("call due:2026-11-01 !high"),
+            (DateTime::date(2026, 11, 1), Priority::High)
+        );
+        assert_eq!(parse_meta("due:2026-13-01"), (None, Priority::Normal));
+        // @today and @tomorrow are fixed to dates, only in tasks
+        assert_eq!(
+            fix_due_dates("@today\r\n- [ ] a @today\r\n- [ ] b @tomorrow !low", &today),
+            "@today\r\n- [ ] a due:2026-10-19\r\n- [ ] b due:2026-10-20 !low"
+        );
+
+        let dir = temp_dir("agenda");
+        fs::write(
+            dir.join("a.md"),
+            "# A\r\n- [ ] late due:2026-10-01\r\n- [ ] now due:2026-10-19 !low\r\n- [ ] urgent due:2026-10-19 !high\r\n- [ ] someday\r\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.md"), "# B\n- [ ] next due:2026-10-25\n").unwrap();
+        let extensions = vec![String::from("md")];
+        let vault = Vault::open(&dir, &extensions, NameStyle::Slug).unwrap();
+        let plan = agenda(open_tasks(&vault, None), &today);
+        let texts = |tasks: &[crate::note::task::Task]| {
+            tasks
+                .iter()
+                .map(|t| t.text.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(texts(&plan.overdue), vec!["late due:2026-10-01"]);
+        assert_eq!(
+            texts(&plan.today),
+            vec!["urgent due:2026-10-19 !high", "now due:2026-10-19 !low"]
+        );
+        assert_eq!(texts(&plan.upcoming), vec!["next due:2026-10-25"]);
+
+        let mut pane = AgendaBuffer::new();
+        pane.set_agenda(plan.clone());
+        pane.set_view_info(60, 10, true);
+        pane.update_view(Event::Key(Key::Char('p')));
+        assert_eq!(pane.get_view()[0], "Overdue (1)");
+        let late = plan.overdue[0].clone();
+        assert_eq!(
+            pane.take_action(),
+            Some(AgendaAction::Postpone(late.clone(), 1))
+        );
+
+        // an overdue task is postponed from today, keeping the line ending
+        let date = postpone_task(&dir, &late, 1, &today).unwrap();
+        assert_eq!(date, DateTime::date(2026, 10, 20).unwrap());
+        let urgent = plan.today[0].clone();
+        postpone_task(&dir, &urgent, 7, &today).unwrap();
+        complete_task(&dir, &plan.today[1]).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("a.md")).unwrap(),
+            "# A\r\n- [ ] late due:2026-10-20\r\n- [x] now due:2026-10-19 !low\r\n- [ ] urgent due:2026-10-26 !high\r\n- [ ] someday\r\n"
+        );
+        // the line is not the task anymore
+        assert!(complete_task(&dir, &urgent).is_err());
+        let someday = open_tasks(&vault, None)
+            .into_iter()
+            .find(|t| t.text == "someday")
+            .unwrap();
+        postpone_task(&dir, &someday, 2, &today).unwrap();
+        assert!(fs::read_to_string(dir.join("a.md"))
+            .unwrap()
+            .ends_with("- [ ] someday due:2026-10-21\r\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn outline() {
+        let to_lines = |text: &str| {
+            text.lines()
+                .map(|l| l.chars().collect::<Vec<char>>())
+                .collect::<Vec<Vec<char>>>()
+        };
+        let to_text = |lines: &[Vec<char>]| {
+            lines
+                .iter()
+                .map(|l| l.iter().collect::<String>())
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        let mut lines =
+            to_lines("---\ntitle: x\n---\n# A\na\n## A1\n```\n# code\n```\n# B ##\nb\n#nope");
+        let found = headings(&lines);
+        assert_eq!(
+            found
+                .iter()
+                .map(|h| (h.level, h.title.as_str(), h.line))
+                .collect::<Vec<_>>(),
+            vec![(1, "A", 3), (2, "A1", 5), (1, "B", 9)]
+        );
+        // a section moves with its children, only among its siblings
+        assert_eq!(move_section(&mut lines, 5, true), None);
+        assert_eq!(move_section(&mut lines, 9, true), Some(3));
+        assert_eq!(
+            to_text(&lines),
+            "---\ntitle: x\n---\n# B ##\nb\n#nope\n# A\na\n## A1\n```\n# code\n```"
+        );
+        assert_eq!(move_section(&mut lines, 3, false), Some(9));
+        assert!(shift_section(&mut lines, 3, 1));
+        assert_eq!(to_text(&lines[3..6]), "## A\na\n### A1");
+        assert!(!shift_section(&mut lines, 9, -1));
+
+        let mut editor = EditorBuffer::new("# A\n## A1\ntext\n## A2\n");
+        editor.set_view_info(40, 10, false);
+        editor.text.set_cursor(2, 2);
+        let mut pane = OutlineBuffer::new();
+        pane.set_outline(headings(&editor.text.text), 2, false);
+        assert_eq!(pane.selected, 1);
+        pane.set_view_info(30, 10, true);
+        pane.update_view(Event::Key(Key::Char('J')));
+        assert_eq!(pane.take_action(), Some(OutlineAction::Move(1, false)));
+        assert!(editor.move_section(1, false));
+        assert_eq!(editor.content(), "# A\n## A2\n## A1\ntext");
+        assert_eq!(editor.text.get_cursor_pos().y, 2);
+        pane.set_outline(headings(&editor.text.text), 2, true);
+        pane.update_view(Event::Key(Key::Char('<')));
+        assert!(pane.get_view()[3].contains("   A1"));
+        assert_eq!(pane.take_action(), Some(OutlineAction::Shift(2, -1)));
+        assert!(editor.shift_section(2, -1));
+        editor.text.undo();
+        assert_eq!(editor.content(), "# A\n## A2\n## A1\ntext");
+    }
+
+    #[test]
+    fn folding() {
+        let lines = "# A\n- a\n  - b\n```\n# c\n```\n## A1\ntext\n# B"
+            .lines()
+            .map(|l| l.chars().collect::<Vec<char>>())
+            .collect::<Vec<Vec<char>>>();
+        let blocks = Blocks::new(&lines);
+        assert_eq!(blocks.end(&lines, 0), Some(7));
+        assert_eq!(blocks.end(&lines, 1), Some(2));
+        assert_eq!(blocks.end(&lines, 2), None);
+        assert_eq!(blocks.end(&lines, 3), Some(5));
+        assert_eq!(blocks.end(&lines, 4), None);
+        assert_eq!(blocks.enclosing(&lines, 4), Some(3));
+        assert_eq!(blocks.enclosing(&lines, 7), Some(6));
+
+        let mut editor = EditorBuffer::new("# A\n- a\n  - b\ntext\n# B\nb");
+        editor.set_view_info(40, 10, true);
+        editor.text.set_cursor(1, 2);
+        editor.update_view(Event::Key(Key::Alt('z')));
+        assert_eq!(editor.text.get_cursor_pos().y, 1);
+        assert_eq!(editor.get_view()[1], "- a ... (1 lines)");
+        assert_eq!(editor.get_view()[2], "text");
+        // the cursor steps over the fold
+        editor.update_view(Event::Key(Key::Down));
+        assert_eq!(editor.text.get_cursor_pos().y, 3);
+        editor.update_view(Event::Key(Key::Up));
+        assert_eq!(editor.text.get_cursor_pos().y, 1);
+        // the fold moves with the lines above it
+        editor.text.set_cursor(3, 0);
+        editor.update_view(Event::Key(Key::Char('\n')));
+        assert_eq!(editor.get_view()[2], "- a ... (1 lines)");
+        editor.update_view(Event::Key(Key::Up));
+        editor.update_view(Event::Key(Key::Alt('z')));
+        assert_eq!(editor.get_view()[0], "# A ... (4 lines)");
+        assert_eq!(editor.get_view()[1], "# B");
+        // a jump into the section opens it
+        editor.text.set_cursor(0, 4);
+        editor.update_view(Event::Unsupported(vec![]));
+        assert_eq!(editor.get_view()[0], "# A");
+        assert_eq!(editor.get_view()[2], "- a ... (1 lines)");
+        editor.update_view(Event::Key(Key::Alt('Z')));
+        assert_eq!(editor.get_view()[3], "  - b");
+    }
+    #[test]
+    fn multi_cursor() {
+        let content = |text: &TextBuffer| {
+            text.get_text()
+                .iter()
+                .map(|l| l.iter().collect::<String>())
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        let mut text = TextBuffer::new("abc\nde\nfghi");
+        text.set_cursor(1, 0);
+        text.add_cursor(true);
+        text.add_cursor(true);
+        assert_eq!(text.cursor_count(), 3);
+        text.input('X');
+        assert_eq!(content(&text), "aXbc\ndXe\nfXghi");
+        text.enter();
+        assert_eq!(content(&text), "aX\nbc\ndX\ne\nfX\nghi");
+        text.back();
+        text.delete();
+        assert_eq!(content(&text), "aXc\ndX\nfXhi");
+        assert_eq!(text.get_cursor_pos().y, 2);
+        assert_eq!(
+            text.extra_cursors()
+                .iter()
+                .map(|c| (c.x, c.y))
+                .collect::<Vec<_>>(),
+            vec![(2, 0), (2, 1)]
+        );
+        // cursors moving onto the same place become one
+        for _ in 0..3 {
+            text.up(false);
+        }
+        assert_eq!(text.cursor_count(), 1);
+        // the edits at all cursors are one undo step
+        text.undo();
+        assert_eq!(content(&text), "aXbc\ndXe\nfXghi");
+        text.undo();
+        assert_eq!(content(&text), "aX\nbc\ndX\ne\nfX\nghi");
+        assert_eq!(text.cursor_count(), 1);
+        // each cursor keeps its column when it passes a short line
+        let mut text = TextBuffer::new("abcdef\nab\nabcdef\nab\nabcdef");
+        text.set_cursor(5, 2);
+        text.add_cursor(true);
+        text.add_cursor(true);
+        text.up(false);
+        text.up(false);
+        let extra = |text: &TextBuffer| {
+            text.extra_cursors()
+                .iter()
+                .map(|c| (c.x, c.y))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(extra(&text), vec![(5, 0), (2, 1)]);
+        text.down(false);
+        text.down(false);
+        assert_eq!(text.get_cursor_pos().x, 5);
+        assert_eq!(extra(&text), vec![(5, 2), (2, 3)]);
+
+        let mut editor = EditorBuffer::new("foo bar foo\nfoo");
+        editor.set_view_info(40, 5, true);
+        editor.text.set_cursor(1, 0);
+        editor.update_view(Event::Key(Key::Alt('N')));
+        editor.update_view(Event::Key(Key::Alt('N')));
+        editor.update_view(Event::Key(Key::Alt('N')));
+        assert_eq!(editor.text.cursor_count(), 3);
+        // the next one wraps around to the first selection, which has a cursor
+        editor.update_view(Event::Key(Key::Alt('N')));
+        assert_eq!(editor.text.cursor_count(), 3);
+        for c in "qux".chars() {
+            editor.update_view(Event::Key(Key::Char(c)));
+        }
+        assert_eq!(editor.content(), "qux bar qux\nqux");
+        assert_eq!(
+            editor.get_view()[0],
+            format!(
+                "qux{} {}bar qux{} {}",
+                termion::style::Invert,
+                termion::style::NoInvert,
+                termion::style::Invert,
+                termion::style::NoInvert
+            )
+        );
+        editor.update_view(Event::Key(Key::Esc));
+        assert_eq!(editor.text.cursor_count(), 1);
+        assert_eq!(editor.get_view()[0], "qux bar qux");
+        // the checkboxes are not toggled with several cursors
+        editor.update_view(Event::Key(Key::Alt('K')));
+        editor.update_view(Event::Key(Key::Ctrl('d')));
+        assert_eq!(editor.text.cursor_count(), 2);
+        assert_eq!(editor.content(), "qux bar qux\nqux");
+    }
 }
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::front_matter;
+use super::link::{find_mention, parse_links};
+use super::vault::{link_key, Vault};
+
+// バックリンク。あるノートを [[ ]] で参照している他のノートの行と、
+// リンクにはなっていないが本文でノートのタイトル(または別名)に触れている行。
+// リンクしているノートは Vault のキャッシュにあるリンク先から探し、
+// タイトルに触れているノートの候補は全文検索の結果を使う。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub path: PathBuf, // relative to the root
+    pub title: String,
+    pub line: usize,  // from 0
+    pub text: String, // the line
+}
+
+// lines of other notes which link to the note
+pub fn backlinks(vault: &Vault, path: &Path) -> Vec<Reference> {
+    let Some(note) = vault.get(path) else {
+        return vec![];
+    };
+    let names = vault.link_names(note);
+    let mut refs = vec![];
+    for other in vault.linking(path) {
+        let Ok(content) = fs::read_to_string(vault.root.join(&other.path)) else {
+            continue;
+        };
+        let lines = content.lines().collect::<Vec<&str>>();
+        let mut found = parse_links(&content)
+            .into_iter()
+            .filter(|l| names.contains(&link_key(&l.target)))
+            .map(|l| l.line)
+            .collect::<Vec<usize>>();
+        found.dedup();
+        refs.extend(found.into_iter().map(|y| Reference {
+            path: other.path.clone(),
+            title: other.title.clone(),
+            line: y,
+            text: lines[y].trim().to_string(),
+        }));
+    }
+    refs.sort_by(|a, b| a.title.cmp(&b.title).then(a.line.cmp(&b.line)));
+    refs
+}
+
+// lines of the candidate notes which write the title or an alias of the note
+// as plain text. front matter and code blocks are skipped.
+pub fn unlinked_mentions(vault: &Vault, path: &Path, candidates: &[PathBuf]) -> Vec<Reference> {
+    let Some(note) = vault.get(path) else {
+        return vec![];
+    };
+    let names = mention_names(&note.title, &note.aliases);
+    let mut refs = vec![];
+    for other in candidates.iter().filter_map(|p| vault.get(p)) {
+        if other.path == note.path {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(vault.root.join(&other.path)) else {
+            continue;
+        };
+        let lines = content.lines().collect::<Vec<&str>>();
+        let skip = front_matter::parse(&lines).map_or(0, |f| f.line_count());
+        let mut fence = false;
+        for (y, line) in lines.iter().enumerate().skip(skip) {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                fence = !fence;
+                continue;
+            }
+            if !fence && names.iter().any(|n| find_mention(line, n).is_some()) {
+                refs.push(Reference {
+                    path: other.path.clone(),
+                    title: other.title.clone(),
+                    line: y,
+                    text: line.trim().to_string(),
+                });
+            }
+        }
+    }
+    refs.sort_by(|a, b| a.title.cmp(&b.title).then(a.line.cmp(&b.line)));
+    refs
+}
+
+// names worth searching for. one or two letters would match everywhere.
+pub fn mention_names(title: &str, aliases: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for name in [title.to_string()].iter().chain(aliases) {
+        let name = name.trim().to_string();
+        if name.chars().count() >= 3 && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::template;
+use super::time::DateTime;
+
+// デイリーノート。日付ごとのノートを、設定したファイル名のパターンで作る。
+// パターンは DateTime::format の書式に、週番号の %W を足したもの。
+// 新しいノートの中身はテンプレートファイル(なければ日付の見出し)で、ノートのテンプレートと同じ
+// {{date}} {{date:%d/%m}} {{time}} に加えて {{weekday}} {{week}} {{week_start}} {{yesterday}} {{tomorrow}} を置き換える。
+// {{yesterday}} と {{tomorrow}} は前後の日のノートの名前なので [[{{yesterday}}]] でリンクになる。
+
+pub const DEFAULT_PATTERN: &str = "daily/%Y-%m-%d";
+const DEFAULT_TEMPLATE: &str = "# {{date}} {{weekday}}\n\n";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Daily {
+    pub pattern: String,           // path of a note without the extension
+    pub template: Option<PathBuf>, // relative to the vault, or absolute
+    pub week_start: u32,           // 0 is sunday
+}
+
+impl Default for Daily {
+    fn default() -> Self {
+        Daily {
+            pattern: String::from(DEFAULT_PATTERN),
+            template: None,
+            week_start: 1,
+        }
+    }
+}
+
+impl Daily {
+    // "daily/2024-01-31"
+    pub fn name(&self, date: &DateTime) -> String {
+        let week = format!("{:02}", date.week_number(self.week_start));
+        date.format(&self.pattern.replace("%W", &week))
+    }
+
+    // the date of a note named by the pattern. the path is relative to the vault.
+    pub fn date_of(&self, path: &Path) -> Option<DateTime> {
+        let name = path.with_extension("").to_string_lossy().replace('\\', "/");
+        let name = name.chars().collect::<Vec<char>>();
+        let pattern = self.pattern.chars().collect::<Vec<char>>();
+        let (mut year, mut month, mut day) = (None, None, None);
+        let (mut i, mut j) = (0, 0);
+        while i < pattern.len() {
+            if pattern[i] != '%' || i + 1 == pattern.len() {
+                if name.get(j) != Some(&pattern[i]) {
+                    return None;
+                }
+                i += 1;
+                j += 1;
+                continue;
+            }
+            let code = pattern[i + 1];
+            i += 2;
+            if code == '%' {
+                if name.get(j) != Some(&'%') {
+                    return None;
+                }
+                j += 1;
+                continue;
+            }
+            let len = match code {
+                'Y' => 4,
+                'm' | 'd' | 'H' | 'M' | 'S' | 'W' => 2,
+                'a' => 3,
+                _ => return None,
+            };
+            let field = name.get(j..j + len)?.iter().collect::<String>();
+            j += len;
+            if code == 'a' {
+                continue;
+            }
+            let value = field
+                .chars()
+                .all(|c| c.is_ascii_digit())
+                .then(|| field.parse::<u32>().ok())
+                .flatten()?;
+            match code {
+                'Y' => year = Some(value as i32),
+                'm' => month = Some(value),
+                'd' => day = Some(value),
+                _ => {}
+            }
+        }
+        if j != name.len() {
+            return None;
+        }
+        DateTime::date(year?, month?, day?)
+    }
+
+    // the text of a new note for the date
+    pub fn content(&self, date: &DateTime, root: &Path) -> io::Result<String> {
+        let template = match &self.template {
+            Some(path) => fs::read_to_string(root.join(path))?,
+            None => String::from(DEFAULT_TEMPLATE),
+        };
+        Ok(self.expand(&template, date))
+    }
+
+    fn expand(&self, template: &str, date: &DateTime) -> String {
+        let lookup = |key: &str| match key {
+            "weekday" => Some(date.format("%a")),
+            "week" => Some(format!("{:02}", date.week_number(self.week_start))),
+            "week_start" => Some(date.week_start(self.week_start).format("%Y-%m-%d")),
+            "yesterday" => Some(self.name(&date.add_days(-1))),
+            "tomorrow" => Some(self.name(&date.add_days(1))),
+            _ => template::builtin(key, date),
+        };
+        template::expand_with(template, &lookup).text
+    }
+}
+
+// "today", "yesterday", "tomorrow", "+3", "-1" or "2024-01-31"
+pub fn parse_day(text: &str, today: &DateTime) -> Option<DateTime> {
+    let text = text.trim();
+    match text {
+        "" | "today" => Some(*today),
+        "yesterday" => Some(today.add_days(-1)),
+        "tomorrow" => Some(today.add_days(1)),
+        _ if text.starts_with('+') || text.starts_with('-') => {
+            let days = text.trim_start_matches('+').parse::<i64>().ok()?;
+            Some(today.add_days(days))
+        }
+        _ => DateTime::parse_date(text),
+    }
+}
+
+// "monday" or "mon" -> 1
+pub fn parse_weekday(text: &str) -> Option<u32> {
+    let text = text.trim().to_lowercase();
+    [
+        "sunday",
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+    ]
+    .iter()
+    .position(|d| text.len() >= 3 && d.starts_with(&text))
+    .map(|i| i as u32)
+}
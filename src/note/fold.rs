@@ -0,0 +1,90 @@
+use super::front_matter;
+use super::list::{item_end, parse_item};
+use super::outline::{headings, Heading};
+
+// 折りたたみ。見出しのセクション、子のあるリストの項目、コードブロック、フロントマターは
+// 最初の行だけを残して折りたためる。どこまでを畳むかは行の内容から求めるので、
+// テキストが変わったら折りたたみの終わりを求め直す。
+
+// the blocks of a text which can be folded
+#[derive(Debug, Clone)]
+pub struct Blocks {
+    front: Option<usize>,        // last line of the front matter
+    fences: Vec<(usize, usize)>, // first and last line of code blocks
+    headings: Vec<Heading>,
+}
+
+impl Blocks {
+    pub fn new(lines: &[Vec<char>]) -> Self {
+        let head = lines
+            .iter()
+            .take(front_matter::MAX_LINES)
+            .map(|l| l.iter().collect::<String>())
+            .collect::<Vec<String>>();
+        let front = front_matter::parse(&head).map(|f| f.end);
+        let mut fences = vec![];
+        let mut open: Option<(usize, Vec<char>)> = None;
+        for (y, line) in lines
+            .iter()
+            .enumerate()
+            .skip(front.map_or(0, |end| end + 1))
+        {
+            let start = line.iter().take_while(|c| **c == ' ').count();
+            let rest = &line[start..];
+            match &open {
+                Some((first, marker)) if rest.starts_with(marker) => {
+                    fences.push((*first, y));
+                    open = None;
+                }
+                Some(_) => {}
+                None => {
+                    if rest.starts_with(&['`', '`', '`']) || rest.starts_with(&['~', '~', '~']) {
+                        open = Some((y, rest[..3].to_vec()));
+                    }
+                }
+            }
+        }
+        if let Some((first, _)) = open {
+            fences.push((first, lines.len() - 1));
+        }
+        Blocks {
+            front,
+            fences,
+            headings: headings(lines),
+        }
+    }
+
+    // the last line of the block which starts on the line, if it can be folded
+    pub fn end(&self, lines: &[Vec<char>], y: usize) -> Option<usize> {
+        let end = if let Some(end) = self.front.filter(|_| y == 0) {
+            end
+        } else if let Some((_, end)) = self.fences.iter().find(|(first, _)| *first == y) {
+            *end
+        } else if self
+            .fences
+            .iter()
+            .any(|(first, last)| *first < y && y <= *last)
+        {
+            return None;
+        } else if let Some(i) = self.headings.iter().position(|h| h.line == y) {
+            let level = self.headings[i].level;
+            self.headings[i + 1..]
+                .iter()
+                .find(|h| h.level <= level)
+                .map_or(lines.len(), |h| h.line)
+                - 1
+        } else if parse_item(&lines[y]).is_some() {
+            item_end(lines, y) - 1
+        } else {
+            return None;
+        };
+        (end > y).then_some(end)
+    }
+
+    // the first line of the innermost block which has the line
+    pub fn enclosing(&self, lines: &[Vec<char>], y: usize) -> Option<usize> {
+        (0..=y)
+            .rev()
+            .find(|first| self.end(lines, *first).is_some_and(|end| end >= y))
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+// ノート先頭の "---" で囲まれたYAMLのフロントマター。
+// YAMLのすべては読まず、ノートのメタデータに使う形だけを扱う。
+//   key: value / key: "quoted" / key: [a, b] / key: の次の行から "- item" のリスト
+// 知らないキーも other に残しておく。
+
+pub const MAX_LINES: usize = 200; // the closing fence must be in these lines
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub aliases: Vec<String>,
+    pub created: Option<String>,
+    pub updated: Option<String>,
+    pub other: Vec<(String, Vec<String>)>,
+    pub end: usize, // line of the closing fence
+}
+
+impl FrontMatter {
+    // lines of the front matter, fences included
+    pub fn line_count(&self) -> usize {
+        self.end + 1
+    }
+}
+
+// parse the leading block of the lines. None if the note has no front matter.
+pub fn parse<S: AsRef<str>>(lines: &[S]) -> Option<FrontMatter> {
+    if lines.first().map(|l| l.as_ref().trim_end()) != Some("---") {
+        return None;
+    }
+    let end = lines
+        .iter()
+        .take(MAX_LINES)
+        .skip(1)
+        .position(|l| matches!(l.as_ref().trim_end(), "---" | "..."))?
+        + 1;
+    let mut front = FrontMatter {
+        end,
+        ..Default::default()
+    };
+    let mut entries: Vec<(String, Vec<String>)> = vec![];
+    for line in lines[1..end].iter().map(|l| l.as_ref()) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // "- item" under the last key
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or(trimmed.strip_prefix('-').filter(|i| i.is_empty()))
+        {
+            if let Some((_, values)) = entries.last_mut() {
+                let item = unquote(item.trim());
+                if !item.is_empty() {
+                    values.push(item);
+                }
+            }
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            continue; // nested mappings are not supported
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        entries.push((key.trim().to_lowercase(), parse_value(value.trim())));
+    }
+    for (key, values) in entries {
+        let first = values.first().cloned();
+        match key.as_str() {
+            "title" => front.title = first,
+            "created" | "date" if front.created.is_none() => front.created = first,
+            "updated" | "modified" if front.updated.is_none() => front.updated = first,
+            "tags" | "tag" => front.tags.extend(split_tags(&values)),
+            "aliases" | "alias" => front.aliases.extend(values),
+            _ => front.other.push((key, values)),
+        }
+    }
+    Some(front)
+}
+
+// "[a, b]" -> [a, b], "value" -> [value], "" -> []
+fn parse_value(value: &str) -> Vec<String> {
+    if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        return inner
+            .split(',')
+            .map(|v| unquote(v.trim()))
+            .filter(|v| !v.is_empty())
+            .collect();
+    }
+    let value = unquote(value);
+    if value.is_empty() {
+        vec![]
+    } else {
+        vec![value]
+    }
+}
+
+fn unquote(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return value[1..value.len() - 1].to_string();
+        }
+    }
+    // a comment after the value
+    match value.find(" #") {
+        Some(i) => value[..i].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+// "tags: a, b c" is also a list. the leading # is optional.
+fn split_tags(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|v| v.split(|c: char| c == ',' || c.is_whitespace()))
+        .map(|t| t.trim_start_matches('#').to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+// set "key: value" in the front matter, adding the key before the closing fence.
+// returns the new lines, or None if the lines have no front matter.
+pub fn set_field<S: AsRef<str>>(lines: &[S], key: &str, value: &str) -> Option<Vec<String>> {
+    let front = parse(lines)?;
+    let mut lines = lines[..front.line_count()]
+        .iter()
+        .map(|l| l.as_ref().to_string())
+        .collect::<Vec<String>>();
+    let field = format!("{}: {}", key, value);
+    let found = lines[1..front.end].iter().position(|l| {
+        l.split_once(':')
+            .is_some_and(|(k, _)| !k.starts_with(char::is_whitespace) && k.trim() == key)
+    });
+    match found {
+        Some(i) => lines[i + 1] = field,
+        None => lines.insert(front.end, field),
+    }
+    Some(lines)
+}
+
+// title and front matter of a note file, reading only its head.
+// the title is the one in the front matter, the first heading or the file name.
+pub fn read_head(path: &Path) -> (String, Option<FrontMatter>) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let Ok(file) = fs::File::open(path) else {
+        return (stem, None);
+    };
+    let mut lines = vec![];
+    for line in BufReader::new(file).lines().take(MAX_LINES) {
+        let Ok(line) = line else {
+            break;
+        };
+        lines.push(line);
+    }
+    if let Some(first) = lines.first_mut() {
+        *first = first.trim_start_matches('\u{feff}').to_string();
+    }
+    let front = parse(&lines);
+    let title = note_title(&lines, front.as_ref()).unwrap_or(stem);
+    (title, front)
+}
+
+// the title in the front matter or the first heading after it
+pub fn note_title<S: AsRef<str>>(lines: &[S], front: Option<&FrontMatter>) -> Option<String> {
+    let body = front.map(|f| f.line_count()).unwrap_or(0);
+    front
+        .and_then(|f| f.title.clone())
+        .filter(|t| !t.is_empty())
+        .or_else(|| {
+            lines
+                .iter()
+                .skip(body)
+                .take(30)
+                .find_map(|l| l.as_ref().strip_prefix("# ").map(|t| t.trim().to_string()))
+        })
+}
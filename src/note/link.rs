@@ -0,0 +1,152 @@
+// ノート間のリンク。[[Note Title]]、[[note#heading]]、[[note#heading|表示名]] の形。
+// リンク先は Vault::find と同じ規則(パス、ファイル名、タイトル)で探す。
+// インラインコードの中の [[ ]] はリンクではない。
+// Markdown のリンク [text](path) は名前の変更でパスを書き換えるために探す。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub target: String, // empty for a heading in the same note
+    pub heading: Option<String>,
+    pub alias: Option<String>,
+    pub line: usize,
+    pub start: usize, // char index of "[["
+    pub end: usize,   // char index after "]]"
+}
+
+// links in a line
+pub fn find_links(line: &[char], y: usize) -> Vec<Link> {
+    let mut links = vec![];
+    let mut in_code = false;
+    let mut i = 0;
+    while i < line.len() {
+        if line[i] == '`' {
+            in_code = !in_code;
+        }
+        if in_code || !line[i..].starts_with(&['[', '[']) {
+            i += 1;
+            continue;
+        }
+        let Some(len) = (i + 2..line.len().saturating_sub(1))
+            .find(|j| line[*j] == ']' && line[*j + 1] == ']')
+            .map(|j| j - i - 2)
+        else {
+            break;
+        };
+        let inner = line[i + 2..i + 2 + len].iter().collect::<String>();
+        if let Some(mut link) = parse_link(&inner) {
+            link.line = y;
+            link.start = i;
+            link.end = i + len + 4;
+            links.push(link);
+        }
+        i += len + 4;
+    }
+    links
+}
+
+// links in the text, line by line. code blocks are skipped.
+pub fn parse_links(content: &str) -> Vec<Link> {
+    let mut fence = false;
+    let mut links = vec![];
+    for (y, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = !fence;
+            continue;
+        }
+        if !fence && line.contains("[[") {
+            links.extend(find_links(&line.chars().collect::<Vec<char>>(), y));
+        }
+    }
+    links
+}
+
+// urls of markdown links "[text](url)" and images in a line, as char ranges.
+// the range is inside "<>" when the url is written in them. inline code is skipped.
+pub fn find_url_ranges(line: &[char]) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    let mut in_code = false;
+    let mut i = 0;
+    while i < line.len() {
+        if line[i] == '`' {
+            in_code = !in_code;
+        }
+        if in_code || !line[i..].starts_with(&[']', '(']) || !line[..i].contains(&'[') {
+            i += 1;
+            continue;
+        }
+        let start = i + 2;
+        let range = if line.get(start) == Some(&'<') {
+            line[start..]
+                .iter()
+                .position(|c| *c == '>')
+                .map(|len| (start + 1, start + len))
+        } else {
+            line[start..]
+                .iter()
+                .position(|c| *c == ')' || c.is_whitespace())
+                .map(|len| (start, start + len))
+        };
+        match range {
+            Some((s, e)) if s < e => {
+                ranges.push((s, e));
+                i = e;
+            }
+            _ => i = start,
+        }
+    }
+    ranges
+}
+
+// char index of the name written as a word outside of links, ignoring the case
+pub fn find_mention(line: &str, name: &str) -> Option<usize> {
+    let chars = line.to_lowercase().chars().collect::<Vec<char>>();
+    let name = name.to_lowercase().chars().collect::<Vec<char>>();
+    if name.is_empty() || chars.len() != line.chars().count() {
+        return None;
+    }
+    let links = find_links(&chars, 0);
+    let is_word = |c: Option<&char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    (0..(chars.len() + 1).saturating_sub(name.len())).find(|i| {
+        chars[*i..].starts_with(&name)
+            && !(i > &0 && is_word(chars.get(i - 1)) && is_word(name.first()))
+            && !(is_word(chars.get(i + name.len())) && is_word(name.last()))
+            && !links.iter().any(|l| l.start <= *i && *i < l.end)
+    })
+}
+
+// "note#heading|alias"
+fn parse_link(inner: &str) -> Option<Link> {
+    if inner.contains('[') || inner.contains('\n') {
+        return None;
+    }
+    let (target, alias) = match inner.split_once('|') {
+        Some((target, alias)) => (target, Some(alias.trim().to_string())),
+        None => (inner, None),
+    };
+    let (target, heading) = match target.split_once('#') {
+        Some((target, heading)) => (target, Some(heading.trim().to_string())),
+        None => (target, None),
+    };
+    if target.trim().is_empty() && heading.is_none() {
+        return None;
+    }
+    Some(Link {
+        target: target.trim().to_string(),
+        heading,
+        alias,
+        line: 0,
+        start: 0,
+        end: 0,
+    })
+}
+
+// line of the heading with the text, ignoring the case
+pub fn find_heading<S: AsRef<str>>(lines: &[S], heading: &str) -> Option<usize> {
+    let heading = heading.trim().to_lowercase();
+    lines.iter().position(|l| {
+        let l = l.as_ref();
+        let text = l.trim_start_matches('#');
+        text.len() < l.len() && text.starts_with(' ') && text.trim().to_lowercase() == heading
+    })
+}
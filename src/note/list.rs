@@ -0,0 +1,184 @@
+// Markdownのリスト。"- "、"* "、"+ " と "1. "、"1) " の項目と、その後の "[ ] " のチェックボックスを読む。
+// 番号付きのリストは、同じ字下げの項目を最初の項目の番号から振り直す。
+// 字下げの深い行は子の項目か項目の続きとして飛ばし、空行か浅い行でリストが終わる。
+// チェックボックスは "[x]" で完了、"[ ]" で未完了。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub indent: usize,         // chars before the marker
+    pub bullet: char,          // '-', '*' or '+', or '.' or ')' after the number
+    pub number: Option<usize>, // of an ordered item
+    pub marker_end: usize,     // char index after the bullet
+    pub checkbox: Option<bool>,
+    pub content: usize, // char index of the text of the item
+}
+
+pub fn parse_item(line: &[char]) -> Option<ListItem> {
+    let indent = indent_of(line);
+    let rest = &line[indent..];
+    let (bullet, number, len) = match rest.first()? {
+        c @ ('-' | '*' | '+') => (*c, None, 1),
+        _ => {
+            let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+            if digits == 0 || digits > 9 {
+                return None;
+            }
+            let bullet = *rest.get(digits).filter(|c| matches!(c, '.' | ')'))?;
+            let number = rest[..digits].iter().collect::<String>().parse().ok()?;
+            (bullet, Some(number), digits + 1)
+        }
+    };
+    let marker_end = indent + len;
+    match line.get(marker_end) {
+        None => {
+            return Some(ListItem {
+                indent,
+                bullet,
+                number,
+                marker_end,
+                checkbox: None,
+                content: marker_end,
+            })
+        }
+        Some(' ') | Some('\t') => {}
+        Some(_) => return None,
+    }
+    let mut content = marker_end + 1;
+    let checkbox = match line.get(content..content + 3) {
+        Some(['[', c @ (' ' | 'x' | 'X'), ']'])
+            if matches!(line.get(content + 3), None | Some(' ')) =>
+        {
+            content = (content + 4).min(line.len());
+            Some(*c != ' ')
+        }
+        _ => None,
+    };
+    Some(ListItem {
+        indent,
+        bullet,
+        number,
+        marker_end,
+        checkbox,
+        content,
+    })
+}
+
+pub fn indent_of(line: &[char]) -> usize {
+    line.iter()
+        .take_while(|c| **c == ' ' || **c == '\t')
+        .count()
+}
+
+// the marker of the item after this one: "  2. " or "- [ ] "
+pub fn next_marker(line: &[char], item: &ListItem) -> Vec<char> {
+    let mut marker = line[..item.indent].to_vec();
+    match item.number {
+        Some(n) => marker.extend(format!("{}{}", n + 1, item.bullet).chars()),
+        None => marker.push(item.bullet),
+    }
+    marker.push(' ');
+    if item.checkbox.is_some() {
+        marker.extend("[ ] ".chars());
+    }
+    marker
+}
+
+// the line after the item and its children
+pub fn item_end(lines: &[Vec<char>], y: usize) -> usize {
+    let Some(item) = parse_item(&lines[y]) else {
+        return y + 1;
+    };
+    let mut end = y + 1;
+    while end < lines.len() && !is_blank(&lines[end]) && indent_of(&lines[end]) > item.indent {
+        end += 1;
+    }
+    end
+}
+
+// the items in the list of the item on line y, with the same indent
+pub fn siblings(lines: &[Vec<char>], y: usize) -> Vec<usize> {
+    let Some(item) = parse_item(&lines[y]) else {
+        return vec![];
+    };
+    let is_sibling = |i: usize| {
+        parse_item(&lines[i]).is_some_and(|it| {
+            it.indent == item.indent && it.number.is_some() == item.number.is_some()
+        })
+    };
+    let mut first = y;
+    let mut i = y;
+    while i > 0 {
+        i -= 1;
+        if is_blank(&lines[i]) {
+            break;
+        }
+        if is_sibling(i) {
+            first = i;
+        } else if indent_of(&lines[i]) <= item.indent {
+            break;
+        }
+    }
+    let mut items = vec![];
+    for (i, line) in lines.iter().enumerate().skip(first) {
+        if is_blank(line) {
+            break;
+        }
+        if is_sibling(i) {
+            items.push(i);
+        } else if indent_of(line) <= item.indent {
+            break;
+        }
+    }
+    items
+}
+
+// number the ordered list of the item on line y from its first item
+pub fn renumber(lines: &mut [Vec<char>], y: usize) {
+    let items = siblings(lines, y);
+    let Some(mut number) = items
+        .first()
+        .and_then(|i| parse_item(&lines[*i]))
+        .and_then(|item| item.number)
+    else {
+        return;
+    };
+    for i in items {
+        set_number(&mut lines[i], number);
+        number += 1;
+    }
+}
+
+// replace the number of an ordered item
+pub fn set_number(line: &mut Vec<char>, number: usize) {
+    let Some(item) = parse_item(line) else {
+        return;
+    };
+    if item.number.is_some() {
+        line.splice(item.indent..item.marker_end - 1, number.to_string().chars());
+    }
+}
+
+// check or uncheck the task. a list item gets a checkbox and other text becomes a task.
+pub fn set_checkbox(line: &mut Vec<char>, done: bool) {
+    let mark = if done { 'x' } else { ' ' };
+    match parse_item(line) {
+        Some(item) if item.checkbox.is_some() => line[item.marker_end + 2] = mark,
+        Some(item) => {
+            if line.len() == item.marker_end {
+                line.push(' ');
+            }
+            line.splice(
+                item.marker_end + 1..item.marker_end + 1,
+                ['[', mark, ']', ' '],
+            );
+        }
+        None => {
+            let indent = indent_of(line);
+            line.splice(indent..indent, ['-', ' ', '[', mark, ']', ' ']);
+        }
+    }
+}
+
+fn is_blank(line: &[char]) -> bool {
+    line.iter().all(|c| c.is_whitespace())
+}
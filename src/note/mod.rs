@@ -0,0 +1,14 @@
+pub mod backlink;
+pub mod daily;
+pub mod fold;
+pub mod front_matter;
+pub mod link;
+pub mod list;
+pub mod outline;
+pub mod rename;
+pub mod tag;
+pub mod task;
+pub mod template;
+pub mod time;
+pub mod vault;
+pub mod walk;
@@ -0,0 +1,131 @@
+use super::front_matter;
+
+// ノートのアウトライン。"# 見出し" から "###### 見出し" までの見出しを並べたもの。
+// 見出しのセクションは、その見出しから同じか浅いレベルの次の見出しの前まで。
+// セクションは同じ親の中で前後のセクションと入れ替え、レベルは子の見出しごと上げ下げする。
+// フロントマターとコードブロックの中の # は見出しではない。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub level: usize, // 1-6
+    pub title: String,
+    pub line: usize,
+}
+
+// "## title" -> (2, "title")
+pub fn parse_heading(line: &[char]) -> Option<(usize, String)> {
+    let level = line.iter().take_while(|c| **c == '#').count();
+    if level == 0 || level > 6 || line.get(level).is_some_and(|c| *c != ' ') {
+        return None;
+    }
+    let title = line[level..].iter().collect::<String>();
+    Some((
+        level,
+        title.trim().trim_end_matches('#').trim_end().to_string(),
+    ))
+}
+
+pub fn headings(lines: &[Vec<char>]) -> Vec<Heading> {
+    let skip = if lines
+        .first()
+        .is_some_and(|l| l.starts_with(&['-', '-', '-']))
+    {
+        let head = lines
+            .iter()
+            .take(front_matter::MAX_LINES)
+            .map(|l| l.iter().collect::<String>())
+            .collect::<Vec<String>>();
+        front_matter::parse(&head).map_or(0, |f| f.line_count())
+    } else {
+        0
+    };
+    let mut headings = vec![];
+    let mut fence = false;
+    for (y, line) in lines.iter().enumerate().skip(skip) {
+        let start = line.iter().take_while(|c| **c == ' ').count();
+        let rest = &line[start..];
+        if rest.starts_with(&['`', '`', '`']) || rest.starts_with(&['~', '~', '~']) {
+            fence = !fence;
+            continue;
+        }
+        if fence {
+            continue;
+        }
+        if let Some((level, title)) = parse_heading(line) {
+            headings.push(Heading {
+                level,
+                title,
+                line: y,
+            });
+        }
+    }
+    headings
+}
+
+// index of the heading whose section has the line
+pub fn current_heading(headings: &[Heading], line: usize) -> Option<usize> {
+    headings.iter().rposition(|h| h.line <= line)
+}
+
+// the line after the section of the i-th heading
+fn section_end(headings: &[Heading], i: usize, line_count: usize) -> usize {
+    headings[i + 1..]
+        .iter()
+        .find(|h| h.level <= headings[i].level)
+        .map_or(line_count, |h| h.line)
+}
+
+// swap the section of the heading on the line with the one before or after it
+// under the same parent. returns the new line of the heading.
+pub fn move_section(lines: &mut Vec<Vec<char>>, line: usize, up: bool) -> Option<usize> {
+    let headings = headings(lines);
+    let i = headings.iter().position(|h| h.line == line)?;
+    let level = headings[i].level;
+    let end = section_end(&headings, i, lines.len());
+    let (first, middle, last) = if up {
+        let j = headings[..i].iter().rposition(|h| h.level <= level)?;
+        if headings[j].level != level {
+            return None;
+        }
+        (headings[j].line, line, end)
+    } else {
+        let k = headings[i + 1..].iter().position(|h| h.level <= level)? + i + 1;
+        if headings[k].level != level {
+            return None;
+        }
+        (line, end, section_end(&headings, k, lines.len()))
+    };
+    let mut moved = lines[middle..last].to_vec();
+    moved.extend_from_slice(&lines[first..middle]);
+    lines.splice(first..last, moved);
+    Some(if up { first } else { first + last - middle })
+}
+
+// promote (a negative delta) or demote the heading and the headings in its section.
+// false if a level would be out of 1-6.
+pub fn shift_section(lines: &mut [Vec<char>], line: usize, delta: isize) -> bool {
+    let headings = headings(lines);
+    let Some(i) = headings.iter().position(|h| h.line == line) else {
+        return false;
+    };
+    let end = section_end(&headings, i, lines.len());
+    let section = headings
+        .iter()
+        .filter(|h| h.line >= line && h.line < end)
+        .collect::<Vec<&Heading>>();
+    if section
+        .iter()
+        .any(|h| !(1..=6).contains(&(h.level as isize + delta)))
+    {
+        return false;
+    }
+    for heading in section {
+        let text = &mut lines[heading.line];
+        if delta < 0 {
+            text.drain(..(-delta) as usize);
+        } else {
+            text.splice(0..0, vec!['#'; delta as usize]);
+        }
+    }
+    true
+}
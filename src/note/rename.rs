@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use super::link::{find_links, find_url_ranges};
+use super::vault::Vault;
+
+// ノートやフォルダの名前を変えたときのリンクの書き換え。
+// 動かすファイルを指している [[リンク]] と相対パスの [text](path) を探して、
+// 変更の一覧(プレビュー)を作ってから、すべてのファイルをまとめて書き換える。
+// 書き込みに一つでも失敗したら、書き換えたファイルを元に戻す。
+// 動かすノート自身の相対リンクも、新しい場所から同じファイルを指すように直す。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineChange {
+    pub line: usize, // from 0
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRewrite {
+    pub path: PathBuf,    // relative to the root, before the move
+    pub original: String, // to find changes after the preview, and to roll back
+    pub content: String,
+    pub changes: Vec<LineChange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenamePlan {
+    pub from: PathBuf, // file or folder, relative to the root
+    pub to: PathBuf,
+    pub moves: Vec<(PathBuf, PathBuf)>, // every file under from
+    pub files: Vec<FileRewrite>,
+}
+
+impl RenamePlan {
+    pub fn change_count(&self) -> usize {
+        self.files.iter().map(|f| f.changes.len()).sum()
+    }
+
+    // "path:line" and the line before and after each change
+    pub fn preview(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} -> {}",
+            self.from.to_string_lossy(),
+            self.to.to_string_lossy()
+        )];
+        for file in self.files.iter() {
+            for change in file.changes.iter() {
+                lines.push(format!(
+                    "{}:{}",
+                    file.path.to_string_lossy(),
+                    change.line + 1
+                ));
+                lines.push(format!("- {}", change.before));
+                lines.push(format!("+ {}", change.after));
+            }
+        }
+        lines
+    }
+}
+
+// find the links to rewrite when from is moved to to. the extension of a note
+// is kept when to has none. nothing is written.
+pub fn plan_rename(vault: &Vault, from: &Path, to: &Path) -> io::Result<RenamePlan> {
+    let from = from.strip_prefix(&vault.root).unwrap_or(from).to_path_buf();
+    let mut to = to.strip_prefix(&vault.root).unwrap_or(to).to_path_buf();
+    let source = vault.root.join(&from);
+    if !source.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", from.to_string_lossy()),
+        ));
+    }
+    if source.is_file() && to.extension().is_none() {
+        if let Some(ext) = from.extension() {
+            to.set_extension(ext);
+        }
+    }
+    if vault.root.join(&to).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.to_string_lossy()),
+        ));
+    }
+    if to.starts_with(&from) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot move {} into itself", from.to_string_lossy()),
+        ));
+    }
+    let moves = if source.is_dir() {
+        list_files(&source)
+            .into_iter()
+            .filter_map(|p| {
+                let rest = p.strip_prefix(&source).ok()?;
+                Some((from.join(rest), to.join(rest)))
+            })
+            .collect()
+    } else {
+        vec![(from.clone(), to.clone())]
+    };
+    let moved = moves.iter().cloned().collect::<HashMap<PathBuf, PathBuf>>();
+    let mut files = vec![];
+    for note in vault.notes() {
+        // files which are not utf-8 are left as they are
+        let Ok(original) = fs::read_to_string(vault.root.join(&note.path)) else {
+            continue;
+        };
+        let mut changes = vec![];
+        let mut fence = false;
+        let mut lines = original.split('\n').map(String::from).collect::<Vec<_>>();
+        for (y, line) in lines.iter_mut().enumerate() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                fence = !fence;
+                continue;
+            }
+            if fence {
+                continue;
+            }
+            let cr = line.ends_with('\r');
+            let text = line.trim_end_matches('\r');
+            let after = rewrite_line(vault, &note.path, text, &moved);
+            if after != text {
+                changes.push(LineChange {
+                    line: y,
+                    before: text.to_string(),
+                    after: after.clone(),
+                });
+                *line = if cr { after + "\r" } else { after };
+            }
+        }
+        if !changes.is_empty() {
+            files.push(FileRewrite {
+                path: note.path.clone(),
+                original,
+                content: lines.join("\n"),
+                changes,
+            });
+        }
+    }
+    Ok(RenamePlan {
+        from,
+        to,
+        moves,
+        files,
+    })
+}
+
+// write the rewritten files and move from to to. files are written to
+// temporary files first, then renamed over the notes. when anything fails,
+// the notes which were replaced get their original content back.
+pub fn apply_rename(root: &Path, plan: &RenamePlan) -> io::Result<()> {
+    if root.join(&plan.to).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", plan.to.to_string_lossy()),
+        ));
+    }
+    for file in plan.files.iter() {
+        let content = fs::read_to_string(root.join(&file.path)).unwrap_or_default();
+        if content != file.original {
+            return Err(io::Error::other(format!(
+                "{} was changed. try again",
+                file.path.to_string_lossy()
+            )));
+        }
+    }
+    let mut temps = vec![];
+    for file in plan.files.iter() {
+        let path = root.join(&file.path);
+        let temp = temp_path(&path);
+        if let Err(e) = fs::write(&temp, &file.content) {
+            let _ = fs::remove_file(&temp);
+            for (temp, _) in temps {
+                let _ = fs::remove_file(temp);
+            }
+            return Err(e);
+        }
+        temps.push((temp, path));
+    }
+    let mut replaced = vec![];
+    for (i, (temp, path)) in temps.iter().enumerate() {
+        if let Err(e) = fs::rename(temp, path) {
+            for (temp, _) in temps[i..].iter() {
+                let _ = fs::remove_file(temp);
+            }
+            return Err(roll_back(root, plan, &replaced, e));
+        }
+        replaced.push(i);
+    }
+    let target = root.join(&plan.to);
+    let result = match target.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+    .and_then(|_| fs::rename(root.join(&plan.from), &target));
+    if let Err(e) = result {
+        return Err(roll_back(root, plan, &replaced, e));
+    }
+    Ok(())
+}
+
+// put back the original content. the error tells if that failed too.
+fn roll_back(root: &Path, plan: &RenamePlan, replaced: &[usize], e: io::Error) -> io::Error {
+    let failed = replaced
+        .iter()
+        .map(|i| &plan.files[*i])
+        .filter(|f| fs::write(root.join(&f.path), &f.original).is_err())
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    if failed.is_empty() {
+        return e;
+    }
+    io::Error::new(
+        e.kind(),
+        format!("{}. could not restore {}", e, failed.join(", ")),
+    )
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.rename-tmp", name))
+}
+
+// files under the directory, recursively
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(list_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+// the line with the links to moved files, and the relative links of a moved
+// note, pointing to the new places
+fn rewrite_line(
+    vault: &Vault,
+    note: &Path,
+    line: &str,
+    moved: &HashMap<PathBuf, PathBuf>,
+) -> String {
+    let chars = line.chars().collect::<Vec<char>>();
+    // (start, end, replacement) by char index
+    let mut edits = vec![];
+    for link in find_links(&chars, 0) {
+        if link.target.is_empty() {
+            continue;
+        }
+        let Some(old) = vault.find(&link.target).first().map(|n| n.path.clone()) else {
+            continue;
+        };
+        let Some(new) = moved.get(&old) else {
+            continue;
+        };
+        let target = wiki_target(vault, &link.target, &old, new);
+        if target != link.target {
+            let start = link.start + 2;
+            let len = chars[start..link.end - 2]
+                .iter()
+                .position(|c| *c == '#' || *c == '|')
+                .unwrap_or(link.end - 2 - start);
+            edits.push((start, start + len, target));
+        }
+    }
+    let old_dir = note.parent().unwrap_or(Path::new(""));
+    let new_note = moved.get(note).map(|p| p.as_path()).unwrap_or(note);
+    let new_dir = new_note.parent().unwrap_or(Path::new(""));
+    for (start, end) in find_url_ranges(&chars) {
+        let url = chars[start..end].iter().collect::<String>();
+        let angle = start > 0 && chars[start - 1] == '<';
+        let (path, anchor) = match url.split_once('#') {
+            Some((path, anchor)) => (path.to_string(), format!("#{}", anchor)),
+            None => (url.clone(), String::new()),
+        };
+        if path.is_empty() || path.starts_with('/') || path.contains(':') {
+            continue;
+        }
+        let Some(old) = normalize(&old_dir.join(decode_url(&path))) else {
+            continue;
+        };
+        let new = match moved.get(&old) {
+            Some(new) => new.clone(),
+            None if new_dir != old_dir && vault.root.join(&old).exists() => old,
+            None => continue,
+        };
+        let mut relative = relative_path(new_dir, &new);
+        if path.starts_with("./") && !relative.starts_with("..") {
+            relative = format!("./{}", relative);
+        }
+        if !angle {
+            relative = relative.replace(' ', "%20");
+        }
+        let relative = relative + &anchor;
+        if relative != url {
+            edits.push((start, end, relative));
+        }
+    }
+    edits.sort_by_key(|e| std::cmp::Reverse(e.0));
+    let mut chars = chars;
+    for (start, end, text) in edits {
+        chars.splice(start..end, text.chars());
+    }
+    chars.into_iter().collect()
+}
+
+// the target written in the same form: a path, a path without the extension,
+// or a file name. a title or an alias does not change.
+fn wiki_target(vault: &Vault, target: &str, old: &Path, new: &Path) -> String {
+    let name = target.trim_start_matches("./");
+    let prefix = &target[..target.len() - name.len()];
+    let is_stem = old.file_stem().is_some_and(|s| s.to_string_lossy() == name);
+    let name = if Path::new(name) == old {
+        new.to_string_lossy().to_string()
+    } else if is_stem && !name.contains('/') {
+        let stem = new
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // another note with the file name would be found first
+        if vault.find(&stem).iter().any(|n| n.path != old) {
+            new.with_extension("").to_string_lossy().to_string()
+        } else {
+            stem
+        }
+    } else if Path::new(name) == old.with_extension("") {
+        new.with_extension("").to_string_lossy().to_string()
+    } else {
+        return target.to_string();
+    };
+    format!("{}{}", prefix, name)
+}
+
+// "a/./b/../c" -> "a/c". None when it goes above the root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut parts: Vec<&std::ffi::OsStr> = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(parts.iter().collect())
+}
+
+// path of the target from the directory, with "/"
+fn relative_path(dir: &Path, target: &Path) -> String {
+    let dir = dir.components().collect::<Vec<_>>();
+    let target = target.components().collect::<Vec<_>>();
+    let common = dir
+        .iter()
+        .zip(target.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts = vec![String::from(".."); dir.len() - common];
+    parts.extend(
+        target[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().to_string()),
+    );
+    parts.join("/")
+}
+
+// "%20" and other escapes of ascii
+fn decode_url(url: &str) -> String {
+    let mut decoded = String::new();
+    let mut chars = url.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            let hex = chars.clone().take(2).collect::<String>();
+            if let Ok(b) = u8::from_str_radix(&hex, 16) {
+                if b.is_ascii() && hex.len() == 2 {
+                    decoded.push(b as char);
+                    chars.nth(1);
+                    continue;
+                }
+            }
+        }
+        decoded.push(c);
+    }
+    decoded
+}
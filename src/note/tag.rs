@@ -0,0 +1,84 @@
+use super::front_matter::FrontMatter;
+
+// ノートのタグ。本文の #tag と、フロントマターの tags を合わせたもの。
+// #project/alpha のように / で区切ったタグは project の下の階層として扱う。
+// 見出し(# title)、コードブロック、インラインコードの中の # はタグではない。
+
+pub fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+// "#tag" and "#nested/tag" in the text, lowercased, in order of appearance
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let mut tags = vec![];
+    let mut fence: Option<&str> = None;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(open) = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) {
+            fence = Some(open);
+            continue;
+        }
+        let chars = line.chars().collect::<Vec<char>>();
+        let mut in_code = false;
+        for i in 0..chars.len() {
+            if chars[i] == '`' {
+                in_code = !in_code;
+            }
+            if in_code || chars[i] != '#' || (i > 0 && !chars[i - 1].is_whitespace()) {
+                continue;
+            }
+            let tag = chars[i + 1..]
+                .iter()
+                .take_while(|c| is_tag_char(**c))
+                .collect::<String>();
+            let tag = tag.trim_end_matches('/');
+            if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+                tags.push(tag.to_lowercase());
+            }
+        }
+    }
+    tags
+}
+
+// tags of a note: the front matter first, then the text. no duplicates.
+pub fn note_tags(front: Option<&FrontMatter>, content: &str) -> Vec<String> {
+    let body = match front {
+        Some(front) => content
+            .lines()
+            .skip(front.line_count())
+            .collect::<Vec<&str>>()
+            .join("\n"),
+        None => content.to_string(),
+    };
+    let mut tags: Vec<String> = vec![];
+    let found = front.map(|f| f.tags.clone()).unwrap_or_default();
+    for tag in found.into_iter().chain(extract_tags(&body)) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+// "project" has "project" and "project/alpha"
+pub fn tag_matches(tag: &str, parent: &str) -> bool {
+    tag == parent
+        || tag
+            .strip_prefix(parent)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+// "project/alpha" -> ["project", "project/alpha"]
+pub fn tag_ancestors(tag: &str) -> Vec<String> {
+    tag.char_indices()
+        .filter(|(_, c)| *c == '/')
+        .map(|(i, _)| tag[..i].to_string())
+        .chain([tag.to_string()])
+        .collect()
+}
@@ -0,0 +1,265 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::front_matter;
+use super::list::{parse_item, set_checkbox};
+use super::tag::{extract_tags, note_tags, tag_matches};
+use super::time::DateTime;
+use super::vault::Vault;
+
+// タスク。"- [ ] やること" のようなチェックボックスのあるリストの項目。
+// 保管庫のすべてのノートから未完了のタスクを集め、ノートごとに並べる。
+// タグで絞り込むときは、その行の #tag か、タスク以外の行とフロントマターにあるノートのタグを見る。
+// フロントマターとコードブロックの中はタスクにしない。
+// タスクの行の due:2026-11-01 は期日、!high !medium !low は優先度。
+// @today と @tomorrow は日が変わっても動かないように、ノートを保存するときに due: の日付に書き換える。
+// アジェンダは期日のあるタスクを期限切れ、今日、これからに分けたもの。
+// 完了と延期はノートのファイルの行を直接書き換える。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    pub path: PathBuf, // relative to the root
+    pub title: String, // of the note
+    pub line: usize,   // from 0
+    pub text: String,  // after the checkbox
+    pub done: bool,
+    pub due: Option<DateTime>,
+    pub priority: Priority,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Medium,
+    Normal,
+    Low,
+}
+
+// tasks with a due date: before today, today and after today.
+// each part is sorted by the date, then the priority.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agenda {
+    pub overdue: Vec<Task>,
+    pub today: Vec<Task>,
+    pub upcoming: Vec<Task>,
+}
+
+// tasks in the text as (line, done, text)
+pub fn find_tasks(content: &str) -> Vec<(usize, bool, String)> {
+    let lines = content.lines().collect::<Vec<&str>>();
+    let skip = front_matter::parse(&lines).map_or(0, |f| f.line_count());
+    let mut tasks = vec![];
+    let mut fence = false;
+    for (y, line) in lines.iter().enumerate().skip(skip) {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = !fence;
+            continue;
+        }
+        let chars = line.chars().collect::<Vec<char>>();
+        if let Some(item) = parse_item(&chars).filter(|_| !fence) {
+            if let Some(done) = item.checkbox {
+                let text = chars[item.content..].iter().collect::<String>();
+                tasks.push((y, done, text.trim().to_string()));
+            }
+        }
+    }
+    tasks
+}
+
+// the due date and the priority written in the text of a task
+pub fn parse_meta(text: &str) -> (Option<DateTime>, Priority) {
+    let mut due = None;
+    let mut priority = Priority::Normal;
+    for word in text.split_whitespace() {
+        match word {
+            "!high" => priority = Priority::High,
+            "!medium" => priority = Priority::Medium,
+            "!low" => priority = Priority::Low,
+            _ => {
+                if let Some(date) = word.strip_prefix("due:").and_then(DateTime::parse_date) {
+                    due = Some(date);
+                }
+            }
+        }
+    }
+    (due, priority)
+}
+
+// open tasks of all notes, by the title of the note and the line.
+// with a tag, only tasks which have it or are in a note which has it.
+pub fn open_tasks(vault: &Vault, tag: Option<&str>) -> Vec<Task> {
+    let tag = tag.map(|t| t.trim_start_matches('#').to_lowercase());
+    let mut tasks = vec![];
+    for note in vault.notes() {
+        let Ok(content) = fs::read_to_string(vault.root.join(&note.path)) else {
+            continue;
+        };
+        let found = find_tasks(&content);
+        // tags of the note without the ones which are only on tasks
+        let lines = content.lines().collect::<Vec<&str>>();
+        let rest = lines
+            .iter()
+            .enumerate()
+            .filter(|(y, _)| !found.iter().any(|(line, _, _)| line == y))
+            .map(|(_, line)| *line)
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let note_tags = note_tags(front_matter::parse(&lines).as_ref(), &rest);
+        let note_tagged = |tag: &str| note_tags.iter().any(|t| tag_matches(t, tag));
+        for (line, done, text) in found {
+            let tagged = |tag: &str| {
+                note_tagged(tag) || extract_tags(&text).iter().any(|t| tag_matches(t, tag))
+            };
+            if done || tag.as_deref().is_some_and(|tag| !tagged(tag)) {
+                continue;
+            }
+            let (due, priority) = parse_meta(&text);
+            tasks.push(Task {
+                path: note.path.clone(),
+                title: note.title.clone(),
+                line,
+                text,
+                done,
+                due,
+                priority,
+            });
+        }
+    }
+    tasks.sort_by(|a, b| {
+        a.title
+            .cmp(&b.title)
+            .then(a.path.cmp(&b.path))
+            .then(a.line.cmp(&b.line))
+    });
+    tasks
+}
+
+pub fn agenda(tasks: Vec<Task>, today: &DateTime) -> Agenda {
+    let mut tasks = tasks
+        .into_iter()
+        .filter(|t| t.due.is_some())
+        .collect::<Vec<Task>>();
+    tasks.sort_by(|a, b| {
+        a.due
+            .cmp(&b.due)
+            .then(a.priority.cmp(&b.priority))
+            .then(a.title.cmp(&b.title))
+            .then(a.line.cmp(&b.line))
+    });
+    let mut agenda = Agenda {
+        overdue: vec![],
+        today: vec![],
+        upcoming: vec![],
+    };
+    for task in tasks {
+        let due = task.due.unwrap();
+        let day = (due.year, due.month, due.day);
+        let part = match day.cmp(&(today.year, today.month, today.day)) {
+            std::cmp::Ordering::Less => &mut agenda.overdue,
+            std::cmp::Ordering::Equal => &mut agenda.today,
+            std::cmp::Ordering::Greater => &mut agenda.upcoming,
+        };
+        part.push(task);
+    }
+    agenda
+}
+
+// the text with @today and @tomorrow of the tasks written as due dates
+pub fn fix_due_dates(content: &str, today: &DateTime) -> String {
+    let tasks = find_tasks(content);
+    content
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(y, line)| {
+            if !tasks.iter().any(|(task, _, _)| *task == y) {
+                return line.to_string();
+            }
+            let body = line.trim_end_matches(['\r', '\n']);
+            let words = body
+                .split(' ')
+                .map(|word| match word {
+                    "@today" => format!("due:{}", today.format("%Y-%m-%d")),
+                    "@tomorrow" => format!("due:{}", today.add_days(1).format("%Y-%m-%d")),
+                    _ => word.to_string(),
+                })
+                .collect::<Vec<String>>();
+            words.join(" ") + &line[body.len()..]
+        })
+        .collect()
+}
+
+// check the task in its note
+pub fn complete_task(root: &Path, task: &Task) -> io::Result<()> {
+    edit_task(root, task, |line| set_checkbox(line, true))
+}
+
+// move the due date of the task some days later. an overdue task is moved from today.
+// returns the new date.
+pub fn postpone_task(
+    root: &Path,
+    task: &Task,
+    days: i64,
+    today: &DateTime,
+) -> io::Result<DateTime> {
+    let from = task.due.filter(|due| due > today).unwrap_or(*today);
+    let date = from.add_days(days);
+    let due = format!("due:{}", date.format("%Y-%m-%d"));
+    edit_task(root, task, |line| {
+        let item = parse_item(line).unwrap();
+        let text = line[item.content..].iter().collect::<String>();
+        let mut replaced = false;
+        let mut words = text
+            .split(' ')
+            .filter_map(|word| {
+                let is_due = word
+                    .strip_prefix("due:")
+                    .is_some_and(|d| DateTime::parse_date(d).is_some());
+                match (is_due, replaced) {
+                    (false, _) => Some(word.to_string()),
+                    (true, false) => {
+                        replaced = true;
+                        Some(due.clone())
+                    }
+                    (true, true) => None,
+                }
+            })
+            .collect::<Vec<String>>();
+        if !replaced {
+            if words.last().is_some_and(|w| w.is_empty()) {
+                words.pop();
+            }
+            words.push(due.clone());
+        }
+        line.truncate(item.content);
+        line.extend(words.join(" ").chars());
+    })?;
+    Ok(date)
+}
+
+// change the line of the open task. fails if the line is not the task anymore.
+fn edit_task(root: &Path, task: &Task, edit: impl FnOnce(&mut Vec<char>)) -> io::Result<()> {
+    let path = root.join(&task.path);
+    let content = fs::read_to_string(&path)?;
+    let mut lines = content
+        .split_inclusive('\n')
+        .map(String::from)
+        .collect::<Vec<String>>();
+    let changed = || io::Error::other(format!("the task has changed: {}", task.text));
+    let Some(line) = lines.get_mut(task.line) else {
+        return Err(changed());
+    };
+    let body = line.trim_end_matches(['\r', '\n']);
+    let ending = line[body.len()..].to_string();
+    let mut chars = body.chars().collect::<Vec<char>>();
+    let text = parse_item(&chars)
+        .filter(|item| item.checkbox == Some(false))
+        .map(|item| chars[item.content..].iter().collect::<String>());
+    if text.as_deref().map(str::trim) != Some(task.text.as_str()) {
+        return Err(changed());
+    }
+    edit(&mut chars);
+    *line = chars.into_iter().collect::<String>() + &ending;
+    fs::write(&path, lines.concat())
+}
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::time::DateTime;
+
+// ノートのテンプレート。テンプレートのディレクトリにあるファイルを新しいノートの中身にする。
+// {{date}} {{time}} と書式つきの {{date:%Y/%m/%d}}、{{title}} を置き換え、
+// {{cursor}} は消して、そこにカーソルを置く。
+// それ以外の {{name}} は変数で、ノートを作るときに値を聞く。
+
+pub const DEFAULT_DIR: &str = ".note/templates";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expanded {
+    pub text: String,
+    pub cursor: Option<(usize, usize)>, // char index and line of {{cursor}}
+}
+
+// files in the directory, by name
+pub fn list_templates(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut files = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            !p.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .starts_with('.')
+        })
+        .collect::<Vec<PathBuf>>();
+    files.sort();
+    files
+}
+
+// names of the placeholders which need a value, in order of appearance
+pub fn variables(template: &str) -> Vec<String> {
+    let now = DateTime::now();
+    let mut names: Vec<String> = vec![];
+    for key in placeholders(template) {
+        if key != "cursor" && builtin(&key, &now).is_none() && !names.contains(&key) {
+            names.push(key);
+        }
+    }
+    names
+}
+
+// replace the placeholders with the time and the values of the variables
+pub fn expand(template: &str, now: &DateTime, values: &[(String, String)]) -> Expanded {
+    expand_with(template, &|key| {
+        values
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.clone())
+            .or_else(|| builtin(key, now))
+    })
+}
+
+// replace the placeholders by the lookup. unknown ones are left as they are.
+pub fn expand_with(template: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Expanded {
+    let mut text = String::new();
+    let mut cursor = None;
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        let key = rest[start + 2..start + len].trim();
+        if key == "cursor" {
+            if cursor.is_none() {
+                let line = text.rsplit('\n').next().unwrap_or("");
+                cursor = Some((line.chars().count(), text.matches('\n').count()));
+            }
+        } else {
+            match lookup(key) {
+                Some(value) => text.push_str(&value),
+                None => text.push_str(&rest[start..start + len + 2]),
+            }
+        }
+        rest = &rest[start + len + 2..];
+    }
+    text.push_str(rest);
+    Expanded { text, cursor }
+}
+
+fn placeholders(template: &str) -> Vec<String> {
+    let mut keys = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        keys.push(rest[start + 2..start + len].trim().to_string());
+        rest = &rest[start + len + 2..];
+    }
+    keys
+}
+
+// {{date}}, {{time}} and {{date:format}}
+pub fn builtin(key: &str, now: &DateTime) -> Option<String> {
+    match key.split_once(':') {
+        Some(("date" | "time", format)) => Some(now.format(format)),
+        _ => match key {
+            "date" => Some(now.format("%Y-%m-%d")),
+            "time" => Some(now.format("%H:%M")),
+            _ => None,
+        },
+    }
+}
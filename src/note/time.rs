@@ -0,0 +1,172 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// ローカル時刻。タイムゾーンの計算はlibcのlocaltime_rに任せる。
+// ノートのファイル名やタイムスタンプに使う。
+// 日付の足し算は暦の上で行うので、夏時間の切り替わりで日がずれることはない。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: i32,
+    pub month: u32, // 1-12
+    pub day: u32,   // 1-31
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub weekday: u32, // 0 is sunday
+}
+
+const WEEKDAYS: &[&str] = &["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+impl DateTime {
+    pub fn now() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        DateTime::from_unix(secs)
+    }
+
+    // unix time to local time
+    pub fn from_unix(secs: i64) -> Self {
+        let time = secs as libc::time_t;
+        // SAFETY: tm is plain data and localtime_r only writes into it
+        let tm = unsafe {
+            let mut tm = std::mem::zeroed::<libc::tm>();
+            libc::localtime_r(&time, &mut tm);
+            tm
+        };
+        DateTime {
+            year: tm.tm_year + 1900,
+            month: tm.tm_mon as u32 + 1,
+            day: tm.tm_mday as u32,
+            hour: tm.tm_hour as u32,
+            minute: tm.tm_min as u32,
+            second: tm.tm_sec as u32,
+            weekday: tm.tm_wday as u32,
+        }
+    }
+
+    // midnight of the date. None for a date which does not exist.
+    pub fn date(year: i32, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        let days = days_from_civil(year, month, day);
+        Some(DateTime {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            weekday: (days + 4).rem_euclid(7) as u32,
+        })
+    }
+
+    // midnight of today
+    pub fn today() -> Self {
+        let now = DateTime::now();
+        DateTime::date(now.year, now.month, now.day).unwrap_or(now)
+    }
+
+    // "2024-01-31"
+    pub fn parse_date(text: &str) -> Option<Self> {
+        let mut parts = text.trim().splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        DateTime::date(year, month, day)
+    }
+
+    // the same time some days later (or earlier with a negative number)
+    pub fn add_days(&self, days: i64) -> Self {
+        let (year, month, day) =
+            civil_from_days(days_from_civil(self.year, self.month, self.day) + days);
+        DateTime {
+            year,
+            month,
+            day,
+            weekday: (self.weekday as i64 + days).rem_euclid(7) as u32,
+            ..*self
+        }
+    }
+
+    // the first day of the week, which starts on the weekday (0 is sunday)
+    pub fn week_start(&self, first_weekday: u32) -> Self {
+        let offset = (self.weekday + 7 - first_weekday % 7) % 7;
+        self.add_days(-(offset as i64))
+    }
+
+    // week of the year. days before the first week start are in week 0, like %U and %W.
+    pub fn week_number(&self, first_weekday: u32) -> u32 {
+        let yday = (days_from_civil(self.year, self.month, self.day)
+            - days_from_civil(self.year, 1, 1)) as u32;
+        let offset = (self.weekday + 7 - first_weekday % 7) % 7;
+        (yday + 7 - offset) / 7
+    }
+
+    // %Y %m %d %H %M %S %a (weekday) and %%
+    pub fn format(&self, pattern: &str) -> String {
+        let mut result = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => result.push_str(&format!("{:04}", self.year)),
+                Some('m') => result.push_str(&format!("{:02}", self.month)),
+                Some('d') => result.push_str(&format!("{:02}", self.day)),
+                Some('H') => result.push_str(&format!("{:02}", self.hour)),
+                Some('M') => result.push_str(&format!("{:02}", self.minute)),
+                Some('S') => result.push_str(&format!("{:02}", self.second)),
+                Some('a') => result.push_str(WEEKDAYS[self.weekday as usize % 7]),
+                Some('%') => result.push('%'),
+                Some(c) => {
+                    result.push('%');
+                    result.push(c);
+                }
+                None => result.push('%'),
+            }
+        }
+        result
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days since 1970-01-01 (from Howard Hinnant's civil calendar algorithms)
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year } as i64;
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month, day)
+}
@@ -0,0 +1,475 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::daily::Daily;
+use super::front_matter::{self, note_title};
+use super::link::parse_links;
+use super::rename::{self, RenamePlan};
+use super::tag::{note_tags, tag_ancestors, tag_matches};
+use super::task::fix_due_dates;
+use super::time::DateTime;
+use super::walk::walk_note_paths;
+
+// Vaultはノートを置くルートディレクトリ。
+// ノートのタイトル、タグ、別名や更新時刻は .note/cache に保存しておき、
+// 起動時には更新時刻かサイズが変わったファイルだけを読み直す。
+
+pub const META_DIR: &str = ".note";
+const CACHE_FILE: &str = "cache";
+const CACHE_HEADER: &str = "note-cache 4";
+const LIST_SEPARATOR: char = '\u{1f}'; // of tags, aliases and links in the cache
+
+// how a new note is named
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NameStyle {
+    Timestamp, // 20240101-093000.md
+    Slug,      // slug of the title, or the timestamp
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteMeta {
+    pub path: PathBuf, // relative to the root
+    pub title: String,
+    pub tags: Vec<String>,    // in the front matter and the text
+    pub aliases: Vec<String>, // other names of the note, in the front matter
+    pub links: Vec<String>,   // targets of [[links]] in the note
+    pub modified: u64,        // unix time
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Vault {
+    pub root: PathBuf,
+    pub extensions: Vec<String>,
+    pub name_style: NameStyle,
+    notes: Vec<NoteMeta>,
+}
+
+impl Vault {
+    // create the root if needed and load the metadata
+    pub fn open(root: &Path, extensions: &[String], name_style: NameStyle) -> io::Result<Self> {
+        fs::create_dir_all(root.join(META_DIR))?;
+        let mut vault = Vault {
+            root: root.to_path_buf(),
+            extensions: extensions.to_vec(),
+            name_style,
+            notes: vec![],
+        };
+        vault.notes = vault.load_cache();
+        vault.refresh()?;
+        Ok(vault)
+    }
+
+    pub fn notes(&self) -> &[NoteMeta] {
+        &self.notes
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&NoteMeta> {
+        let path = self.relative(path);
+        self.notes.iter().find(|n| n.path == path)
+    }
+
+    // tags with the number of notes which have them, by name.
+    // a parent of nested tags is listed with the notes of its children.
+    pub fn tags(&self) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for note in self.notes.iter() {
+            let mut tags = note
+                .tags
+                .iter()
+                .flat_map(|t| tag_ancestors(t))
+                .collect::<Vec<String>>();
+            tags.sort();
+            tags.dedup();
+            for tag in tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+        counts.into_iter().collect()
+    }
+
+    // notes which have the tag or its nested tags
+    pub fn tagged(&self, tag: &str) -> Vec<&NoteMeta> {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        self.notes
+            .iter()
+            .filter(|n| n.tags.iter().any(|t| tag_matches(t, &tag)))
+            .collect()
+    }
+
+    // lowercased names which a [[link]] can use for the note
+    pub fn link_names(&self, note: &NoteMeta) -> Vec<String> {
+        let mut names = vec![
+            note.path.to_string_lossy().to_string(),
+            note.path.with_extension("").to_string_lossy().to_string(),
+            note.title.clone(),
+        ];
+        names.extend(
+            note.path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string()),
+        );
+        names.extend(note.aliases.iter().cloned());
+        let mut names = names
+            .into_iter()
+            .map(|n| n.to_lowercase())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // other notes which have links to the note
+    pub fn linking(&self, path: &Path) -> Vec<&NoteMeta> {
+        let Some(note) = self.get(path) else {
+            return vec![];
+        };
+        let names = self.link_names(note);
+        self.notes
+            .iter()
+            .filter(|n| n.path != note.path)
+            .filter(|n| n.links.iter().any(|l| names.contains(&link_key(l))))
+            .collect()
+    }
+
+    // the most recently modified note
+    pub fn latest(&self) -> Option<&NoteMeta> {
+        self.notes.iter().max_by_key(|n| n.modified)
+    }
+
+    // notes called by the name: the path, the path without extension,
+    // the file name or the title and aliases, in this order
+    pub fn find(&self, name: &str) -> Vec<&NoteMeta> {
+        let name = name.trim_start_matches("./");
+        let tiers: [&dyn Fn(&NoteMeta) -> bool; 4] = [
+            &|n| n.path == Path::new(name),
+            &|n| n.path.with_extension("") == Path::new(name),
+            &|n| {
+                n.path
+                    .file_stem()
+                    .is_some_and(|s| s.to_string_lossy() == name)
+            },
+            &|n| {
+                let name = name.to_lowercase();
+                n.title.to_lowercase() == name || n.aliases.iter().any(|a| a.to_lowercase() == name)
+            },
+        ];
+        for tier in tiers {
+            let found = self.notes.iter().filter(|n| tier(n)).collect::<Vec<_>>();
+            if !found.is_empty() {
+                return found;
+            }
+        }
+        vec![]
+    }
+
+    pub fn remove(&mut self, path: &Path) -> io::Result<()> {
+        let path = self.relative(path);
+        fs::remove_file(self.root.join(&path))?;
+        self.notes.retain(|n| n.path != path);
+        self.save_cache()
+    }
+
+    // the links to rewrite when a note or a folder is moved. nothing is written.
+    // the extension of the note is added when to has none.
+    pub fn plan_rename(&self, from: &Path, to: &Path) -> io::Result<RenamePlan> {
+        rename::plan_rename(self, from, to)
+    }
+
+    // rewrite the links and move the file or the folder, all or nothing.
+    // returns the new absolute path.
+    pub fn apply_rename(&mut self, plan: &RenamePlan) -> io::Result<PathBuf> {
+        rename::apply_rename(&self.root, plan)?;
+        self.refresh()?;
+        Ok(self.root.join(&plan.to))
+    }
+
+    // scan the root. only changed files are read again.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        let mut cached = self
+            .notes
+            .drain(..)
+            .map(|n| (n.path.clone(), n))
+            .collect::<HashMap<PathBuf, NoteMeta>>();
+        let mut notes = vec![];
+        for path in walk_note_paths(&self.root, &self.extensions) {
+            let (modified, size) = file_stamp(&self.root.join(&path));
+            match cached.remove(&path) {
+                Some(meta) if meta.modified == modified && meta.size == size => notes.push(meta),
+                _ => notes.push(self.read_meta(&path)),
+            }
+        }
+        self.notes = notes;
+        self.save_cache()
+    }
+
+    // read the note again after it is saved
+    pub fn update(&mut self, path: &Path) -> io::Result<()> {
+        let path = self.relative(path);
+        let meta = self.read_meta(&path);
+        match self.notes.iter_mut().find(|n| n.path == path) {
+            Some(note) => *note = meta,
+            None => self.notes.push(meta),
+        }
+        self.save_cache()
+    }
+
+    // absolute path for a new note with the title. without a title it is named by the time.
+    pub fn new_note_path(&self, title: &str) -> PathBuf {
+        let now = DateTime::now();
+        let timestamp = now.format("%Y%m%d-%H%M%S");
+        let name = match self.name_style {
+            NameStyle::Timestamp => timestamp,
+            NameStyle::Slug => {
+                let slug = slugify(title);
+                if slug.is_empty() {
+                    timestamp
+                } else {
+                    slug
+                }
+            }
+        };
+        self.unique_path(&name)
+    }
+
+    // write a note which only has the heading
+    pub fn create_note(&mut self, title: &str) -> io::Result<PathBuf> {
+        self.create_note_with(title, &format!("# {}\n", title))
+    }
+
+    // write a new note named from the title, which the content may not have
+    pub fn create_note_with(&mut self, title: &str, content: &str) -> io::Result<PathBuf> {
+        let path = self.new_note_path(title);
+        fs::write(&path, content)?;
+        self.update(&path)?;
+        Ok(path)
+    }
+
+    // the note of the day. an existing note with another extension is used.
+    pub fn daily_path(&self, daily: &Daily, date: &DateTime) -> PathBuf {
+        let name = daily.name(date);
+        let found = self
+            .notes
+            .iter()
+            .find(|n| n.path.with_extension("") == Path::new(&name));
+        match found {
+            Some(note) => self.root.join(&note.path),
+            None => {
+                let ext = self.extensions.first().map(|e| e.as_str()).unwrap_or("md");
+                self.root.join(format!("{}.{}", name, ext))
+            }
+        }
+    }
+
+    // the note of the day, written from the template if missing
+    pub fn create_daily(&mut self, daily: &Daily, date: &DateTime) -> io::Result<PathBuf> {
+        let path = self.daily_path(daily, date);
+        if !path.exists() {
+            let content = daily.content(date, &self.root)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)?;
+            self.update(&path)?;
+        }
+        Ok(path)
+    }
+
+    // append a timestamped item to the note, which is created if missing
+    pub fn capture(&mut self, target: &str, text: &str, now: &DateTime) -> io::Result<PathBuf> {
+        let found = self.find(target);
+        let path = match found.as_slice() {
+            [note] => self.root.join(&note.path),
+            [] => {
+                let path = self.unique_path(&slugify(target));
+                fs::write(&path, format!("# {}\n\n", target))?;
+                path
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is ambiguous", target),
+                ))
+            }
+        };
+        let mut content = fs::read_to_string(&path)?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&fix_due_dates(&capture_item(text, now), now));
+        fs::write(&path, content)?;
+        self.update(&path)?;
+        Ok(path)
+    }
+
+    // "name.md", "name-2.md", ...
+    fn unique_path(&self, name: &str) -> PathBuf {
+        let ext = self.extensions.first().map(|e| e.as_str()).unwrap_or("md");
+        let mut path = self.root.join(format!("{}.{}", name, ext));
+        let mut n = 2;
+        while path.exists() {
+            path = self.root.join(format!("{}-{}.{}", name, n, ext));
+            n += 1;
+        }
+        path
+    }
+
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.root).unwrap_or(path).to_path_buf()
+    }
+
+    fn read_meta(&self, path: &Path) -> NoteMeta {
+        let full = self.root.join(path);
+        let (modified, size) = file_stamp(&full);
+        let content = fs::read(&full)
+            .map(|b| {
+                String::from_utf8_lossy(&b)
+                    .trim_start_matches('\u{feff}')
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let lines = content.lines().collect::<Vec<&str>>();
+        let front = front_matter::parse(&lines);
+        let title = note_title(&lines, front.as_ref()).unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+        NoteMeta {
+            path: path.to_path_buf(),
+            title,
+            tags: note_tags(front.as_ref(), &content),
+            aliases: front.map(|f| f.aliases).unwrap_or_default(),
+            links: link_targets(&content),
+            modified,
+            size,
+        }
+    }
+
+    // one note per line: modified, size, path, tags, aliases, links and title separated by tabs
+    fn load_cache(&self) -> Vec<NoteMeta> {
+        let Ok(content) = fs::read_to_string(self.root.join(META_DIR).join(CACHE_FILE)) else {
+            return vec![];
+        };
+        let mut lines = content.lines();
+        if lines.next() != Some(CACHE_HEADER) {
+            return vec![];
+        }
+        lines
+            .filter_map(|line| {
+                let mut fields = line.splitn(7, '\t');
+                Some(NoteMeta {
+                    modified: fields.next()?.parse().ok()?,
+                    size: fields.next()?.parse().ok()?,
+                    path: PathBuf::from(fields.next()?),
+                    tags: split_list(fields.next()?),
+                    aliases: split_list(fields.next()?),
+                    links: split_list(fields.next()?),
+                    title: fields.next()?.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    fn save_cache(&self) -> io::Result<()> {
+        let mut content = format!("{}\n", CACHE_HEADER);
+        for note in self.notes.iter() {
+            content.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                note.modified,
+                note.size,
+                note.path.to_string_lossy(),
+                join_list(&note.tags),
+                join_list(&note.aliases),
+                join_list(&note.links),
+                note.title.replace(['\t', '\n'], " ")
+            ));
+        }
+        // write and rename, so that a crash does not leave half a cache
+        let dir = self.root.join(META_DIR);
+        let tmp = dir.join(format!("{}.tmp", CACHE_FILE));
+        fs::write(&tmp, content)?;
+        fs::rename(tmp, dir.join(CACHE_FILE))
+    }
+}
+
+// "- 2024-01-01 09:30 first line" and the other lines indented under it
+pub fn capture_item(text: &str, now: &DateTime) -> String {
+    let mut item = String::new();
+    for (i, line) in text.trim().lines().enumerate() {
+        if i == 0 {
+            item.push_str(&format!("- {} {}\n", now.format("%Y-%m-%d %H:%M"), line));
+        } else {
+            item.push_str(format!("  {}", line).trim_end());
+            item.push('\n');
+        }
+    }
+    item
+}
+
+// a link target as it is compared with link_names
+pub fn link_key(target: &str) -> String {
+    target.trim_start_matches("./").to_lowercase()
+}
+
+// targets of the links without duplicates
+fn link_targets(content: &str) -> Vec<String> {
+    let mut targets: Vec<String> = vec![];
+    for link in parse_links(content) {
+        if !link.target.is_empty() && !targets.contains(&link.target) {
+            targets.push(link.target);
+        }
+    }
+    targets
+}
+
+fn join_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|i| i.replace(['\t', '\n', LIST_SEPARATOR], " "))
+        .collect::<Vec<String>>()
+        .join(&LIST_SEPARATOR.to_string())
+}
+
+fn split_list(field: &str) -> Vec<String> {
+    field
+        .split(LIST_SEPARATOR)
+        .filter(|i| !i.is_empty())
+        .map(|i| i.to_string())
+        .collect()
+}
+
+fn file_stamp(path: &Path) -> (u64, u64) {
+    match fs::metadata(path) {
+        Ok(meta) => {
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            (modified, meta.len())
+        }
+        Err(_) => (0, 0),
+    }
+}
+
+// "Hello, World!" -> "hello-world". letters of other scripts are kept.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.chars()
+        .take(60)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
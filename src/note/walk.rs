@@ -0,0 +1,264 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use super::front_matter::read_head;
+
+// ノートディレクトリの中のノートファイルを列挙する。
+// 各ディレクトリの .gitignore と .ignore を読んで、.gitignore と同じ書式で除外する。
+// 隠しファイル(.から始まる)は常に除外する。
+
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+const BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteFile {
+    pub path: PathBuf, // relative to the root
+    pub title: String,
+}
+
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    base: PathBuf, // dir of the ignore file, relative to the root
+    pattern: Vec<char>,
+    negate: bool,
+    dir_only: bool,
+    anchored: bool, // match from the base dir, not only the file name
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    rules: Vec<IgnoreRule>,
+}
+
+impl Ignore {
+    // add rules of an ignore file. base is relative to the root.
+    pub fn add_rules(&mut self, base: &Path, content: &str) {
+        for line in content.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negate, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let line = line.trim_start_matches('/');
+            if line.is_empty() {
+                continue;
+            }
+            self.rules.push(IgnoreRule {
+                base: base.to_path_buf(),
+                pattern: line.chars().collect(),
+                negate,
+                dir_only,
+                anchored,
+            });
+        }
+    }
+
+    // path is relative to the root. the last matched rule wins.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for rule in self.rules.iter() {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let Ok(rel) = path.strip_prefix(&rule.base) else {
+                continue;
+            };
+            let target = if rule.anchored {
+                rel.to_string_lossy().replace('\\', "/")
+            } else {
+                rel.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            if glob_match(&rule.pattern, &target.chars().collect::<Vec<char>>()) {
+                ignored = !rule.negate;
+            }
+        }
+        ignored
+    }
+}
+
+// glob with *, ?, ** and [abc] / [a-z] / [!abc]
+pub fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // "**/" matches zero or more dirs, "**" matches everything
+            let rest = if pattern.get(2) == Some(&'/') {
+                &pattern[3..]
+            } else {
+                &pattern[2..]
+            };
+            (0..=text.len()).any(|i| {
+                (i == 0 || text[i - 1] == '/' || pattern.get(2) != Some(&'/'))
+                    && glob_match(rest, &text[i..])
+            })
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_match(rest, &text[i..]) {
+                    return true;
+                }
+                if i < text.len() && text[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !text.is_empty() && text[0] != '/' && glob_match(&pattern[1..], &text[1..]),
+        Some('[') => {
+            let Some(end) = pattern.iter().skip(1).position(|c| *c == ']') else {
+                return !text.is_empty() && text[0] == '[' && glob_match(&pattern[1..], &text[1..]);
+            };
+            if text.is_empty() {
+                return false;
+            }
+            let class = &pattern[1..end + 1];
+            let (negate, class) = match class.first() {
+                Some('!') | Some('^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= text[0] && text[0] <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == text[0];
+                    i += 1;
+                }
+            }
+            matched != negate && glob_match(&pattern[end + 2..], &text[1..])
+        }
+        Some('\\') if pattern.len() > 1 => {
+            !text.is_empty() && text[0] == pattern[1] && glob_match(&pattern[2..], &text[1..])
+        }
+        Some(c) => !text.is_empty() && text[0] == *c && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+pub fn is_note_file(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .map(|e| extensions.iter().any(|x| e.to_string_lossy() == *x))
+        .unwrap_or(false)
+}
+
+// title in the front matter, the first heading or the file name
+pub fn read_title(path: &Path) -> String {
+    read_head(path).0
+}
+
+// paths of all note files under the root, without reading them.
+pub fn walk_note_paths(root: &Path, extensions: &[String]) -> Vec<PathBuf> {
+    let mut paths = vec![];
+    walk_dir(
+        root,
+        Path::new(""),
+        &Ignore::default(),
+        extensions,
+        false,
+        &mut |batch| {
+            paths.extend(batch.drain(..).map(|f| f.path));
+            true
+        },
+    );
+    paths
+}
+
+// list note files in a background thread. files are sent in batches,
+// and the walk stops when the receiver is dropped.
+pub fn walk_notes_async(root: &Path, extensions: &[String], tx: Sender<Vec<NoteFile>>) {
+    let root = root.to_path_buf();
+    let extensions = extensions.to_vec();
+    std::thread::spawn(move || {
+        let mut batch = vec![];
+        let running = walk_dir(
+            &root,
+            Path::new(""),
+            &Ignore::default(),
+            &extensions,
+            true,
+            &mut |files| {
+                batch.append(files);
+                if batch.len() >= BATCH_SIZE {
+                    return tx.send(std::mem::take(&mut batch)).is_ok();
+                }
+                true
+            },
+        );
+        if running {
+            let _ = tx.send(batch);
+        }
+    });
+}
+
+// returns false when the callback stopped the walk
+fn walk_dir(
+    root: &Path,
+    rel: &Path,
+    ignore: &Ignore,
+    extensions: &[String],
+    read_titles: bool,
+    found: &mut dyn FnMut(&mut Vec<NoteFile>) -> bool,
+) -> bool {
+    let dir = root.join(rel);
+    let mut ignore = ignore.clone();
+    for name in IGNORE_FILES {
+        if let Ok(content) = fs::read_to_string(dir.join(name)) {
+            ignore.add_rules(rel, &content);
+        }
+    }
+    let Ok(read_dir) = fs::read_dir(&dir) else {
+        return true;
+    };
+    let mut entries = read_dir
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .map(|e| {
+            (
+                e.file_name(),
+                e.file_type().map(|t| t.is_dir()).unwrap_or(false),
+            )
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let mut files = vec![];
+    let mut dirs = vec![];
+    for (name, is_dir) in entries {
+        let path = rel.join(&name);
+        if ignore.is_ignored(&path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            dirs.push(path);
+        } else if is_note_file(&path, extensions) {
+            let title = if read_titles {
+                read_title(&root.join(&path))
+            } else {
+                String::new()
+            };
+            files.push(NoteFile { path, title });
+        }
+    }
+    if !files.is_empty() && !found(&mut files) {
+        return false;
+    }
+    for path in dirs {
+        if !walk_dir(root, &path, &ignore, extensions, read_titles, found) {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,120 @@
+// fuzzy matching for pickers.
+// the query chars must appear in the target in order (case insensitive).
+// a match gets more score when the chars are continuous or at the start of words.
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 10;
+const BONUS_CONSECUTIVE: i64 = 8;
+const PENALTY_GAP: i64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    pub positions: Vec<usize>, // char index of matched chars in the target
+}
+
+pub fn fuzzy_match(query: &str, target: &str) -> Option<FuzzyMatch> {
+    let query = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect::<Vec<char>>();
+    let chars = target.chars().collect::<Vec<char>>();
+    if query.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: vec![],
+        });
+    }
+    let lower = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect::<Vec<char>>();
+    let (m, n) = (query.len(), chars.len());
+    if m > n {
+        return None;
+    }
+    // cheap subsequence check before the dp, most targets fail here
+    let mut rest = query.iter().peekable();
+    for c in lower.iter() {
+        if rest.peek() == Some(&c) {
+            rest.next();
+        }
+    }
+    if rest.peek().is_some() {
+        return None;
+    }
+
+    // score[i][j] : best score when query[i] is matched at target[j]
+    // from[i][j] : position of query[i - 1] for the best score
+    let none = i64::MIN / 2;
+    let mut score = vec![vec![none; n]; m];
+    let mut from = vec![vec![0; n]; m];
+    for i in 0..m {
+        // best (score[i - 1][k] - gap) for k < j, and its k
+        let mut carry = (none, 0);
+        for j in 0..n {
+            if i > 0 && j > 0 {
+                let prev = score[i - 1][j - 1];
+                if carry.0 - PENALTY_GAP < prev {
+                    carry = (prev, j - 1);
+                } else {
+                    carry.0 -= PENALTY_GAP;
+                }
+            }
+            if lower[j] != query[i] {
+                continue;
+            }
+            let bonus = SCORE_MATCH + boundary_bonus(&chars, j);
+            if i == 0 {
+                score[i][j] = bonus;
+                continue;
+            }
+            let consecutive = if j > 0 && score[i - 1][j - 1] > none {
+                score[i - 1][j - 1] + BONUS_CONSECUTIVE
+            } else {
+                none
+            };
+            if consecutive >= carry.0 && consecutive > none {
+                score[i][j] = bonus + consecutive;
+                from[i][j] = j - 1;
+            } else if carry.0 > none {
+                score[i][j] = bonus + carry.0;
+                from[i][j] = carry.1;
+            }
+        }
+    }
+
+    let (mut j, best) = score[m - 1]
+        .iter()
+        .enumerate()
+        .max_by_key(|(j, s)| (**s, -(*j as i64)))
+        .map(|(j, s)| (j, *s))?;
+    if best <= none {
+        return None;
+    }
+    let mut positions = vec![j; m];
+    for i in (1..m).rev() {
+        j = from[i][j];
+        positions[i - 1] = j;
+    }
+    Some(FuzzyMatch {
+        score: best,
+        positions,
+    })
+}
+
+fn boundary_bonus(chars: &[char], j: usize) -> i64 {
+    if j == 0 {
+        return BONUS_BOUNDARY;
+    }
+    let prev = chars[j - 1];
+    let c = chars[j];
+    if matches!(prev, ' ' | '/' | '_' | '-' | '.' | ':' | '#')
+        || (prev.is_lowercase() && c.is_uppercase())
+    {
+        BONUS_BOUNDARY
+    } else {
+        0
+    }
+}
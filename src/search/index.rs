@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use super::query::{parse_query, Field, Query};
+use crate::note::vault::{NoteMeta, Vault, META_DIR};
+
+// 全文検索の転置インデックス。語ごとに、出てくるノートと位置を持つ。
+// 英数字は単語ごと、日本語などは2文字ずつ(bi-gram)に分ける。
+// .note/index にはノートごとの語の一覧を追記していき、
+// 読み込むときに転置インデックスを組み立てる。古い記録が増えたら書き直す。
+// 記録はタブと改行で区切るので、パスの中のタブや改行はエスケープして書く。
+
+const INDEX_FILE: &str = "index";
+const INDEX_HEADER: &str = "note-index 5";
+
+// term -> positions in a note
+type Terms = BTreeMap<String, Vec<u32>>;
+
+#[derive(Debug, Clone)]
+struct Doc {
+    path: PathBuf,
+    modified: u64,
+    size: u64,
+    terms: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub path: PathBuf, // relative to the root
+    pub score: usize,
+    pub line: usize,   // from 0
+    pub column: usize, // char index in the line
+    pub text: String,  // the line
+}
+
+#[derive(Debug, Clone)]
+pub struct Index {
+    root: PathBuf,
+    docs: Vec<Option<Doc>>,
+    ids: HashMap<PathBuf, usize>,
+    terms: BTreeMap<String, HashMap<usize, Vec<u32>>>, // term -> doc -> positions
+    records: usize,                                    // records in the file
+}
+
+impl Index {
+    pub fn open(root: &Path) -> io::Result<Self> {
+        let mut index = Index {
+            root: root.to_path_buf(),
+            docs: vec![],
+            ids: HashMap::new(),
+            terms: BTreeMap::new(),
+            records: 0,
+        };
+        index.load()?;
+        Ok(index)
+    }
+
+    // index changed notes and drop deleted ones
+    pub fn sync(&mut self, vault: &Vault) -> io::Result<()> {
+        let mut log = String::new();
+        for note in vault.notes() {
+            let fresh = self
+                .ids
+                .get(&note.path)
+                .and_then(|id| self.docs[*id].as_ref())
+                .is_some_and(|d| d.modified == note.modified && d.size == note.size);
+            if !fresh {
+                log.push_str(&self.index_note(note));
+            }
+        }
+        let alive = vault
+            .notes()
+            .iter()
+            .map(|n| n.path.clone())
+            .collect::<HashSet<PathBuf>>();
+        let gone = self
+            .ids
+            .keys()
+            .filter(|p| !alive.contains(*p))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        for path in gone {
+            self.remove_doc(&path);
+            log.push_str(&format!("- {}\n", escape_path(&path)));
+            self.records += 1;
+        }
+        self.write_log(&log)
+    }
+
+    // index the note again after it is saved
+    pub fn update(&mut self, note: &NoteMeta) -> io::Result<()> {
+        let log = self.index_note(note);
+        self.write_log(&log)
+    }
+
+    // notes matching the query, best first. files are read to find the line
+    // of the match, so only `limit` notes are returned.
+    pub fn search(&self, input: &str, limit: usize) -> Result<Vec<SearchHit>, String> {
+        let query = parse_query(input)?;
+        let mut scores = self
+            .eval(&query)
+            .into_iter()
+            .filter_map(|(id, score)| Some((self.docs[id].as_ref()?, score)))
+            .collect::<Vec<(&Doc, usize)>>();
+        scores.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.path.cmp(&b.0.path)));
+        let mut needles = vec![];
+        collect_needles(&query, false, &mut needles);
+        Ok(scores
+            .into_iter()
+            .take(limit)
+            .map(|(doc, score)| {
+                let (line, column, text) = self.locate(&doc.path, &needles);
+                SearchHit {
+                    path: doc.path.clone(),
+                    score,
+                    line,
+                    column,
+                    text,
+                }
+            })
+            .collect())
+    }
+
+    // matched docs and their scores
+    fn eval(&self, query: &Query) -> HashMap<usize, usize> {
+        match query {
+            Query::Phrase { field, tokens, .. } => self.phrase(*field, tokens),
+            Query::Not(inner) => {
+                let excluded = self.eval(inner);
+                self.ids
+                    .values()
+                    .filter(|id| !excluded.contains_key(*id))
+                    .map(|id| (*id, 0))
+                    .collect()
+            }
+            Query::And(list) => {
+                let mut result = self.eval(&list[0]);
+                for query in list.iter().skip(1) {
+                    let found = self.eval(query);
+                    result = result
+                        .into_iter()
+                        .filter_map(|(id, score)| found.get(&id).map(|s| (id, score + s)))
+                        .collect();
+                }
+                result
+            }
+            Query::Or(list) => {
+                let mut result = HashMap::new();
+                for query in list {
+                    for (id, score) in self.eval(query) {
+                        *result.entry(id).or_insert(0) += score;
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    // docs with the tokens in a row, and the count of the matches
+    fn phrase(&self, field: Field, tokens: &[(String, bool)]) -> HashMap<usize, usize> {
+        let postings = tokens
+            .iter()
+            .map(|(token, prefix)| self.postings(field, token, *prefix))
+            .collect::<Vec<_>>();
+        let mut result = HashMap::new();
+        for (id, starts) in postings[0].iter() {
+            let count = starts
+                .iter()
+                .filter(|start| {
+                    postings.iter().enumerate().skip(1).all(|(i, p)| {
+                        p.get(id)
+                            .is_some_and(|positions| positions.contains(&(**start + i as u32)))
+                    })
+                })
+                .count();
+            if count > 0 {
+                result.insert(*id, count);
+            }
+        }
+        result
+    }
+
+    fn postings(&self, field: Field, token: &str, prefix: bool) -> HashMap<usize, HashSet<u32>> {
+        let key = format!("{}{}", field.key(), token);
+        let mut result: HashMap<usize, HashSet<u32>> = HashMap::new();
+        let mut add = |postings: &HashMap<usize, Vec<u32>>| {
+            for (id, positions) in postings {
+                result.entry(*id).or_default().extend(positions);
+            }
+        };
+        if prefix {
+            for (_, postings) in self
+                .terms
+                .range(key.clone()..)
+                .take_while(|(t, _)| t.starts_with(&key))
+            {
+                add(postings);
+            }
+        } else {
+            if let Some(postings) = self.terms.get(&key) {
+                add(postings);
+            }
+            // a tag matches its nested tags
+            if field == Field::Tag {
+                let nested = format!("{}/", key);
+                for (_, postings) in self
+                    .terms
+                    .range(nested.clone()..)
+                    .take_while(|(t, _)| t.starts_with(&nested))
+                {
+                    add(postings);
+                }
+            }
+        }
+        result
+    }
+
+    // first line which contains one of the needles
+    fn locate(&self, path: &Path, needles: &[String]) -> (usize, usize, String) {
+        let content = fs::read_to_string(self.root.join(path)).unwrap_or_default();
+        for needle in needles {
+            for (i, line) in content.lines().enumerate() {
+                // lowercase can change the length, so keep the column of each lowered char
+                let mut lower = String::new();
+                let mut columns = vec![];
+                for (column, c) in line.chars().enumerate() {
+                    for l in c.to_lowercase() {
+                        lower.push(l);
+                        columns.push(column);
+                    }
+                }
+                if let Some(byte) = lower.find(needle.as_str()) {
+                    let column = columns[lower[..byte].chars().count()];
+                    return (i, column, line.to_string());
+                }
+            }
+        }
+        let first = content.lines().next().unwrap_or_default();
+        (0, 0, first.to_string())
+    }
+
+    // read and index the note. returns the record for the file.
+    fn index_note(&mut self, note: &NoteMeta) -> String {
+        let content = fs::read_to_string(self.root.join(&note.path)).unwrap_or_default();
+        let terms = doc_terms(note, &content);
+        self.remove_doc(&note.path);
+        let mut record = format!(
+            "+ {}\t{}\t{}\n",
+            escape_path(&note.path),
+            note.modified,
+            note.size
+        );
+        for (term, positions) in terms.iter() {
+            let positions = positions
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>();
+            record.push_str(&format!("{}\t{}\n", term, positions.join(",")));
+        }
+        self.add_doc(note.path.clone(), note.modified, note.size, terms);
+        self.records += 1;
+        record
+    }
+
+    fn add_doc(&mut self, path: PathBuf, modified: u64, size: u64, terms: Terms) {
+        let id = self.docs.len();
+        let mut names = vec![];
+        for (term, positions) in terms {
+            self.terms
+                .entry(term.clone())
+                .or_default()
+                .insert(id, positions);
+            names.push(term);
+        }
+        self.docs.push(Some(Doc {
+            path: path.clone(),
+            modified,
+            size,
+            terms: names,
+        }));
+        self.ids.insert(path, id);
+    }
+
+    fn remove_doc(&mut self, path: &Path) {
+        let Some(id) = self.ids.remove(path) else {
+            return;
+        };
+        if let Some(doc) = self.docs[id].take() {
+            for term in doc.terms {
+                if let Some(postings) = self.terms.get_mut(&term) {
+                    postings.remove(&id);
+                    if postings.is_empty() {
+                        self.terms.remove(&term);
+                    }
+                }
+            }
+        }
+    }
+
+    fn file(&self) -> PathBuf {
+        self.root.join(META_DIR).join(INDEX_FILE)
+    }
+
+    fn load(&mut self) -> io::Result<()> {
+        let content = match fs::read_to_string(self.file()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let mut lines = content.lines();
+        if lines.next() != Some(INDEX_HEADER) {
+            return Ok(()); // built again by sync
+        }
+        let mut current: Option<(PathBuf, u64, u64, Terms)> = None;
+        for line in lines {
+            if let Some(rest) = line.strip_prefix("+ ") {
+                if let Some((path, modified, size, terms)) = current.take() {
+                    self.add_doc(path, modified, size, terms);
+                }
+                let mut fields = rest.rsplitn(3, '\t');
+                let size = fields.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                let modified = fields.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                let path = unescape_path(fields.next().unwrap_or_default());
+                self.remove_doc(&path);
+                current = Some((path, modified, size, BTreeMap::new()));
+                self.records += 1;
+            } else if let Some(path) = line.strip_prefix("- ") {
+                if let Some((path, modified, size, terms)) = current.take() {
+                    self.add_doc(path, modified, size, terms);
+                }
+                self.remove_doc(&unescape_path(path));
+                self.records += 1;
+            } else if let (Some((_, _, _, terms)), Some((term, positions))) =
+                (current.as_mut(), line.split_once('\t'))
+            {
+                let positions = positions
+                    .split(',')
+                    .filter_map(|p| p.parse().ok())
+                    .collect();
+                terms.insert(term.to_string(), positions);
+            }
+        }
+        if let Some((path, modified, size, terms)) = current.take() {
+            self.add_doc(path, modified, size, terms);
+        }
+        Ok(())
+    }
+
+    // append the records. the file is written again when most records are old.
+    fn write_log(&mut self, log: &str) -> io::Result<()> {
+        if self.records > self.ids.len() * 2 + 100 {
+            return self.compact();
+        }
+        if log.is_empty() {
+            return Ok(());
+        }
+        let path = self.file();
+        let is_new = !path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if is_new {
+            writeln!(file, "{}", INDEX_HEADER)?;
+        }
+        file.write_all(log.as_bytes())
+    }
+
+    fn compact(&mut self) -> io::Result<()> {
+        let mut content = format!("{}\n", INDEX_HEADER);
+        let mut docs = self.docs.iter().flatten().collect::<Vec<&Doc>>();
+        docs.sort_by(|a, b| a.path.cmp(&b.path));
+        for doc in docs.iter() {
+            let id = self.ids[&doc.path];
+            content.push_str(&format!(
+                "+ {}\t{}\t{}\n",
+                escape_path(&doc.path),
+                doc.modified,
+                doc.size
+            ));
+            for term in doc.terms.iter() {
+                let positions = self.terms[term][&id]
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>();
+                content.push_str(&format!("{}\t{}\n", term, positions.join(",")));
+            }
+        }
+        let tmp = self.file().with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(tmp, self.file())?;
+        self.records = docs.len();
+        Ok(())
+    }
+}
+
+// the path for a record, without the tabs and line breaks which separate records
+fn escape_path(path: &Path) -> String {
+    let mut escaped = String::new();
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_path(text: &str) -> PathBuf {
+    let mut path = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => path.push('\t'),
+            Some('n') => path.push('\n'),
+            Some('r') => path.push('\r'),
+            Some(c) => path.push(c),
+            None => path.push('\\'),
+        }
+    }
+    PathBuf::from(path)
+}
+
+// what is searched by the plain words of the query
+fn collect_needles(query: &Query, negated: bool, needles: &mut Vec<String>) {
+    match query {
+        Query::Phrase {
+            field: Field::Body,
+            text,
+            tokens,
+        } if !negated => {
+            needles.push(text.clone());
+            needles.push(tokens[0].0.clone());
+        }
+        Query::Phrase { .. } => {}
+        Query::Not(inner) => collect_needles(inner, !negated, needles),
+        Query::And(list) | Query::Or(list) => {
+            for query in list {
+                collect_needles(query, negated, needles);
+            }
+        }
+    }
+}
+
+// terms of a note with their positions. fields have their own positions.
+// aliases are searched as titles.
+fn doc_terms(note: &NoteMeta, content: &str) -> Terms {
+    let mut terms = Terms::new();
+    let mut add = |field: Field, tokens: Vec<String>| {
+        for (i, token) in tokens.into_iter().enumerate() {
+            terms
+                .entry(format!("{}{}", field.key(), token))
+                .or_default()
+                .push(i as u32);
+        }
+    };
+    add(Field::Body, tokenize(content));
+    let mut titles = vec![note.title.clone()];
+    titles.extend(note.aliases.iter().cloned());
+    add(Field::Title, tokenize(&titles.join("\n")));
+    add(Field::Path, tokenize(&note.path.to_string_lossy()));
+    add(Field::Tag, note.tags.clone());
+    terms
+}
+
+// scripts without spaces between words
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30ff     // hiragana, katakana
+        | 0x3400..=0x4dbf   // cjk extension a
+        | 0x4e00..=0x9fff   // cjk unified ideographs
+        | 0xac00..=0xd7af   // hangul
+        | 0xf900..=0xfaff   // cjk compatibility ideographs
+        | 0xff66..=0xff9f) // halfwidth katakana
+}
+
+// words in lowercase, and bi-grams of cjk runs. the last char of a cjk run is
+// also a token, so that a query of one char can be matched as a prefix.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    for (run, cjk) in split_runs(text) {
+        if !cjk {
+            tokens.push(run.into_iter().collect());
+            continue;
+        }
+        for pair in run.windows(2) {
+            tokens.push(pair.iter().collect());
+        }
+        tokens.push(run[run.len() - 1].to_string());
+    }
+    tokens
+}
+
+// tokens of a query. a single cjk char is matched as a prefix.
+pub fn tokenize_query(text: &str) -> Vec<(String, bool)> {
+    let mut tokens = vec![];
+    for (run, cjk) in split_runs(text) {
+        if !cjk {
+            tokens.push((run.into_iter().collect(), false));
+        } else if run.len() == 1 {
+            tokens.push((run[0].to_string(), true));
+        } else {
+            for pair in run.windows(2) {
+                tokens.push((pair.iter().collect(), false));
+            }
+        }
+    }
+    tokens
+}
+
+// runs of word chars (lowercased) and of cjk chars
+fn split_runs(text: &str) -> Vec<(Vec<char>, bool)> {
+    let mut runs = vec![];
+    let mut run: Vec<char> = vec![];
+    let mut run_cjk = false;
+    for c in text.chars() {
+        let cjk = is_cjk(c);
+        let word = cjk || c.is_alphanumeric() || c == '_';
+        if !run.is_empty() && (!word || cjk != run_cjk) {
+            runs.push((std::mem::take(&mut run), run_cjk));
+        }
+        if word {
+            run_cjk = cjk;
+            run.extend(c.to_lowercase());
+        }
+    }
+    if !run.is_empty() {
+        runs.push((run, run_cjk));
+    }
+    runs
+}
@@ -0,0 +1,3 @@
+pub mod fuzzy;
+pub mod index;
+pub mod query;
@@ -0,0 +1,219 @@
+use super::index::tokenize_query;
+
+// 全文検索のクエリ。
+// 空白で区切った語はすべてを含むノート(AND)。OR、NOT(または -語)、括弧も使える。
+// "..." はフレーズ、語* は前方一致、title: tag: path: でフィールドを絞る。
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Body,
+    Title,
+    Tag,
+    Path,
+}
+
+impl Field {
+    // prefix of the terms in the index. every field has one, so that a prefix
+    // query of a field doesn't run into the terms of the others.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Field::Body => "body:",
+            Field::Title => "title:",
+            Field::Tag => "tag:",
+            Field::Path => "path:",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    // tokens in a row. a token with true is matched as a prefix.
+    Phrase {
+        field: Field,
+        tokens: Vec<(String, bool)>,
+        text: String, // as typed, to find the match in the note
+    },
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lex {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+    Quoted(String, String), // field and the phrase
+}
+
+pub fn parse_query(input: &str) -> Result<Query, String> {
+    let lexes = lex(input)?;
+    let mut pos = 0;
+    let query = parse_or(&lexes, &mut pos)?;
+    match lexes.get(pos) {
+        None => Ok(query),
+        Some(Lex::Close) => Err(String::from("unbalanced )")),
+        Some(lex) => Err(format!("unexpected {:?}", lex)),
+    }
+}
+
+fn lex(input: &str) -> Result<Vec<Lex>, String> {
+    let chars = input.chars().collect::<Vec<char>>();
+    let mut lexes = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                lexes.push(Lex::Open);
+                i += 1;
+            }
+            ')' => {
+                lexes.push(Lex::Close);
+                i += 1;
+            }
+            '-' if i + 1 < chars.len() && !chars[i + 1].is_whitespace() => {
+                lexes.push(Lex::Not);
+                i += 1;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !matches!(chars[i], '(' | ')' | '"')
+                {
+                    i += 1;
+                }
+                let word = chars[start..i].iter().collect::<String>();
+                // field:"phrase" or "phrase"
+                if i < chars.len() && chars[i] == '"' && (word.is_empty() || word.ends_with(':')) {
+                    let end = chars[i + 1..]
+                        .iter()
+                        .position(|c| *c == '"')
+                        .ok_or_else(|| String::from("unclosed \""))?;
+                    let phrase = chars[i + 1..i + 1 + end].iter().collect::<String>();
+                    lexes.push(Lex::Quoted(word.trim_end_matches(':').to_string(), phrase));
+                    i += end + 2;
+                    continue;
+                }
+                if word.is_empty() {
+                    return Err(String::from("unexpected \""));
+                }
+                lexes.push(match word.as_str() {
+                    "AND" => Lex::And,
+                    "OR" => Lex::Or,
+                    "NOT" => Lex::Not,
+                    _ => Lex::Word(word),
+                });
+            }
+        }
+    }
+    Ok(lexes)
+}
+
+fn parse_or(lexes: &[Lex], pos: &mut usize) -> Result<Query, String> {
+    let mut list = vec![parse_and(lexes, pos)?];
+    while lexes.get(*pos) == Some(&Lex::Or) {
+        *pos += 1;
+        list.push(parse_and(lexes, pos)?);
+    }
+    Ok(if list.len() == 1 {
+        list.remove(0)
+    } else {
+        Query::Or(list)
+    })
+}
+
+fn parse_and(lexes: &[Lex], pos: &mut usize) -> Result<Query, String> {
+    let mut list = vec![parse_unary(lexes, pos)?];
+    loop {
+        match lexes.get(*pos) {
+            Some(Lex::And) => {
+                *pos += 1;
+                list.push(parse_unary(lexes, pos)?);
+            }
+            None | Some(Lex::Or) | Some(Lex::Close) => break,
+            Some(_) => list.push(parse_unary(lexes, pos)?),
+        }
+    }
+    Ok(if list.len() == 1 {
+        list.remove(0)
+    } else {
+        Query::And(list)
+    })
+}
+
+fn parse_unary(lexes: &[Lex], pos: &mut usize) -> Result<Query, String> {
+    let lex = lexes
+        .get(*pos)
+        .ok_or_else(|| String::from("unexpected end of query"))?;
+    *pos += 1;
+    match lex {
+        Lex::Not => Ok(Query::Not(Box::new(parse_unary(lexes, pos)?))),
+        Lex::Open => {
+            let query = parse_or(lexes, pos)?;
+            if lexes.get(*pos) != Some(&Lex::Close) {
+                return Err(String::from("unbalanced ("));
+            }
+            *pos += 1;
+            Ok(query)
+        }
+        Lex::Word(word) => {
+            let (field, text) = split_field(word);
+            let prefix = text.ends_with('*');
+            phrase(field, text.trim_end_matches('*'), prefix)
+        }
+        Lex::Quoted(field, text) => {
+            let field = match field.as_str() {
+                "" => Field::Body,
+                name => field_by_name(name).ok_or_else(|| format!("unknown field: {}", name))?,
+            };
+            phrase(field, text, false)
+        }
+        lex => Err(format!("unexpected {:?}", lex)),
+    }
+}
+
+// "title:rust" -> (Title, "rust"). unknown names are a part of the word.
+fn split_field(word: &str) -> (Field, &str) {
+    if let Some((name, text)) = word.split_once(':') {
+        if let Some(field) = field_by_name(name) {
+            return (field, text);
+        }
+    }
+    (Field::Body, word)
+}
+
+fn field_by_name(name: &str) -> Option<Field> {
+    match name {
+        "title" => Some(Field::Title),
+        "tag" => Some(Field::Tag),
+        "path" => Some(Field::Path),
+        _ => None,
+    }
+}
+
+fn phrase(field: Field, text: &str, prefix: bool) -> Result<Query, String> {
+    // tags are not split into words
+    let mut tokens = if field == Field::Tag {
+        vec![(text.trim_start_matches('#').to_lowercase(), false)]
+    } else {
+        tokenize_query(text)
+    };
+    if tokens.is_empty() || tokens[0].0.is_empty() {
+        return Err(format!("nothing to search in {:?}", text));
+    }
+    if prefix {
+        if let Some(last) = tokens.last_mut() {
+            last.1 = true;
+        }
+    }
+    Ok(Query::Phrase {
+        field,
+        tokens,
+        text: text.to_lowercase(),
+    })
+}